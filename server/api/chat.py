@@ -9,12 +9,12 @@ from fastapi.responses import StreamingResponse
 from pydantic import BaseModel, Field
 
 from server.chat import ChatEngine
-from server.constants import DATA_DIR
 from server.database.config.manager import config_manager
 from server.llm_client.client import AsyncLLMClient, get_llm_client
 from server.nlp_tools.document_processing import extract_text_from_document
 from server.schemas.chat import ChatRequest, ChatResponse
 from server.schemas.documents import VisualDocumentPage
+from server.utils.llama_models import llama_model_manager
 
 router = APIRouter()
 
@@ -81,7 +81,7 @@ def _is_local_vision_capable(config: dict) -> bool:
     """Local (Tauri) builds always run vision-capable VLMs with a projector."""
     if config.get("LLM_BASE_URL"):
         return False  # remote mode — use the normal probe/cache path
-    return any((DATA_DIR / "llm_models").glob("*mmproj*.gguf"))
+    return llama_model_manager.has_vision_projector()
 
 
 def _get_vision_capability_cache(config: dict) -> dict:
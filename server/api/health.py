@@ -0,0 +1,16 @@
+"""Unauthenticated liveness probe.
+
+The desktop process manager polls ``GET /health`` after unlock to know the
+server is accepting requests. It carries no request token, so the path is in
+``PUBLIC_PATHS``.
+"""
+
+from fastapi import APIRouter
+
+router = APIRouter()
+
+
+@router.get("/health")
+async def health_check():
+    """Return OK once the server is serving requests."""
+    return {"status": "ok"}
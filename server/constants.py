@@ -51,6 +51,59 @@ DATA_DIR, BUILD_DIR = get_app_directories()
 # Create directories if they don't exist
 DATA_DIR.mkdir(parents=True, exist_ok=True)
 
+# Settings file, written by the desktop app, pointing the models folders
+# somewhere other than DATA_DIR (e.g. an external drive)
+MODELS_DIR_FILE = "models_dir.txt"
+
+
+def get_models_dir(subdir: str, data_dir: Path | None = None) -> Path:
+    """Models folder `subdir` (llm_models, whisper_models, ...).
+
+    Lives under the directory named in models_dir.txt when that is an
+    existing absolute path, otherwise under the data directory. Mirrors
+    the desktop app's resolution so both sides see the same files.
+    """
+    data_dir = data_dir or DATA_DIR
+    root = data_dir
+    try:
+        override = (data_dir / MODELS_DIR_FILE).read_text().strip()
+    except FileNotFoundError:
+        override = ""
+    except OSError as e:
+        logger.warning("Could not read %s: %s", MODELS_DIR_FILE, e)
+        override = ""
+    if override:
+        path = Path(override)
+        if path.is_absolute() and path.is_dir():
+            root = path
+        else:
+            logger.warning(
+                "Ignoring %s: %s is not an existing absolute directory",
+                MODELS_DIR_FILE,
+                override,
+            )
+    return root / subdir
+
+
+# Directory levels below a models folder that are scanned, so models can be
+# grouped into subfolders. Matches MODEL_SCAN_DEPTH in the desktop app.
+MODEL_SCAN_DEPTH = 3
+
+
+def find_gguf_files(models_dir: Path, max_depth: int = MODEL_SCAN_DEPTH) -> list[Path]:
+    """`.gguf` files under `models_dir`, shallowest first.
+
+    Hidden files and folders are skipped, as the desktop app does.
+    """
+    found = []
+    for path in models_dir.rglob("*.gguf"):
+        parts = path.relative_to(models_dir).parts
+        if len(parts) > max_depth + 1 or any(p.startswith(".") for p in parts):
+            continue
+        if path.is_file():
+            found.append(path)
+    return sorted(found, key=lambda p: (len(p.parts), p))
+
 
 def get_temp_directory():
     """Get appropriate temporary directory based on environment"""
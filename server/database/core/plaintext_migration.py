@@ -0,0 +1,48 @@
+"""One-off encryption of a plaintext database from before encryption existed.
+
+The desktop app runs this in a separate server process, via
+``--encrypt-plaintext-db SRC DST`` with the key on stdin, and swaps the
+encrypted copy in itself.
+"""
+
+import logging
+from pathlib import Path
+
+import sqlcipher3 as sqlite3
+
+
+def _quote(value: str) -> str:
+    return "'" + value.replace("'", "''") + "'"
+
+
+def encrypt_plaintext_database(src: str, dst: str, key: str) -> None:
+    """Write an encrypted copy of the plaintext database at ``src`` to ``dst``.
+
+    Args:
+        src: Path of the unencrypted database; left untouched
+        dst: Path for the encrypted copy; must not exist yet
+        key: Encryption key, as later given to PRAGMA key
+
+    Raises:
+        ValueError: If ``src`` is not a readable plaintext database or
+            ``dst`` already exists
+    """
+    if Path(dst).exists():
+        raise ValueError(f"Refusing to overwrite {dst}")
+
+    db = sqlite3.connect(src)
+    try:
+        try:
+            db.execute("SELECT count(*) FROM sqlite_master").fetchone()
+        except sqlite3.DatabaseError:
+            raise ValueError("Source is not a plaintext database - already encrypted?") from None
+
+        # Fold any WAL content into the main file so nothing is left behind
+        db.execute("PRAGMA wal_checkpoint(TRUNCATE)")
+        db.execute(f"ATTACH DATABASE {_quote(dst)} AS encrypted KEY {_quote(key)}")
+        db.execute("SELECT sqlcipher_export('encrypted')")
+        db.execute("DETACH DATABASE encrypted")
+    finally:
+        db.close()
+
+    logging.info("Encrypted copy of plaintext database written")
@@ -3,6 +3,7 @@ if __name__ == "__main__":
 
     multiprocessing.freeze_support()
 
+import json
 import logging
 import os
 import secrets
@@ -155,6 +156,7 @@ def initialize_and_get_app():
     # Then load API submodules
     from server.api import (
         dashboard,
+        health,
         letter,
         patient,
         templates,
@@ -179,6 +181,7 @@ def initialize_and_get_app():
                 ) from e
 
     # Include routers
+    app.include_router(health.router)
     app.include_router(patient.router, prefix="/api/note")
     app.include_router(transcribe.router, prefix="/api/transcribe")
     app.include_router(dashboard.router, prefix="/api/dashboard")
@@ -295,10 +298,16 @@ def start_server_for_desktop():
     set_ports(server_port, llama_port, whisper_port, embedding_port)
 
     # Write ports and token to stdout so process manager can read them
-    print(
-        f"PORTS:{server_port},{llama_port},{whisper_port},{embedding_port}|TOKEN:{get_request_token()}",
-        flush=True,
+    ports = json.dumps(
+        {
+            "server": server_port,
+            "llama": llama_port,
+            "whisper": whisper_port,
+            "embedding": embedding_port,
+        },
+        separators=(",", ":"),
     )
+    print(f"PORTS:{ports}|TOKEN:{get_request_token()}", flush=True)
 
     config = uvicorn.Config(
         app,
@@ -314,8 +323,31 @@ def start_server_for_desktop():
     server.run()
 
 
+def encrypt_plaintext_database_from_cli(src: str, dst: str):
+    """Encrypt a plaintext database for the desktop app and exit.
+
+    Reads the key from stdin and reports ``ENCRYPTED`` or ``ERROR:<reason>``.
+    """
+    key = sys.stdin.readline().strip()
+    if not key:
+        print("ERROR:No key received on stdin", flush=True)
+        sys.exit(1)
+
+    from server.database.core.plaintext_migration import encrypt_plaintext_database
+
+    try:
+        encrypt_plaintext_database(src, dst, key)
+    except Exception as e:
+        logger.error(f"Failed to encrypt plaintext database: {e}")
+        print(f"ERROR:{e}", flush=True)
+        sys.exit(1)
+    print("ENCRYPTED", flush=True)
+
+
 if __name__ == "__main__":
-    if not IS_DOCKER:
+    if len(sys.argv) == 4 and sys.argv[1] == "--encrypt-plaintext-db":
+        encrypt_plaintext_database_from_cli(sys.argv[2], sys.argv[3])
+    elif not IS_DOCKER:
         # Desktop mode - dynamic port, single worker
         start_server_for_desktop()
     else:
@@ -0,0 +1,25 @@
+"""
+Tests for the top-level health probe used by the desktop app.
+"""
+
+from fastapi import FastAPI
+from fastapi.staticfiles import StaticFiles
+from fastapi.testclient import TestClient
+
+from server.api.health import router
+from server.middleware import LocalTokenMiddleware
+from server.utils.local_request_token import set_request_token
+
+
+def test_health_is_public_and_precedes_static_mount(tmp_path):
+    (tmp_path / "index.html").write_text("<html></html>")
+    app = FastAPI()
+    app.add_middleware(LocalTokenMiddleware)
+    app.include_router(router)
+    # Mirrors server.py, where the React build is mounted at "/"
+    app.mount("/", StaticFiles(directory=tmp_path, html=True), name="static")
+    set_request_token("test-token")
+
+    response = TestClient(app).get("/health")
+    assert response.status_code == 200
+    assert response.json() == {"status": "ok"}
@@ -0,0 +1,66 @@
+"""
+Tests for models directory resolution shared with the desktop app.
+"""
+
+from server.constants import MODELS_DIR_FILE, find_gguf_files, get_models_dir
+from server.utils.llama_models import find_llama_model, find_mmproj
+
+
+def test_models_dir_defaults_to_data_dir(tmp_path):
+    assert get_models_dir("llm_models", tmp_path) == tmp_path / "llm_models"
+
+
+def test_models_dir_follows_override(tmp_path):
+    data_dir = tmp_path / "data"
+    external = tmp_path / "external"
+    data_dir.mkdir()
+    external.mkdir()
+    (data_dir / MODELS_DIR_FILE).write_text(f"{external}\n")
+
+    assert get_models_dir("whisper_models", data_dir) == external / "whisper_models"
+
+
+def test_models_dir_ignores_invalid_override(tmp_path):
+    (tmp_path / MODELS_DIR_FILE).write_text("relative/models")
+    assert get_models_dir("llm_models", tmp_path) == tmp_path / "llm_models"
+
+    (tmp_path / MODELS_DIR_FILE).write_text(str(tmp_path / "missing"))
+    assert get_models_dir("embedding_models", tmp_path) == tmp_path / "embedding_models"
+
+
+def test_find_gguf_files_scans_subfolders_to_depth(tmp_path):
+    for relative in [
+        "top.gguf",
+        "qwen/model.gguf",
+        "a/b/c/deep.gguf",
+        "a/b/c/d/too-deep.gguf",
+        ".cache/hidden.gguf",
+        "qwen/notes.txt",
+    ]:
+        path = tmp_path / relative
+        path.parent.mkdir(parents=True, exist_ok=True)
+        path.write_text("x")
+
+    found = [p.relative_to(tmp_path).as_posix() for p in find_gguf_files(tmp_path)]
+    assert found == ["top.gguf", "qwen/model.gguf", "a/b/c/deep.gguf"]
+    assert find_gguf_files(tmp_path, 0) == [tmp_path / "top.gguf"]
+
+
+def test_subfolder_selection_and_projector(tmp_path):
+    qwen = tmp_path / "qwen"
+    qwen.mkdir()
+    (qwen / "model.gguf").write_text("x")
+    (qwen / "mmproj-model-f16.gguf").write_text("x")
+    (tmp_path / "other.gguf").write_text("x")
+
+    model = find_llama_model(tmp_path, "qwen/model.gguf")
+    assert model == (qwen / "model.gguf").resolve()
+    assert find_mmproj(model) == qwen / "mmproj-model-f16.gguf"
+
+    # Without a selection the first model found runs; its folder has no projector
+    model = find_llama_model(tmp_path, None)
+    assert model == tmp_path / "other.gguf"
+    assert find_mmproj(model) is None
+
+    # A selection outside the folder falls back to the scan
+    assert find_llama_model(tmp_path, "../escape.gguf") == tmp_path / "other.gguf"
@@ -0,0 +1,28 @@
+"""Test encrypting a plaintext database from before encryption existed."""
+
+import pytest
+import sqlcipher3 as sqlite3
+
+from server.database.core.plaintext_migration import encrypt_plaintext_database
+
+
+def test_encrypt_plaintext_database(tmp_path):
+    src = tmp_path / "plain.sqlite"
+    dst = tmp_path / "encrypted.sqlite"
+    db = sqlite3.connect(str(src))
+    db.execute("CREATE TABLE notes (body TEXT)")
+    db.execute("INSERT INTO notes VALUES ('kept')")
+    db.commit()
+    db.close()
+
+    encrypt_plaintext_database(str(src), str(dst), "test_key")
+
+    assert not dst.read_bytes().startswith(b"SQLite format 3\0")
+    db = sqlite3.connect(str(dst))
+    db.execute("PRAGMA key='test_key'")
+    assert db.execute("SELECT body FROM notes").fetchone()[0] == "kept"
+    db.close()
+
+    # The encrypted copy is not plaintext, so a second run is refused
+    with pytest.raises(ValueError):
+        encrypt_plaintext_database(str(dst), str(tmp_path / "again.sqlite"), "test_key")
@@ -13,7 +13,7 @@ import httpx
 if TYPE_CHECKING:
     from pathlib import Path
 
-from server.constants import DATA_DIR
+from server.constants import get_models_dir
 
 logger = logging.getLogger(__name__)
 
@@ -21,9 +21,6 @@ EMBEDDING_REPO = "Qwen/Qwen3-Embedding-0.6B-GGUF"
 EMBEDDING_FILENAME = "Qwen3-Embedding-0.6B-Q8_0.gguf"
 EMBEDDING_SIZE_MB = 639
 
-EMBEDDING_MODELS_DIR = DATA_DIR / "embedding_models"
-EMBEDDING_MODELS_DIR.mkdir(parents=True, exist_ok=True)
-
 
 @dataclass
 class DownloadProgress:
@@ -38,7 +35,9 @@ class DownloadProgress:
 
 def embedding_model_path() -> Path:
     """Absolute path where the embedding GGUF lives."""
-    return EMBEDDING_MODELS_DIR / EMBEDDING_FILENAME
+    models_dir = get_models_dir("embedding_models")
+    models_dir.mkdir(parents=True, exist_ok=True)
+    return models_dir / EMBEDDING_FILENAME
 
 
 def is_embedding_model_downloaded() -> bool:
@@ -9,11 +9,11 @@ import logging
 import time
 from contextlib import suppress
 from dataclasses import dataclass
-from pathlib import Path
+from pathlib import Path, PurePosixPath
 
 import httpx
 
-from server.constants import DATA_DIR
+from server.constants import DATA_DIR, find_gguf_files, get_models_dir
 
 logger = logging.getLogger(__name__)
 
@@ -117,16 +117,39 @@ PRECONFIGURED_MODELS = {
 }
 
 
+def is_mmproj(path: Path) -> bool:
+    """Whether `path` is a multimodal projector rather than a model."""
+    return "mmproj" in path.name.lower()
+
+
+def find_llama_model(models_dir: Path, selection: str | None) -> Path | None:
+    """The model the desktop app runs: the selection when it names a file
+    inside `models_dir`, otherwise the first model found."""
+    if selection:
+        root = models_dir.resolve()
+        candidate = (models_dir / selection).resolve()
+        if candidate.is_relative_to(root) and candidate.is_file():
+            return candidate
+    return next((p for p in find_gguf_files(models_dir) if not is_mmproj(p)), None)
+
+
+def find_mmproj(model: Path) -> Path | None:
+    """The projector in the model's own folder, if any."""
+    return next((p for p in find_gguf_files(model.parent, 0) if is_mmproj(p)), None)
+
+
 class LlamaModelManager:
     """Manages LLM GGUF model downloads and listing.
 
     Follows the Whisper pattern: only one model at a time.
     """
 
-    def __init__(self):
-        # Models stored in DATA_DIR/llm_models
-        self.models_dir = DATA_DIR / "llm_models"
-        self.models_dir.mkdir(parents=True, exist_ok=True)
+    @property
+    def models_dir(self) -> Path:
+        """The llm_models folder, following the models directory setting."""
+        models_dir = get_models_dir("llm_models")
+        models_dir.mkdir(parents=True, exist_ok=True)
+        return models_dir
 
     def get_available_models(self) -> list[dict]:
         """Get list of pre-configured models."""
@@ -159,8 +182,10 @@ class LlamaModelManager:
         if selection_file.exists():
             selected_filename = selection_file.read_text().strip()
 
-        for model_file in self.models_dir.glob("*.gguf"):
+        models_dir = self.models_dir
+        for model_file in find_gguf_files(models_dir):
             filename = model_file.name
+            relative = model_file.relative_to(models_dir).as_posix()
 
             # Only surface pre-configured models; ignore any other files in the dir.
             matched = next(
@@ -176,6 +201,7 @@ class LlamaModelManager:
 
             model_id, model_info = matched
             canonical = str(model_info["filename"])
+            canonical_relative = PurePosixPath(relative).with_name(canonical).as_posix()
             size_mb = round(model_file.stat().st_size / (1024 * 1024), 1)
             models.append(
                 {
@@ -186,7 +212,7 @@ class LlamaModelManager:
                     "description": model_info["description"],
                     "path": str(model_file),
                     "category": model_info["category"],
-                    "is_selected": selected_filename in (filename, canonical),
+                    "is_selected": selected_filename in (relative, canonical_relative),
                 }
             )
 
@@ -198,20 +224,25 @@ class LlamaModelManager:
             (
                 v
                 for v in PRECONFIGURED_MODELS.values()
-                if str(v["filename"]).lower() == filename.lower()
+                if str(v["filename"]).lower() == PurePosixPath(filename).name.lower()
             ),
             None,
         )
         if not info:
             return None
-        model_file = self.models_dir / info["filename"]
-        if model_file.exists():
-            return model_file
-        return None
+        return next(self._find_model_files(str(info["filename"])), None)
+
+    def _find_model_files(self, filename: str):
+        """Copies of `filename` anywhere under the models folder."""
+        return (
+            p
+            for p in find_gguf_files(self.models_dir)
+            if p.name.lower() == filename.lower()
+        )
 
     def _delete_all_models(self) -> None:
         """Delete all existing model files to ensure only one model exists."""
-        for model_file in self.models_dir.glob("*.gguf"):
+        for model_file in find_gguf_files(self.models_dir):
             try:
                 model_file.unlink()
                 logger.info(f"Deleted existing LLM model: {model_file.name}")
@@ -347,19 +378,19 @@ class LlamaModelManager:
         if not info:
             return False
 
-        model_file = self.models_dir / info["filename"]
         deleted = False
-        if model_file.exists():
+        for model_file in list(self._find_model_files(str(info["filename"]))):
             model_file.unlink()
             logger.info(f"Deleted LLM model {info['filename']}")
             deleted = True
 
-        # Also remove any companion multimodal projector files.
-        for mmproj in self.models_dir.glob("*mmproj*.gguf"):
+            # Also remove the companion multimodal projector beside it.
+            mmproj = find_mmproj(model_file)
+            if mmproj is None:
+                continue
             try:
                 mmproj.unlink()
                 logger.info(f"Deleted mmproj file: {mmproj.name}")
-                deleted = True
             except Exception as e:
                 logger.warning(f"Failed to delete {mmproj.name}: {e}")
 
@@ -403,7 +434,7 @@ class LlamaModelManager:
         if not selection_file.exists():
             return None
 
-        selected_filename = selection_file.read_text().strip()
+        selected_filename = PurePosixPath(selection_file.read_text().strip()).name
 
         # Try to map filename to model_id for pre-configured models
         for model_id, info in PRECONFIGURED_MODELS.items():
@@ -415,7 +446,16 @@ class LlamaModelManager:
 
     def ensure_default_model_exists(self) -> bool:
         """Check if any model exists."""
-        return any(self.models_dir.glob("*.gguf"))
+        return any(find_gguf_files(self.models_dir))
+
+    def has_vision_projector(self) -> bool:
+        """Whether the model the desktop app runs has a projector beside it."""
+        selection_file = self._get_model_selection_file_path()
+        selection = None
+        with suppress(OSError):
+            selection = selection_file.read_text().strip()
+        model = find_llama_model(self.models_dir, selection)
+        return model is not None and find_mmproj(model) is not None
 
 
 # Singleton instance
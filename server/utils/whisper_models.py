@@ -15,7 +15,7 @@ from typing import TypedDict
 
 import httpx
 
-from server.constants import DATA_DIR
+from server.constants import get_models_dir
 
 logger = logging.getLogger(__name__)
 
@@ -62,9 +62,12 @@ DEFAULT_MODEL_ID = "omi-med-stt-v1-q8_0"
 class WhisperModelManager:
     """Manages the Omi Med STT model download and listing."""
 
-    def __init__(self):
-        self.models_dir = DATA_DIR / "whisper_models"
-        self.models_dir.mkdir(parents=True, exist_ok=True)
+    @property
+    def models_dir(self) -> Path:
+        """The whisper_models folder, following the models directory setting."""
+        models_dir = get_models_dir("whisper_models")
+        models_dir.mkdir(parents=True, exist_ok=True)
+        return models_dir
 
     def get_available_models(self) -> list[dict]:
         """Get list of all available STT models."""
@@ -1,4 +1,80 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+#[path = "src/binary_manifest.rs"]
+mod binary_manifest;
+
+use binary_manifest::{changed_hashes, parse_manifest, Manifest, MANIFEST_FILE};
+
+/// Sidecars listed under `externalBin` in tauri.conf.json.
+const EXTERNAL_BINARIES: [&str; 3] = ["phlox-server", "phlox-llama-server", "phlox-whisper-server"];
+
 fn main() {
+    // Written first: it is listed under bundle.resources, which tauri-build
+    // requires to exist
+    if let Err(e) = record_binary_hashes() {
+        println!("cargo:warning=Could not record {}: {}", MANIFEST_FILE, e);
+    }
+
     tauri_build::build();
     println!("cargo:rerun-if-changed=../process-manager/src");
+    println!("cargo:rerun-if-changed=binaries");
+}
+
+/// Hash the sidecars tauri-build copies next to the app into
+/// `binaries.sha256`, which the bundler ships as a resource, warning when a
+/// recorded hash has changed.
+fn record_binary_hashes() -> io::Result<()> {
+    let target = std::env::var("TARGET").unwrap_or_default();
+    let exe_suffix = if target.contains("windows") {
+        ".exe"
+    } else {
+        ""
+    };
+
+    let mut current = Manifest::new();
+    for name in EXTERNAL_BINARIES {
+        let source = Path::new("binaries").join(format!("{}-{}{}", name, target, exe_suffix));
+        // tauri-build only checks for sidecars after this runs, so say which
+        // one is missing from the manifest
+        if !source.exists() {
+            println!(
+                "cargo:warning=Sidecar {} is missing; it is left out of {}",
+                source.display(),
+                MANIFEST_FILE
+            );
+            continue;
+        }
+        current.insert(format!("{}{}", name, exe_suffix), sha256_file(&source)?);
+    }
+
+    let manifest_path = Path::new(MANIFEST_FILE);
+    let previous_text = fs::read_to_string(manifest_path).ok();
+    if let Some(text) = &previous_text {
+        let previous = parse_manifest(text).unwrap_or_default();
+        for change in changed_hashes(&previous, &current) {
+            println!(
+                "cargo:warning={} changed since the last build ({} -> {}); re-sync it if that was not intended",
+                change.name, change.old, change.new
+            );
+        }
+    }
+    let text: String = current
+        .iter()
+        .map(|(name, hash)| format!("{}  {}\n", hash, name))
+        .collect();
+    // Leave an unchanged manifest alone so its mtime stays put
+    if previous_text.as_deref() == Some(text.as_str()) {
+        return Ok(());
+    }
+    fs::write(manifest_path, text)
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
 }
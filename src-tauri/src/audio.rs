@@ -0,0 +1,175 @@
+//! WAV helpers for feeding long recordings to the transcription service.
+
+/// Only 16-bit mono PCM is accepted, which is what the recorder produces.
+const BITS_PER_SAMPLE: u16 = 16;
+const CHANNELS: u16 = 1;
+const PCM_FORMAT: u16 = 1;
+const BYTES_PER_SAMPLE: usize = (BITS_PER_SAMPLE / 8) as usize;
+
+/// Split a 16-bit mono PCM WAV into WAVs of at most `max_seconds` each,
+/// written back to back into one buffer. Every chunk gets its own header,
+/// whose RIFF size marks where the next one starts; samples are never split.
+pub fn chunk_wav(wav_bytes: &[u8], max_seconds: u32) -> Result<Vec<u8>, String> {
+    if max_seconds == 0 {
+        return Err("max_seconds must be greater than zero".to_string());
+    }
+    let (sample_rate, data) = parse_wav(wav_bytes)?;
+
+    let chunk_len = sample_rate as usize * max_seconds as usize * BYTES_PER_SAMPLE;
+    let mut out = Vec::with_capacity(data.len() + WAV_HEADER_LEN * data.len().div_ceil(chunk_len));
+    for samples in data.chunks(chunk_len) {
+        write_wav(&mut out, sample_rate, samples);
+    }
+    Ok(out)
+}
+
+/// Return the sample rate and PCM data of a 16-bit mono WAV.
+fn parse_wav(bytes: &[u8]) -> Result<(u32, &[u8]), String> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err("Not a RIFF/WAVE file".to_string());
+    }
+
+    let mut sample_rate = None;
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let id = &bytes[pos..pos + 4];
+        let size = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let body_start = pos + 8;
+        let body_end = body_start.saturating_add(size).min(bytes.len());
+        let body = &bytes[body_start..body_end];
+
+        match id {
+            b"fmt " => {
+                if body.len() < 16 {
+                    return Err("WAV fmt chunk is truncated".to_string());
+                }
+                let format = u16::from_le_bytes([body[0], body[1]]);
+                let channels = u16::from_le_bytes([body[2], body[3]]);
+                let rate = u32::from_le_bytes(body[4..8].try_into().unwrap());
+                let bits = u16::from_le_bytes([body[14], body[15]]);
+                if format != PCM_FORMAT {
+                    return Err(format!("Unsupported WAV format {} (expected PCM)", format));
+                }
+                if channels != CHANNELS || bits != BITS_PER_SAMPLE {
+                    return Err(format!(
+                        "Unsupported WAV layout: {} channel(s), {}-bit (expected mono 16-bit)",
+                        channels, bits
+                    ));
+                }
+                if rate == 0 {
+                    return Err("WAV sample rate is zero".to_string());
+                }
+                sample_rate = Some(rate);
+            }
+            b"data" => {
+                let rate = sample_rate.ok_or("WAV data chunk precedes fmt chunk")?;
+                // Drop a trailing odd byte rather than emit half a sample
+                let whole = body.len() - body.len() % BYTES_PER_SAMPLE;
+                return Ok((rate, &body[..whole]));
+            }
+            _ => {}
+        }
+
+        // Chunks are padded to an even size
+        pos = body_start.saturating_add(size + size % 2);
+    }
+
+    Err("WAV has no data chunk".to_string())
+}
+
+/// Length of the canonical header written by [`write_wav`].
+const WAV_HEADER_LEN: usize = 44;
+
+/// Append a canonical 44-byte-header WAV around `samples` to `wav`.
+fn write_wav(wav: &mut Vec<u8>, sample_rate: u32, samples: &[u8]) {
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+    let byte_rate = sample_rate * block_align as u32;
+    let data_len = samples.len() as u32;
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&PCM_FORMAT.to_le_bytes());
+    wav.extend_from_slice(&CHANNELS.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&byte_rate.to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.extend_from_slice(samples);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn synthetic_wav(sample_rate: u32, seconds: u32) -> Vec<u8> {
+        let samples: Vec<u8> = (0..sample_rate * seconds)
+            .flat_map(|i| (i as i16).to_le_bytes())
+            .collect();
+        let mut wav = Vec::new();
+        write_wav(&mut wav, sample_rate, &samples);
+        wav
+    }
+
+    /// Split `chunk_wav` output the way the frontend does, by RIFF size.
+    fn split_wavs(mut bytes: &[u8]) -> Vec<&[u8]> {
+        let mut wavs = Vec::new();
+        while !bytes.is_empty() {
+            let riff_len = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+            let (wav, rest) = bytes.split_at(8 + riff_len);
+            wavs.push(wav);
+            bytes = rest;
+        }
+        wavs
+    }
+
+    #[test]
+    fn test_chunk_wav_preserves_samples() {
+        let wav = synthetic_wav(16000, 30);
+        let out = chunk_wav(&wav, 10).unwrap();
+        let chunks = split_wavs(&out);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(out.len(), wav.len() + 2 * WAV_HEADER_LEN);
+
+        let mut total = 0;
+        let mut joined = Vec::new();
+        for chunk in &chunks {
+            let (rate, data) = parse_wav(chunk).unwrap();
+            assert_eq!(rate, 16000);
+            assert_eq!(data.len() / BYTES_PER_SAMPLE, 16000 * 10);
+            total += data.len() / BYTES_PER_SAMPLE;
+            joined.extend_from_slice(data);
+        }
+        assert_eq!(total, 16000 * 30);
+        assert_eq!(joined, parse_wav(&wav).unwrap().1);
+    }
+
+    #[test]
+    fn test_chunk_wav_short_remainder() {
+        let wav = synthetic_wav(8000, 25);
+        let out = chunk_wav(&wav, 10).unwrap();
+        let lens: Vec<usize> = split_wavs(&out)
+            .iter()
+            .map(|c| parse_wav(c).unwrap().1.len() / BYTES_PER_SAMPLE)
+            .collect();
+        assert_eq!(lens, vec![80000, 80000, 40000]);
+    }
+
+    #[test]
+    fn test_chunk_wav_rejects_unsupported_input() {
+        let mut stereo = synthetic_wav(16000, 1);
+        stereo[22..24].copy_from_slice(&2u16.to_le_bytes());
+        assert!(chunk_wav(&stereo, 10).unwrap_err().contains("mono 16-bit"));
+
+        let mut eight_bit = synthetic_wav(16000, 1);
+        eight_bit[34..36].copy_from_slice(&8u16.to_le_bytes());
+        assert!(chunk_wav(&eight_bit, 10).is_err());
+
+        assert!(chunk_wav(b"not a wav file", 10).is_err());
+        assert!(chunk_wav(&synthetic_wav(16000, 1), 0).is_err());
+    }
+}
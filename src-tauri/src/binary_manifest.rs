@@ -0,0 +1,96 @@
+//! `binaries.sha256`: the SHA-256 of each bundled sidecar binary, recorded
+//! by build.rs next to the built app. Dependency-free so build.rs can
+//! include it too.
+
+use std::collections::BTreeMap;
+
+/// Manifest file name, next to the app executable.
+pub const MANIFEST_FILE: &str = "binaries.sha256";
+
+/// Binary file name to lowercase hex SHA-256.
+pub type Manifest = BTreeMap<String, String>;
+
+/// A binary whose recorded hash differs between two manifests.
+#[derive(Debug, PartialEq, Eq)]
+pub struct HashChange<'a> {
+    pub name: &'a str,
+    pub old: &'a str,
+    pub new: &'a str,
+}
+
+/// Parse `sha256sum`-style lines, `<hex>  <name>`. Blank lines are skipped.
+pub fn parse_manifest(text: &str) -> Result<Manifest, String> {
+    let mut manifest = Manifest::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (hash, name) = line.split_once(char::is_whitespace).ok_or_else(|| {
+            format!(
+                "{} line {}: expected `<sha256>  <name>`",
+                MANIFEST_FILE,
+                i + 1
+            )
+        })?;
+        let hash = hash.to_lowercase();
+        if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("{} line {}: invalid SHA-256", MANIFEST_FILE, i + 1));
+        }
+        manifest.insert(name.trim().to_string(), hash);
+    }
+    Ok(manifest)
+}
+
+/// Binaries present in both manifests whose hash changed. Binaries that
+/// were added or dropped are not reported.
+pub fn changed_hashes<'a>(previous: &'a Manifest, current: &'a Manifest) -> Vec<HashChange<'a>> {
+    previous
+        .iter()
+        .filter_map(|(name, old)| {
+            let new = current.get(name)?;
+            (new != old).then_some(HashChange { name, old, new })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+    #[test]
+    fn test_parse_manifest() {
+        let text = format!(
+            "{}  phlox-whisper-server\n\n{}  phlox-llama-server\n",
+            A,
+            B.to_uppercase()
+        );
+        let manifest = parse_manifest(&text).unwrap();
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(manifest["phlox-llama-server"], B);
+        assert_eq!(manifest["phlox-whisper-server"], A);
+
+        assert!(parse_manifest("phlox-server").is_err());
+        assert!(parse_manifest("abc123  phlox-server").is_err());
+    }
+
+    #[test]
+    fn test_changed_hashes() {
+        let previous =
+            parse_manifest(&format!("{}  llama\n{}  whisper\n{}  gone\n", A, A, A)).unwrap();
+        let current =
+            parse_manifest(&format!("{}  llama\n{}  whisper\n{}  new\n", A, B, B)).unwrap();
+        assert_eq!(
+            changed_hashes(&previous, &current),
+            vec![HashChange {
+                name: "whisper",
+                old: A,
+                new: B,
+            }]
+        );
+        assert!(changed_hashes(&current, &current).is_empty());
+    }
+}
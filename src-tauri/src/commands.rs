@@ -1,19 +1,157 @@
 use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use sysinfo::System;
-use tauri::Manager;
+use tauri::{Emitter, Manager};
+use zeroize::Zeroizing;
 
+use crate::audio;
+use crate::download::{self, ActiveDownloads, DownloadProgress};
 use crate::encryption::{self, EncryptionError};
-use crate::pm::{PmState, StatusData};
+use crate::hardware::{self, AppleSiliconInfo, GpuInfo};
+use crate::keychain;
+use crate::logging;
+use crate::pm::{self, InstallInfo, PmState, PortReport, StatusData};
+use crate::process::{self, cleanup_stale_files};
+
+/// Cached service status snapshot from the in-process supervisor, with the
+/// time it was taken.
+#[derive(Default)]
+pub struct CachedServiceStatus(pub Mutex<Option<(Instant, StatusData)>>);
+
+impl CachedServiceStatus {
+    /// How long a snapshot is served before a fresh one is taken.
+    const TTL: Duration = Duration::from_secs(2);
+
+    /// The cached snapshot if it is younger than `TTL`, otherwise a fresh one
+    /// from `refresh`, which is then cached.
+    fn get_or_refresh(&self, now: Instant, refresh: impl FnOnce() -> StatusData) -> StatusData {
+        let mut cached = self.0.lock().unwrap();
+        if let Some((taken_at, status)) = cached.as_ref() {
+            if now.saturating_duration_since(*taken_at) < Self::TTL {
+                return status.clone();
+            }
+        }
+        let status = refresh();
+        *cached = Some((now, status.clone()));
+        status
+    }
 
-/// Cached service status snapshot from the in-process supervisor.
-pub struct CachedServiceStatus(pub Mutex<Option<StatusData>>);
+    fn store(&self, status: StatusData) {
+        *self.0.lock().unwrap() = Some((Instant::now(), status));
+    }
+}
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct AppleSiliconInfo {
-    pub is_apple_silicon: bool,
-    pub generation: Option<u8>,
-    pub tier: Option<String>,
+/// Consecutive wrong-key unlock attempts, used to slow down brute forcing.
+#[derive(Default)]
+pub struct UnlockThrottle {
+    failures: u32,
+}
+
+impl UnlockThrottle {
+    /// Failures allowed before any delay kicks in.
+    const FREE_ATTEMPTS: u32 = 2;
+    const MAX_DELAY: Duration = Duration::from_secs(30);
+
+    /// Delay after `failures` consecutive failures: 0, 0, 1s, 2s, 4s, ... capped at 30s.
+    fn delay_for(failures: u32) -> Duration {
+        if failures <= Self::FREE_ATTEMPTS {
+            return Duration::ZERO;
+        }
+        let exp = (failures - Self::FREE_ATTEMPTS - 1).min(5);
+        Duration::from_secs(1 << exp).min(Self::MAX_DELAY)
+    }
+
+    /// Count a failed attempt and return how long to hold the response.
+    fn record_failure(&mut self) -> Duration {
+        self.failures = self.failures.saturating_add(1);
+        Self::delay_for(self.failures)
+    }
+
+    fn record_success(&mut self) {
+        self.failures = 0;
+    }
+}
+
+/// Stable reason for a failed command, so the frontend can branch without
+/// matching on message text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    PassphraseTooShort,
+    PassphraseRequired,
+    SamePassphrase,
+    NotPlaintextDatabase,
+    WrongPassphrase,
+    ServerNotRunning,
+    ServerNotReady,
+    ModelMissing,
+    Updating,
+    Internal,
+}
+
+impl From<pm::PmErrorKind> for ErrorCode {
+    fn from(kind: pm::PmErrorKind) -> Self {
+        match kind {
+            pm::PmErrorKind::WrongKey => ErrorCode::WrongPassphrase,
+            pm::PmErrorKind::ServerNotRunning => ErrorCode::ServerNotRunning,
+            pm::PmErrorKind::ServerNotReady => ErrorCode::ServerNotReady,
+            pm::PmErrorKind::ModelMissing => ErrorCode::ModelMissing,
+            pm::PmErrorKind::Updating => ErrorCode::Updating,
+            pm::PmErrorKind::Other => ErrorCode::Internal,
+        }
+    }
+}
+
+/// Error returned by commands that the UI needs to tell apart. Serializes
+/// as `{ code, message }`; `message` is for display.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CommandError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl CommandError {
+    /// Wrap a process manager error, prefixing the message with `context`.
+    fn pm(context: &str, error: pm::PmError) -> Self {
+        CommandError {
+            code: error.kind.into(),
+            message: format!("{}: {}", context, error),
+        }
+    }
+
+    /// An error the frontend has no special handling for.
+    fn internal(message: String) -> Self {
+        CommandError {
+            code: ErrorCode::Internal,
+            message,
+        }
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError::internal(message)
+    }
+}
+
+impl From<EncryptionError> for CommandError {
+    fn from(error: EncryptionError) -> Self {
+        let (code, message) = match error {
+            EncryptionError::PassphraseTooShort { min } => (
+                ErrorCode::PassphraseTooShort,
+                format!("Passphrase must be at least {} characters", min),
+            ),
+            EncryptionError::PassphraseRequired => {
+                (ErrorCode::PassphraseRequired, error.to_string())
+            }
+            EncryptionError::SamePassphrase => (ErrorCode::SamePassphrase, error.to_string()),
+            EncryptionError::NotPlaintext => (ErrorCode::NotPlaintextDatabase, error.to_string()),
+            EncryptionError::Migration(_) => (ErrorCode::Internal, error.to_string()),
+        };
+        CommandError { code, message }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -26,39 +164,13 @@ pub struct SystemSpecs {
     pub arch: String,
     pub apple_silicon: Option<AppleSiliconInfo>,
     pub dgpu_vram_gb: Option<f64>,
-}
-
-fn parse_apple_silicon(cpu_brand: &str) -> Option<AppleSiliconInfo> {
-    let brand = cpu_brand.trim();
-
-    // Check if it's an Apple Silicon chip
-    if !brand.starts_with("Apple ") || !brand.contains('M') {
-        return None;
-    }
-
-    // Parse "Apple M3 Pro" -> gen=3, tier="Pro"
-    // Extract the number after "M"
-    let m_pos = brand.find('M')?;
-    let after_m = &brand[m_pos + 1..];
-
-    // Parse generation (1-4, potentially more in future)
-    let gen_str: String = after_m.chars().take_while(|c| c.is_ascii_digit()).collect();
-    let generation: u8 = gen_str.parse().ok()?;
-
-    // Parse tier (Pro/Max/Ultra) - case insensitive
-    let remaining = after_m[gen_str.len()..].trim().to_lowercase();
-    let tier = match remaining.as_str() {
-        "pro" => "Pro".to_string(),
-        "max" => "Max".to_string(),
-        "ultra" => "Ultra".to_string(),
-        _ => "Base".to_string(),
-    };
-
-    Some(AppleSiliconInfo {
-        is_apple_silicon: true,
-        generation: Some(generation),
-        tier: Some(tier),
-    })
+    pub gpu: Option<GpuInfo>,
+    /// SIMD extensions from `hardware::KNOWN_CPU_FEATURES` the CPU supports
+    pub cpu_features: Vec<String>,
+    /// Space on the filesystem holding the phlox data directory
+    pub data_dir_free_gb: Option<f64>,
+    pub data_dir_total_gb: Option<f64>,
+    pub on_battery: Option<bool>,
 }
 
 /// Take a status snapshot from the in-process supervisor (reaps dead children).
@@ -89,30 +201,62 @@ fn port_from_status(status: &StatusData, service: &str) -> String {
     }
 }
 
+/// Status snapshot for the port getters, reusing a recent one if available.
+fn cached_snapshot(pm_state: &PmState, cached: &CachedServiceStatus) -> StatusData {
+    cached.get_or_refresh(Instant::now(), || snapshot_status(pm_state))
+}
+
 #[tauri::command]
-pub fn get_server_port(pm_state: tauri::State<PmState>) -> String {
-    let status = snapshot_status(&pm_state);
+pub fn get_server_port(
+    pm_state: tauri::State<PmState>,
+    cached_status: tauri::State<CachedServiceStatus>,
+) -> String {
+    let status = cached_snapshot(&pm_state, &cached_status);
     port_from_status(&status, "server")
 }
 
 #[tauri::command]
-pub fn get_llm_port(pm_state: tauri::State<PmState>) -> String {
-    let status = snapshot_status(&pm_state);
+pub fn get_llm_port(
+    pm_state: tauri::State<PmState>,
+    cached_status: tauri::State<CachedServiceStatus>,
+) -> String {
+    let status = cached_snapshot(&pm_state, &cached_status);
     port_from_status(&status, "llama")
 }
 
 #[tauri::command]
-pub fn get_whisper_port(pm_state: tauri::State<PmState>) -> String {
-    let status = snapshot_status(&pm_state);
+pub fn get_whisper_port(
+    pm_state: tauri::State<PmState>,
+    cached_status: tauri::State<CachedServiceStatus>,
+) -> String {
+    let status = cached_snapshot(&pm_state, &cached_status);
     port_from_status(&status, "whisper")
 }
 
 #[tauri::command]
-pub fn get_embedding_port(pm_state: tauri::State<PmState>) -> String {
-    let status = snapshot_status(&pm_state);
+pub fn get_embedding_port(
+    pm_state: tauri::State<PmState>,
+    cached_status: tauri::State<CachedServiceStatus>,
+) -> String {
+    let status = cached_snapshot(&pm_state, &cached_status);
     port_from_status(&status, "embedding")
 }
 
+/// Every service port from a single status snapshot.
+#[tauri::command]
+pub fn get_all_ports(
+    pm_state: tauri::State<PmState>,
+    cached_status: tauri::State<CachedServiceStatus>,
+) -> serde_json::Value {
+    let status = cached_snapshot(&pm_state, &cached_status);
+    serde_json::json!({
+        "server": port_from_status(&status, "server"),
+        "llama": port_from_status(&status, "llama"),
+        "whisper": port_from_status(&status, "whisper"),
+        "embedding": port_from_status(&status, "embedding"),
+    })
+}
+
 /// Get the request token for API authentication.
 #[tauri::command]
 pub fn get_request_token(webview: tauri::WebviewWindow, pm_state: tauri::State<PmState>) -> String {
@@ -150,7 +294,7 @@ pub fn get_service_status(
     cached_status: tauri::State<CachedServiceStatus>,
 ) -> serde_json::Value {
     let status = snapshot_status(&pm_state);
-    *cached_status.0.lock().unwrap() = Some(status.clone());
+    cached_status.store(status.clone());
 
     serde_json::json!({
         "server_running": status.server.as_ref().map(|s| s.running).unwrap_or(false),
@@ -160,34 +304,74 @@ pub fn get_service_status(
         "server_port": status.server.as_ref().map(|s| s.port).unwrap_or(5000),
         "llm_port": status.llama.as_ref().map(|s| s.port).unwrap_or(8082),
         "whisper_port": status.whisper.as_ref().map(|s| s.port).unwrap_or(8081),
-        "embedding_port": status.embedding.as_ref().map(|s| s.port).unwrap_or(8083)
+        "embedding_port": status.embedding.as_ref().map(|s| s.port).unwrap_or(8083),
+        "llm_gpu_layers": status.llama.as_ref().and_then(|s| s.gpu_layers),
+        "llm_flash_attn": status.llama.as_ref().and_then(|s| s.flash_attn),
+        "uptime_seconds": {
+            "server": status.server.as_ref().map(|s| s.uptime_seconds),
+            "llama": status.llama.as_ref().map(|s| s.uptime_seconds),
+            "whisper": status.whisper.as_ref().map(|s| s.uptime_seconds),
+            "embedding": status.embedding.as_ref().map(|s| s.uptime_seconds),
+        },
+        "model_name": {
+            "llama": status.llama.as_ref().and_then(|s| s.model_name.clone()),
+            "whisper": status.whisper.as_ref().and_then(|s| s.model_name.clone()),
+            "embedding": status.embedding.as_ref().and_then(|s| s.model_name.clone()),
+        },
+        "memory_mb": {
+            "server": status.server.as_ref().and_then(|s| s.memory_mb),
+            "llama": status.llama.as_ref().and_then(|s| s.memory_mb),
+            "whisper": status.whisper.as_ref().and_then(|s| s.memory_mb),
+            "embedding": status.embedding.as_ref().and_then(|s| s.memory_mb),
+        },
+        "cpu_percent": {
+            "server": status.server.as_ref().and_then(|s| s.cpu_percent),
+            "llama": status.llama.as_ref().and_then(|s| s.cpu_percent),
+            "whisper": status.whisper.as_ref().and_then(|s| s.cpu_percent),
+            "embedding": status.embedding.as_ref().and_then(|s| s.cpu_percent),
+        },
+        "restart_count": {
+            "server": status.server.as_ref().map_or(0, |s| s.restart_count),
+            "llama": status.llama.as_ref().map_or(0, |s| s.restart_count),
+            "whisper": status.whisper.as_ref().map_or(0, |s| s.restart_count),
+            "embedding": status.embedding.as_ref().map_or(0, |s| s.restart_count),
+        },
+        "last_exit": status.last_exit,
+        "failing_services": status.failing
     })
 }
 
+/// Exit status and stderr tail from the last time `service` died, if it has.
+#[tauri::command]
+pub fn get_last_failure(
+    pm_state: tauri::State<PmState>,
+    service: String,
+) -> Option<pm::LastFailure> {
+    pm_state.0.lock().unwrap().last_failure(&service)
+}
+
 #[tauri::command]
 pub fn restart_whisper(
     _app_handle: tauri::AppHandle,
     pm_state: tauri::State<PmState>,
-) -> Result<String, String> {
+) -> Result<String, CommandError> {
     log::info!("Restarting whisper-server...");
 
     let mut state = pm_state.0.lock().unwrap();
-    let _ = state.stop("whisper");
-
-    match state.start_whisper(None) {
+    match state.restart("whisper") {
         Ok((pid, port)) => {
             log::info!("Whisper restarted with PID: {}, port: {}", pid, port);
             Ok(format!("Whisper server restarted with PID: {}", pid))
         }
         Err(e) => {
             log::error!("Failed to restart Whisper: {}", e);
-            Err(format!("Failed to restart Whisper: {}", e))
+            Err(CommandError::pm("Failed to restart Whisper", e))
         }
     }
 }
 
 #[tauri::command]
-pub fn start_llama_service(pm_state: tauri::State<PmState>) -> Result<String, String> {
+pub fn start_llama_service(pm_state: tauri::State<PmState>) -> Result<String, CommandError> {
     log::info!("Starting llama-server...");
 
     let mut state = pm_state.0.lock().unwrap();
@@ -198,13 +382,13 @@ pub fn start_llama_service(pm_state: tauri::State<PmState>) -> Result<String, St
         }
         Err(e) => {
             log::error!("Failed to start Llama: {}", e);
-            Err(format!("Failed to start Llama: {}", e))
+            Err(CommandError::pm("Failed to start Llama", e))
         }
     }
 }
 
 #[tauri::command]
-pub fn start_whisper_service(pm_state: tauri::State<PmState>) -> Result<String, String> {
+pub fn start_whisper_service(pm_state: tauri::State<PmState>) -> Result<String, CommandError> {
     log::info!("Starting whisper-server...");
 
     let mut state = pm_state.0.lock().unwrap();
@@ -215,7 +399,7 @@ pub fn start_whisper_service(pm_state: tauri::State<PmState>) -> Result<String,
         }
         Err(e) => {
             log::error!("Failed to start Whisper: {}", e);
-            Err(format!("Failed to start Whisper: {}", e))
+            Err(CommandError::pm("Failed to start Whisper", e))
         }
     }
 }
@@ -224,26 +408,106 @@ pub fn start_whisper_service(pm_state: tauri::State<PmState>) -> Result<String,
 pub fn restart_llama(
     _app_handle: tauri::AppHandle,
     pm_state: tauri::State<PmState>,
-) -> Result<String, String> {
+) -> Result<String, CommandError> {
     log::info!("Restarting llama-server...");
 
     let mut state = pm_state.0.lock().unwrap();
-    let _ = state.stop("llama");
-
-    match state.start_llama(None) {
+    match state.restart("llama") {
         Ok((pid, port)) => {
             log::info!("Llama restarted with PID: {}, port: {}", pid, port);
             Ok(format!("Llama server restarted with PID: {}", pid))
         }
         Err(e) => {
             log::error!("Failed to restart Llama: {}", e);
-            Err(format!("Failed to restart Llama: {}", e))
+            Err(CommandError::pm("Failed to restart Llama", e))
+        }
+    }
+}
+
+/// Stop one service. A service that is already stopped is left alone.
+fn stop_service(pm_state: &PmState, service: &str, label: &str) -> Result<String, CommandError> {
+    log::info!("Stopping {}...", service);
+    match pm_state.0.lock().unwrap().stop(service) {
+        Ok(()) => Ok(format!("{} stopped", label)),
+        Err(e) => {
+            log::error!("Failed to stop {}: {}", service, e);
+            Err(CommandError::pm(&format!("Failed to stop {}", label), e))
         }
     }
 }
 
 #[tauri::command]
-pub fn start_embedding_service(pm_state: tauri::State<PmState>) -> Result<String, String> {
+pub fn stop_llama_service(pm_state: tauri::State<PmState>) -> Result<String, CommandError> {
+    stop_service(&pm_state, "llama", "Llama server")
+}
+
+#[tauri::command]
+pub fn stop_whisper_service(pm_state: tauri::State<PmState>) -> Result<String, CommandError> {
+    stop_service(&pm_state, "whisper", "Whisper server")
+}
+
+#[tauri::command]
+pub fn stop_embedding_service(pm_state: tauri::State<PmState>) -> Result<String, CommandError> {
+    stop_service(&pm_state, "embedding", "Embedding server")
+}
+
+/// Stop every managed process, including the Python server.
+#[tauri::command]
+pub fn shutdown_process_manager(pm_state: tauri::State<PmState>) -> Result<(), String> {
+    log::info!("shutdown_process_manager called");
+    pm_state.0.lock().unwrap().shutdown();
+    cleanup_stale_files();
+    Ok(())
+}
+
+/// Start a sidecar if it isn't already running. Returns its pid and port
+/// either way.
+#[tauri::command]
+pub fn ensure_service_running(
+    pm_state: tauri::State<PmState>,
+    service: String,
+) -> Result<serde_json::Value, CommandError> {
+    log::info!("ensure_service_running called for {}", service);
+    let mut state = pm_state.0.lock().unwrap();
+    let (pid, port) = state
+        .ensure_running(&service)
+        .map_err(|e| CommandError::pm(&format!("Failed to start {}", service), e))?;
+    Ok(serde_json::json!({ "pid": pid, "port": port }))
+}
+
+/// Stop llama, whisper, embedding and the Python server in that order,
+/// reporting which were stopped and which were already down.
+#[tauri::command]
+pub fn stop_all_services(pm_state: tauri::State<PmState>) -> pm::StopAllReport {
+    log::info!("stop_all_services called");
+    pm_state.0.lock().unwrap().stop_all()
+}
+
+/// Check the bundled sidecar binaries against the SHA-256 manifest
+/// recorded at build time.
+#[tauri::command]
+pub async fn verify_bundled_binaries(
+    app_handle: tauri::AppHandle,
+) -> Result<pm::BinaryVerification, String> {
+    let resource_dir = app_handle
+        .path()
+        .resource_dir()
+        .map_err(|e| format!("Could not determine resource directory: {}", e))?;
+    tauri::async_runtime::spawn_blocking(move || pm::verify_bundled_binaries(&resource_dir))
+        .await
+        .map_err(|e| format!("Verification task panicked: {}", e))?
+}
+
+/// List the processes a startup cleanup would kill, without killing any.
+/// For diagnosing orphaned or stuck processes.
+#[tauri::command]
+pub fn list_killable_processes() -> Vec<process::KillTarget> {
+    log::info!("list_killable_processes called");
+    process::list_killable_processes()
+}
+
+#[tauri::command]
+pub fn start_embedding_service(pm_state: tauri::State<PmState>) -> Result<String, CommandError> {
     log::info!("Starting embedding server...");
 
     let mut state = pm_state.0.lock().unwrap();
@@ -254,7 +518,7 @@ pub fn start_embedding_service(pm_state: tauri::State<PmState>) -> Result<String
         }
         Err(e) => {
             log::error!("Failed to start embedding: {}", e);
-            Err(format!("Failed to start embedding: {}", e))
+            Err(CommandError::pm("Failed to start embedding", e))
         }
     }
 }
@@ -263,24 +527,111 @@ pub fn start_embedding_service(pm_state: tauri::State<PmState>) -> Result<String
 pub fn restart_embedding(
     _app_handle: tauri::AppHandle,
     pm_state: tauri::State<PmState>,
-) -> Result<String, String> {
+) -> Result<String, CommandError> {
     log::info!("Restarting embedding server...");
 
     let mut state = pm_state.0.lock().unwrap();
-    let _ = state.stop("embedding");
-
-    match state.start_embedding(None) {
+    match state.restart("embedding") {
         Ok((pid, port)) => {
             log::info!("Embedding restarted with PID: {}, port: {}", pid, port);
             Ok(format!("Embedding server restarted with PID: {}", pid))
         }
         Err(e) => {
             log::error!("Failed to restart embedding: {}", e);
-            Err(format!("Failed to restart embedding: {}", e))
+            Err(CommandError::pm("Failed to restart embedding", e))
         }
     }
 }
 
+/// Compare sidecar ports with the ports the Python server allocated.
+/// With `fix`, sidecars running on the wrong port are restarted on the right one.
+#[tauri::command]
+pub fn diagnose_port_consistency(
+    pm_state: tauri::State<PmState>,
+    fix: Option<bool>,
+) -> Result<PortReport, String> {
+    let mut state = pm_state.0.lock().unwrap();
+    let report = state.diagnose_ports(fix.unwrap_or(false))?;
+    if !report.consistent {
+        log::warn!("Port mismatches: {:?}", report.mismatches);
+    }
+    Ok(report)
+}
+
+/// Report how the app was installed and where bundled resources live.
+#[tauri::command]
+pub fn get_install_type() -> InstallInfo {
+    let info = pm::detect_install();
+    log::info!(
+        "Detected install type: {:?}, resource root: {:?}",
+        info.install_type,
+        info.resource_root
+    );
+    info
+}
+
+/// Location of the app log file, so support can ask for it.
+#[tauri::command]
+pub fn get_log_path(app_handle: tauri::AppHandle) -> Result<String, String> {
+    logging::log_file_path(&app_handle).map(|path| path.to_string_lossy().into_owned())
+}
+
+/// The data directory shared with the Python server.
+#[tauri::command]
+pub fn get_data_dir() -> Result<String, String> {
+    pm::require_phlox_dir().map(|dir| dir.to_string_lossy().into_owned())
+}
+
+/// Change the log level for this session (`off` through `trace`).
+#[tauri::command]
+pub fn set_log_level(level: String) -> Result<(), String> {
+    let filter = logging::parse_level(&level)?;
+    logging::set_level(filter);
+    log::info!("Log level set to {}", filter);
+    Ok(())
+}
+
+/// Wait until a running service passes its HTTP readiness probe (model
+/// loaded), not merely until its port accepts connections.
+#[tauri::command]
+pub async fn wait_for_service_ready(
+    app_handle: tauri::AppHandle,
+    service: String,
+    timeout_seconds: Option<u64>,
+) -> Result<(), String> {
+    let probe = pm::ReadinessProbe::for_service(&service)
+        .ok_or_else(|| format!("Unknown service: {}", service))?;
+    let status = snapshot_status(&app_handle.state::<PmState>());
+    let port = match service.as_str() {
+        "llama" => status.llama.map(|s| s.port),
+        "whisper" => status.whisper.map(|s| s.port),
+        "embedding" => status.embedding.map(|s| s.port),
+        _ => status.server.map(|s| s.port),
+    }
+    .ok_or_else(|| format!("{} is not running", service))?;
+    let timeout = Duration::from_secs(timeout_seconds.unwrap_or(120));
+
+    tauri::async_runtime::spawn_blocking(move || pm::wait_for_ready(port, &probe, timeout))
+        .await
+        .map_err(|e| format!("Readiness task panicked: {}", e))?
+}
+
+/// Stop every service ahead of an app update and block restarts until relaunch.
+/// Returns once the managed processes are gone and their PID files removed.
+#[tauri::command]
+pub async fn prepare_for_update(app_handle: tauri::AppHandle) -> Result<Vec<String>, String> {
+    log::info!("prepare_for_update called");
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let pm_state = app_handle.state::<PmState>();
+        let stopped = pm_state.0.lock().unwrap().prepare_for_update();
+        cleanup_stale_files();
+        stopped.into_iter().map(String::from).collect()
+    })
+    .await
+    .map_err(|e| format!("Update preparation task panicked: {}", e))
+}
+
 #[tauri::command]
 pub fn get_system_specs() -> SystemSpecs {
     let mut sys = System::new_all();
@@ -296,12 +647,16 @@ pub fn get_system_specs() -> SystemSpecs {
         .map(|cpu| cpu.brand().to_string())
         .unwrap_or_else(|| "Unknown".to_string());
 
-    let apple_silicon = parse_apple_silicon(&cpu_brand).or_else(|| synthesize_perf_class());
+    let apple_silicon =
+        hardware::parse_apple_silicon(&cpu_brand).or_else(|| synthesize_perf_class());
 
     #[cfg(target_os = "linux")]
-    let dgpu_vram_gb = detect_dgpu_vram_mb().map(|mb| mb as f64 / 1024.0);
+    let dgpu_vram_gb = hardware::detect_dgpu_vram_mb().map(|mb| mb as f64 / 1024.0);
     #[cfg(not(target_os = "linux"))]
     let dgpu_vram_gb = None;
+    let gpu = hardware::detect_gpu(&cpu_brand, total_memory);
+    let disk_space = pm::phlox_dir().and_then(|dir| hardware::disk_space_for(&dir));
+    let to_gb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0 * 1024.0);
 
     SystemSpecs {
         total_memory_gb: total_memory,
@@ -312,13 +667,470 @@ pub fn get_system_specs() -> SystemSpecs {
         arch: std::env::consts::ARCH.to_string(),
         apple_silicon,
         dgpu_vram_gb,
+        gpu,
+        cpu_features: hardware::cpu_features(),
+        data_dir_free_gb: disk_space.map(|(free, _)| to_gb(free)),
+        data_dir_total_gb: disk_space.map(|(_, total)| to_gb(total)),
+        on_battery: hardware::on_battery_power(),
     }
 }
 
+/// A suggested model size and quant, best first.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ModelRecommendation {
+    pub tier: String,
+    pub rationale: String,
+}
+
+/// Model size/quant tiers and the memory their weights need (GB), largest first.
+const MODEL_TIERS: [(&str, f64); 5] = [
+    ("32B Q4_K_M", 20.0),
+    ("14B Q4_K_M", 9.0),
+    ("7B Q4_K_M", 4.5),
+    ("3B Q4_K_M", 2.0),
+    ("1B Q8_0", 1.3),
+];
+/// Memory kept free on top of the weights for the KV cache and compute buffers.
+const CONTEXT_HEADROOM_GB: f64 = 1.5;
+/// Share of unified memory macOS lets the GPU use.
+const APPLE_GPU_MEMORY_FRACTION: f64 = 0.65;
+/// Largest weights that stay responsive on base and Pro Apple Silicon bandwidth.
+const APPLE_BASE_MAX_WEIGHTS_GB: f64 = 10.0;
+/// Share of system RAM to spend on weights when running on the CPU.
+const CPU_MEMORY_FRACTION: f64 = 0.5;
+/// Largest weights worth suggesting for CPU inference or partial offload.
+const CPU_MAX_WEIGHTS_GB: f64 = 10.0;
+const MAX_RECOMMENDATIONS: usize = 3;
+
+/// Suggest model tiers for the given specs. Tiers that fit entirely in GPU
+/// (or unified) memory come first, then ones that need system RAM.
+fn recommend_models(
+    total_memory_gb: f64,
+    gpu: Option<&GpuInfo>,
+    apple_silicon: Option<&AppleSiliconInfo>,
+) -> Vec<ModelRecommendation> {
+    let apple_silicon = apple_silicon.filter(|a| a.is_apple_silicon);
+    let cpu_budget = total_memory_gb * CPU_MEMORY_FRACTION;
+
+    // (fast memory, where it lives, extra system RAM usable for offload)
+    let (fast_gb, fast_label, slow_gb) = if let Some(apple) = apple_silicon {
+        let mut budget = total_memory_gb * APPLE_GPU_MEMORY_FRACTION;
+        if !matches!(apple.tier.as_deref(), Some("Max") | Some("Ultra")) {
+            budget = budget.min(APPLE_BASE_MAX_WEIGHTS_GB + CONTEXT_HEADROOM_GB);
+        }
+        (budget, "unified memory", 0.0)
+    } else if let Some(vram) = gpu.and_then(|g| g.vram_gb) {
+        (vram, "VRAM", cpu_budget)
+    } else {
+        (0.0, "", cpu_budget)
+    };
+
+    let mut fast = Vec::new();
+    let mut slow = Vec::new();
+    for (tier, weights_gb) in MODEL_TIERS {
+        let needed = weights_gb + CONTEXT_HEADROOM_GB;
+        if needed <= fast_gb {
+            fast.push(ModelRecommendation {
+                tier: tier.to_string(),
+                rationale: format!(
+                    "Fits in {:.0} GB of {} with room for context",
+                    fast_gb, fast_label
+                ),
+            });
+        } else if weights_gb <= CPU_MAX_WEIGHTS_GB && needed <= fast_gb + slow_gb {
+            let rationale = if fast_gb > 0.0 {
+                "Partly offloaded to system RAM; slower but workable".to_string()
+            } else {
+                format!(
+                    "Runs on the CPU from {:.0} GB of RAM; expect slower responses",
+                    total_memory_gb
+                )
+            };
+            slow.push(ModelRecommendation {
+                tier: tier.to_string(),
+                rationale,
+            });
+        }
+    }
+
+    let mut recommendations: Vec<_> = fast
+        .into_iter()
+        .chain(slow)
+        .take(MAX_RECOMMENDATIONS)
+        .collect();
+    if recommendations.is_empty() {
+        let (tier, _) = MODEL_TIERS[MODEL_TIERS.len() - 1];
+        recommendations.push(ModelRecommendation {
+            tier: tier.to_string(),
+            rationale: "Below the recommended memory; expect slow responses".to_string(),
+        });
+    }
+    recommendations
+}
+
+/// Suggest model size/quant tiers for this machine, best first.
+#[tauri::command]
+pub fn recommend_model() -> Vec<ModelRecommendation> {
+    let specs = get_system_specs();
+    recommend_models(
+        specs.total_memory_gb,
+        specs.gpu.as_ref(),
+        specs.apple_silicon.as_ref(),
+    )
+}
+
+/// Header carrying `chunk_audio`'s chunk length in seconds.
+const MAX_SECONDS_HEADER: &str = "max-seconds";
+
+/// Split a recorded WAV into pieces of at most `max_seconds` so long
+/// recordings can be sent to the transcription service one at a time.
+///
+/// Recordings run to hundreds of megabytes, so they travel as raw bytes
+/// rather than JSON arrays: the WAV is the request body, the chunk length
+/// goes in the `max-seconds` header, and the response is the chunks back to
+/// back, each a complete WAV whose RIFF size gives its length.
+#[tauri::command]
+pub fn chunk_audio(request: tauri::ipc::Request<'_>) -> Result<tauri::ipc::Response, String> {
+    let tauri::ipc::InvokeBody::Raw(wav_bytes) = request.body() else {
+        return Err("chunk_audio expects the WAV as a raw request body".to_string());
+    };
+    let max_seconds = request
+        .headers()
+        .get(MAX_SECONDS_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u32>().ok())
+        .ok_or_else(|| format!("Missing or invalid {} header", MAX_SECONDS_HEADER))?;
+    audio::chunk_wav(wav_bytes, max_seconds).map(tauri::ipc::Response::new)
+}
+
+/// Minimum gap between `model-download-progress` events.
+const DOWNLOAD_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Download a model into the service's models directory, emitting
+/// `model-download-progress` events. An interrupted download resumes on the
+/// next call, and a given `sha256` must match before the file is kept.
+/// Returns the path of the saved model.
+#[tauri::command]
+pub async fn download_model(
+    app_handle: tauri::AppHandle,
+    url: String,
+    dest_filename: String,
+    service: String,
+    sha256: Option<String>,
+) -> Result<String, CommandError> {
+    download_model_file(
+        &app_handle,
+        &url,
+        &dest_filename,
+        service,
+        sha256.as_deref(),
+    )
+    .await
+    .map(|dest| dest.to_string_lossy().into_owned())
+    .map_err(CommandError::from)
+}
+
+/// Body of [`download_model`], shared with [`install_and_select_model`].
+async fn download_model_file(
+    app_handle: &tauri::AppHandle,
+    url: &str,
+    dest_filename: &str,
+    service: String,
+    sha256: Option<&str>,
+) -> Result<std::path::PathBuf, String> {
+    let dest_filename = pm::sanitize_model_filename(dest_filename)?.to_string();
+    let models_dir = pm::models_dir(&service)?;
+    std::fs::create_dir_all(&models_dir)
+        .map_err(|e| format!("Failed to create {:?}: {}", models_dir, e))?;
+    let dest = models_dir.join(&dest_filename);
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    {
+        let downloads = app_handle.state::<ActiveDownloads>();
+        let mut active = downloads.0.lock().unwrap();
+        if active.contains_key(&dest_filename) {
+            return Err(format!("{} is already downloading", dest_filename));
+        }
+        active.insert(dest_filename.clone(), cancelled.clone());
+    }
+    log::info!("Downloading {} for {}", dest_filename, service);
+
+    let client = tauri_plugin_http::reqwest::Client::new();
+    let mut last_emit: Option<Instant> = None;
+    let result = download::download_to(&client, url, &dest, sha256, |downloaded, total| {
+        let done = total == Some(downloaded);
+        if done || last_emit.is_none_or(|t| t.elapsed() >= DOWNLOAD_PROGRESS_INTERVAL) {
+            last_emit = Some(Instant::now());
+            let _ = app_handle.emit(
+                "model-download-progress",
+                DownloadProgress {
+                    service: service.clone(),
+                    dest_filename: dest_filename.clone(),
+                    downloaded,
+                    total,
+                },
+            );
+        }
+        !cancelled.load(Ordering::SeqCst)
+    })
+    .await;
+
+    app_handle
+        .state::<ActiveDownloads>()
+        .0
+        .lock()
+        .unwrap()
+        .remove(&dest_filename);
+
+    match result {
+        Ok(()) => {
+            log::info!("Downloaded {} to {:?}", dest_filename, dest);
+            Ok(dest)
+        }
+        Err(e) => {
+            log::error!("Failed to download {}: {}", dest_filename, e);
+            Err(e)
+        }
+    }
+}
+
+/// Download a model, select it and restart the service on it, returning
+/// once the service answers its readiness probe. A restart that fails, or
+/// whose model never becomes ready, restores the previous selection and
+/// model. Returns the new pid and port.
+#[tauri::command]
+pub async fn install_and_select_model(
+    app_handle: tauri::AppHandle,
+    service: String,
+    url: String,
+    filename: String,
+    sha256: Option<String>,
+) -> Result<serde_json::Value, CommandError> {
+    pm::ensure_model_switchable(&service)?;
+    log::info!("install_and_select_model called for {}", service);
+    download_model_file(
+        &app_handle,
+        &url,
+        &filename,
+        service.clone(),
+        sha256.as_deref(),
+    )
+    .await?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let probe = pm::ReadinessProbe::for_service(&service)
+            .ok_or_else(|| format!("Unknown service: {}", service))?;
+        let pm_state = app_handle.state::<PmState>();
+        let (pid, port) = select_until_ready(&*pm_state, &service, &filename, |port| {
+            pm::wait_for_ready(port, &probe, Duration::from_secs(120))
+        })?;
+        log::info!("{} now serving {}", service, filename);
+        Ok(serde_json::json!({ "pid": pid, "port": port }))
+    })
+    .await
+    .map_err(|e| CommandError::internal(format!("Install task panicked: {}", e)))?
+}
+
+/// Process manager calls made by [`install_and_select_model`], so tests can
+/// stand in for real services.
+trait ModelSelector {
+    fn begin_switch(&self, service: &str, filename: &str) -> Result<pm::ModelSwitch, pm::PmError>;
+    fn undo_switch(&self, switch: &pm::ModelSwitch);
+}
+
+impl ModelSelector for PmState {
+    fn begin_switch(&self, service: &str, filename: &str) -> Result<pm::ModelSwitch, pm::PmError> {
+        self.0.lock().unwrap().begin_model_switch(service, filename)
+    }
+
+    fn undo_switch(&self, switch: &pm::ModelSwitch) {
+        self.0.lock().unwrap().undo_model_switch(switch)
+    }
+}
+
+/// Switch `service` to `filename` and wait for `wait_ready` on the new port,
+/// undoing the switch if the model never becomes ready. The lock is not
+/// held while waiting. Returns the new pid and port.
+fn select_until_ready(
+    selector: &impl ModelSelector,
+    service: &str,
+    filename: &str,
+    wait_ready: impl FnOnce(u16) -> Result<(), String>,
+) -> Result<(u32, u16), CommandError> {
+    let switch = selector
+        .begin_switch(service, filename)
+        .map_err(|e| CommandError::pm("Failed to switch model", e))?;
+    if let Err(e) = wait_ready(switch.port) {
+        log::error!("{} never became ready on {}: {}", service, filename, e);
+        selector.undo_switch(&switch);
+        return Err(e.into());
+    }
+    Ok((switch.pid, switch.port))
+}
+
+/// Cancel an in-flight model download. The partial file is kept so a later
+/// `download_model` resumes it. Returns false if nothing was downloading.
+#[tauri::command]
+pub fn cancel_model_download(
+    downloads: tauri::State<ActiveDownloads>,
+    dest_filename: String,
+) -> bool {
+    match downloads.0.lock().unwrap().get(&dest_filename) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Check a model file already inside the data or models directory against a
+/// SHA-256.
+#[tauri::command]
+pub async fn verify_model_checksum(path: String, sha256: String) -> Result<bool, String> {
+    let expected = download::parse_sha256(&sha256)?;
+    let data_dir = pm::require_phlox_dir()?
+        .canonicalize()
+        .map_err(|e| format!("Data directory unavailable: {}", e))?;
+    let models_root = pm::models_root().and_then(|dir| dir.canonicalize().ok());
+    let path = std::path::Path::new(&path)
+        .canonicalize()
+        .map_err(|e| format!("Model {} not found: {}", path, e))?;
+    if !path.starts_with(&data_dir) && !models_root.is_some_and(|root| path.starts_with(root)) {
+        return Err("Model path is outside the data and models directories".to_string());
+    }
+
+    let actual = tauri::async_runtime::spawn_blocking(move || download::sha256_file(&path))
+        .await
+        .map_err(|e| format!("Checksum task panicked: {}", e))??;
+    Ok(actual == expected)
+}
+
+/// Select the LLM model by path relative to `llm_models`.
+/// The path must resolve inside the models directory.
+#[tauri::command]
+pub fn set_llm_model(filename: String) -> Result<(), CommandError> {
+    log::info!("set_llm_model called");
+    pm::select_llama_model(&filename)
+        .map(|_| ())
+        .map_err(|e| CommandError::pm("Failed to select model", e))
+}
+
+/// Switch a running service to another model and restart it in place.
+/// Returns the new pid and port.
+#[tauri::command]
+pub fn switch_model(
+    pm_state: tauri::State<PmState>,
+    service: String,
+    model_filename: String,
+) -> Result<serde_json::Value, CommandError> {
+    log::info!("switch_model called for {}", service);
+
+    let mut state = pm_state.0.lock().unwrap();
+    match state.switch_model(&service, &model_filename) {
+        Ok((pid, port)) => {
+            log::info!("{} switched models, PID: {}, port: {}", service, pid, port);
+            Ok(serde_json::json!({ "pid": pid, "port": port }))
+        }
+        Err(e) => {
+            log::error!("Failed to switch {} model: {}", service, e);
+            Err(CommandError::pm("Failed to switch model", e))
+        }
+    }
+}
+
+/// The directory holding the models folders, after applying any override.
+#[tauri::command]
+pub fn get_models_dir() -> Result<String, String> {
+    pm::require_dir(pm::models_root()).map(|dir| dir.to_string_lossy().into_owned())
+}
+
+/// Point the models folders at `path`, or back at the default when it is
+/// empty. Takes effect on the next service start.
+#[tauri::command]
+pub fn set_models_dir(path: String) -> Result<String, String> {
+    if path.trim().is_empty() {
+        log::info!("Resetting models directory to default");
+        let file = pm::require_phlox_dir()?.join(pm::MODELS_DIR_FILE);
+        if let Err(e) = std::fs::remove_file(&file) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(format!("Failed to remove {}: {}", pm::MODELS_DIR_FILE, e));
+            }
+        }
+    } else {
+        let dir = pm::validate_models_root(&path)?;
+        log::info!("Setting models directory to {:?}", dir);
+        pm::write_setting(pm::MODELS_DIR_FILE, &dir.to_string_lossy())?;
+    }
+    get_models_dir()
+}
+
+/// Whether llama-server is paced down while running on battery.
+#[tauri::command]
+pub fn get_battery_saver() -> bool {
+    pm::battery_saver_enabled()
+}
+
+/// Enable or disable battery-saver pacing. Takes effect on the next llama start.
+#[tauri::command]
+pub fn set_battery_saver(enabled: bool) -> Result<(), String> {
+    log::info!("Setting battery saver to {}", enabled);
+    pm::write_setting(
+        pm::BATTERY_SAVER_FILE,
+        if enabled { "true" } else { "false" },
+    )
+}
+
+/// Enable or disable llama-server flash attention. Takes effect on the next
+/// llama start.
+#[tauri::command]
+pub fn set_flash_attention(enabled: bool) -> Result<(), String> {
+    log::info!("Setting flash attention to {}", enabled);
+    pm::write_setting(
+        pm::LLM_FLASH_ATTN_FILE,
+        if enabled { "true" } else { "false" },
+    )
+}
+
+/// Per-service automatic restart policy
+#[tauri::command]
+pub fn get_restart_policy() -> pm::RestartPolicies {
+    pm::restart_policies()
+}
+
+/// Save the per-service restart policy. Takes effect on the next monitor pass.
+#[tauri::command]
+pub fn set_restart_policy(policy: pm::RestartPolicies) -> Result<(), String> {
+    pm::validate_restart_policies(&policy)?;
+    log::info!("Setting restart policy to {:?}", policy);
+    let json = serde_json::to_string_pretty(&policy).map_err(|e| e.to_string())?;
+    pm::write_setting(pm::RESTART_POLICY_FILE, &json)
+}
+
+/// Set how long the server gets to shut down before it is killed, in
+/// seconds. Returns the effective value after clamping.
+#[tauri::command]
+pub fn set_server_grace_period(seconds: u64) -> u64 {
+    pm::set_server_grace_period(seconds)
+}
+
+/// Set the llama-server context size in tokens, clamped to the supported
+/// range. Takes effect on the next llama start; returns the stored value.
+#[tauri::command]
+pub fn set_llm_context_size(size: u32) -> Result<u32, String> {
+    let clamped = pm::clamp_ctx_size(size);
+    if clamped != size {
+        log::warn!("Context size {} out of range, clamped to {}", size, clamped);
+    }
+    log::info!("Setting llm context size to {}", clamped);
+    pm::write_setting(pm::LLM_CTX_SIZE_FILE, &clamped.to_string())?;
+    Ok(clamped)
+}
+
 fn synthesize_perf_class() -> Option<AppleSiliconInfo> {
     #[cfg(target_os = "linux")]
     {
-        let (gen, tier) = match detect_dgpu_vram_mb() {
+        let (gen, tier) = match hardware::detect_dgpu_vram_mb() {
             Some(v) if v >= 16384 => (3u8, "Ultra"),
             Some(v) if v >= 8192 => (3u8, "Max"),
             Some(v) if v >= 4096 => (3u8, "Pro"),
@@ -337,81 +1149,31 @@ fn synthesize_perf_class() -> Option<AppleSiliconInfo> {
     }
 }
 
-#[cfg(target_os = "linux")]
-fn detect_dgpu_vram_mb() -> Option<u64> {
-    if let Some(mb) = nvidia_vram_mb() {
-        return Some(mb);
-    }
-
-    if let Ok(drm) = std::fs::read_dir("/sys/class/drm") {
-        let mut max_vram: u64 = 0;
-        for entry in drm.flatten() {
-            let name = entry.file_name().to_string_lossy().into_owned();
-            if !name.starts_with("card") || name.contains('-') {
-                continue;
-            }
-            if let Ok(s) =
-                std::fs::read_to_string(entry.path().join("device/mem_info_vram_total"))
-            {
-                if let Ok(bytes) = s.trim().parse::<u64>() {
-                    let mb = bytes / (1024 * 1024);
-                    if mb > max_vram {
-                        max_vram = mb;
-                    }
-                }
-            }
-        }
-        if max_vram >= 2048 {
-            return Some(max_vram);
-        }
-    }
-
-    if let Ok(pci) = std::fs::read_dir("/sys/bus/pci/devices") {
-        for entry in pci.flatten() {
-            let class_path = entry.path().join("class");
-            let vendor_path = entry.path().join("vendor");
-            let class_s = match std::fs::read_to_string(&class_path) {
-                Ok(s) => s,
-                Err(_) => continue,
-            };
-            let vendor_s = match std::fs::read_to_string(&vendor_path) {
-                Ok(s) => s,
-                Err(_) => continue,
-            };
-            let class_val = match u32::from_str_radix(
-                class_s.trim().trim_start_matches("0x"),
-                16,
-            ) {
-                Ok(v) => v,
-                Err(_) => continue,
-            };
-            let vendor_val = match u32::from_str_radix(
-                vendor_s.trim().trim_start_matches("0x"),
-                16,
-            ) {
-                Ok(v) => v,
-                Err(_) => continue,
-            };
-
-            if (class_val == 0x030000 || class_val == 0x030200) && vendor_val == 0x10de {
-                return Some(8 * 1024);
-            }
-        }
-    }
-
-    None
-}
-
-#[cfg(target_os = "linux")]
-fn nvidia_vram_mb() -> Option<u64> {
-    let out = std::process::Command::new("nvidia-smi")
-        .args(["--query-gpu=memory.total", "--format=csv,noheader,nounits"])
-        .output()
-        .ok()?;
-    if !out.status.success() {
-        return None;
+/// Check everything unlocking needs (bundled binaries, an LLM and a
+/// transcription model, encryption setup) in one pass, so the UI can list
+/// every problem at once.
+#[tauri::command]
+pub fn preflight_check() -> Result<serde_json::Value, String> {
+    let mut items = pm::preflight()?;
+    let encrypted = encryption::has_encryption_setup();
+    items.push(pm::PreflightItem::new(
+        "encryption",
+        encrypted,
+        None,
+        if encrypted {
+            "Encryption is set up".to_string()
+        } else {
+            "Encryption is not set up yet".to_string()
+        },
+    ));
+
+    for item in items.iter().filter(|item| !item.ok) {
+        log::warn!("Preflight: {}: {}", item.name, item.message);
     }
-    String::from_utf8_lossy(&out.stdout).trim().parse::<u64>().ok()
+    Ok(serde_json::json!({
+        "ok": items.iter().all(|item| item.ok),
+        "items": items,
+    }))
 }
 
 // ============================================================================
@@ -430,8 +1192,8 @@ pub fn has_database() -> bool {
     encryption::database_exists()
 }
 
-/// Check if passphrase is cached in keychain
-/// Always returns false since we don't use keychain caching (PHI requirement)
+/// Check if the database key is cached in the OS keychain
+/// Always false unless the user opted in to caching (off by default for PHI)
 #[tauri::command]
 pub fn has_keychain_entry() -> bool {
     encryption::has_keychain_entry()
@@ -440,28 +1202,60 @@ pub fn has_keychain_entry() -> bool {
 /// Set up encryption with a new passphrase
 /// Returns hex-encoded passphrase for immediate use with start_server_command
 #[tauri::command]
-pub fn setup_encryption(passphrase: String) -> Result<String, String> {
+pub fn setup_encryption(passphrase: String) -> Result<String, CommandError> {
     log::info!("setup_encryption called");
+    let passphrase = Zeroizing::new(passphrase);
 
-    encryption::setup_encryption(&passphrase).map_err(|e| match e {
-        EncryptionError::PassphraseTooShort => {
-            "Passphrase must be at least 12 characters".to_string()
-        }
-        _ => format!("Failed to set up encryption: {}", e),
+    Ok(encryption::setup_encryption(&passphrase)?)
+}
+
+/// Encrypt a plaintext database from before encryption existed
+/// Returns hex-encoded passphrase for immediate use with start_server_command
+#[tauri::command]
+pub async fn migrate_plaintext_database(passphrase: String) -> Result<String, CommandError> {
+    log::info!("migrate_plaintext_database called");
+    let passphrase = Zeroizing::new(passphrase);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let (hex_passphrase, backup) = encryption::migrate_plaintext_database(&passphrase)?;
+        log::info!("Unencrypted original kept at {:?}", backup);
+        Ok(hex_passphrase)
     })
+    .await
+    .map_err(|e| CommandError::internal(format!("Migration task panicked: {}", e)))?
 }
 
 /// Unlock with passphrase
 /// Returns hex-encoded passphrase for immediate use with start_server_command
 /// Note: Verification happens when Python tries to open the database
 #[tauri::command]
-pub fn unlock_with_passphrase(passphrase: String) -> Result<String, String> {
+pub fn unlock_with_passphrase(passphrase: String) -> Result<String, CommandError> {
     log::info!("unlock_with_passphrase called");
+    let passphrase = Zeroizing::new(passphrase);
 
-    encryption::unlock_with_passphrase(&passphrase).map_err(|e| match e {
-        EncryptionError::PassphraseRequired => "Passphrase required".to_string(),
-        _ => format!("Failed to unlock: {}", e),
-    })
+    Ok(encryption::unlock_with_passphrase(&passphrase)?)
+}
+
+/// Pre-validate a passphrase change
+/// Returns per-field errors so the UI can flag both inputs at once
+#[tauri::command]
+pub fn validate_passphrase_change(
+    old_passphrase: String,
+    new_passphrase: String,
+) -> encryption::PassphraseChangeValidation {
+    log::info!("validate_passphrase_change called");
+    let old_passphrase = Zeroizing::new(old_passphrase);
+    let new_passphrase = Zeroizing::new(new_passphrase);
+    encryption::validate_passphrase_change(&old_passphrase, &new_passphrase)
+}
+
+/// Estimate passphrase strength for setup feedback
+/// Never logs the passphrase itself
+#[tauri::command]
+pub fn estimate_passphrase_strength(passphrase: String) -> encryption::PassphraseStrength {
+    log::info!("estimate_passphrase_strength called");
+    let passphrase = Zeroizing::new(passphrase);
+    encryption::estimate_passphrase_strength(&passphrase)
 }
 
 /// Change passphrase (future enhancement - placeholder)
@@ -471,11 +1265,48 @@ pub fn change_passphrase(_old_passphrase: String, _new_passphrase: String) -> Re
     Err("Passphrase change is not yet implemented".to_string())
 }
 
-/// Clear keychain (no-op since we don't use keychain)
+/// Remove any cached database key from the OS keychain
 #[tauri::command]
 pub fn clear_keychain() -> Result<(), String> {
-    log::info!("clear_keychain called - no-op (no keychain used)");
-    Ok(())
+    log::info!("clear_keychain called");
+    keychain::delete()
+}
+
+/// Whether keychain caching is enabled
+#[tauri::command]
+pub fn get_keychain_caching_enabled() -> bool {
+    keychain::caching_enabled()
+}
+
+/// Opt in to (or out of) caching the database key in the OS keychain
+/// Opting out also removes any cached key
+#[tauri::command]
+pub fn set_keychain_caching_enabled(enabled: bool) -> Result<(), String> {
+    log::info!("Setting keychain caching to {}", enabled);
+    keychain::set_caching_enabled(enabled)
+}
+
+/// Unlock with the key cached in the OS keychain
+/// Returns hex-encoded passphrase for immediate use with send_passphrase_command
+#[tauri::command]
+pub fn unlock_with_keychain() -> Result<String, String> {
+    log::info!("unlock_with_keychain called");
+    if !keychain::caching_enabled() {
+        return Err("Keychain caching is disabled".to_string());
+    }
+    match keychain::load()? {
+        Some(key) => Ok(key.to_string()),
+        None => Err("No key cached in keychain".to_string()),
+    }
+}
+
+/// Which screen to open on: setup, migrating an unencrypted database,
+/// unlock, a missing cached key, unattended unlock, or a damaged database
+#[tauri::command]
+pub fn get_startup_state() -> encryption::StartupState {
+    let state = encryption::startup_state();
+    log::info!("Startup state: {:?}", state);
+    state
 }
 
 /// Get encryption setup status for UI
@@ -484,11 +1315,13 @@ pub fn get_encryption_status() -> serde_json::Value {
     let has_setup = encryption::has_encryption_setup();
     let has_db = encryption::database_exists();
     let has_keychain = encryption::has_keychain_entry();
+    let corrupt = encryption::database_file_state() == encryption::DatabaseFileState::Corrupt;
 
     serde_json::json!({
         "has_setup": has_setup,
         "has_database": has_db,
-        "has_keychain": has_keychain
+        "has_keychain": has_keychain,
+        "corrupt": corrupt
     })
 }
 
@@ -497,7 +1330,7 @@ pub fn get_encryption_status() -> serde_json::Value {
 pub async fn start_server_command(
     _app_handle: tauri::AppHandle,
     pm_state: tauri::State<'_, PmState>,
-) -> Result<String, String> {
+) -> Result<String, CommandError> {
     log::info!("start_server_command called - warming up server");
 
     let mut state = pm_state.0.lock().unwrap();
@@ -508,24 +1341,54 @@ pub async fn start_server_command(
         }
         Err(e) => {
             log::error!("Failed to start server: {}", e);
-            Err(format!("Failed to start server: {}", e))
+            Err(CommandError::pm("Failed to start server", e))
         }
     }
 }
 
+/// Frontend event for an unlock outcome: `server-ready` once `/health`
+/// answers, `server-start-timeout` when it never did. Other failures, such
+/// as a wrong passphrase, emit neither.
+fn unlock_event(result: &Result<pm::AllocatedPorts, pm::PmError>) -> Option<&'static str> {
+    match result {
+        Ok(_) => Some("server-ready"),
+        Err(e) if e.kind == pm::PmErrorKind::ServerNotReady => Some("server-start-timeout"),
+        Err(_) => None,
+    }
+}
+
 /// Send passphrase to the waiting server.
 #[tauri::command]
 pub async fn send_passphrase_command(
     app_handle: tauri::AppHandle,
     passphrase_hex: String,
-) -> Result<String, String> {
+) -> Result<String, CommandError> {
     log::info!("send_passphrase_command called");
 
     tauri::async_runtime::spawn_blocking(move || {
+        // Only cache a key the server has accepted.
+        let to_cache = keychain::caching_enabled().then(|| Zeroizing::new(passphrase_hex.clone()));
         let pm_state = app_handle.state::<PmState>();
-        let mut state = pm_state.0.lock().unwrap();
-        match state.send_passphrase(passphrase_hex) {
+        let result = pm_state.send_passphrase(passphrase_hex);
+        match (&result, unlock_event(&result)) {
+            (Ok(ports), Some(event)) => {
+                let _ = app_handle.emit(event, ports.server);
+            }
+            (Err(e), Some(event)) => {
+                let _ = app_handle.emit(event, &e.message);
+            }
+            (_, None) => {}
+        }
+        let throttle = app_handle.state::<Mutex<UnlockThrottle>>();
+        match result {
             Ok(ports) => {
+                throttle.lock().unwrap().record_success();
+                pm_state.0.lock().unwrap().reset_server_restarts();
+                if let Some(key) = to_cache {
+                    if let Err(e) = keychain::store(&key) {
+                        log::warn!("Failed to cache key in keychain: {}", e);
+                    }
+                }
                 log::info!(
                     "Server unlocked; ports: server={}, llama={}, whisper={}, embedding={}",
                     ports.server,
@@ -537,10 +1400,286 @@ pub async fn send_passphrase_command(
             }
             Err(e) => {
                 log::error!("Failed to send passphrase: {}", e);
-                Err(format!("Failed to unlock server: {}", e))
+                if e.kind == pm::PmErrorKind::WrongKey {
+                    let delay = throttle.lock().unwrap().record_failure();
+                    if !delay.is_zero() {
+                        log::warn!(
+                            "Repeated failed unlock attempts; delaying response by {:?}",
+                            delay
+                        );
+                        std::thread::sleep(delay);
+                    }
+                }
+                Err(CommandError::pm("Failed to unlock server", e))
             }
         }
     })
     .await
-    .map_err(|e| format!("Passphrase task panicked: {}", e))?
+    .map_err(|e| CommandError::internal(format!("Passphrase task panicked: {}", e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlock_events_are_exclusive() {
+        let ports = pm::AllocatedPorts {
+            server: 5000,
+            llama: 8082,
+            whisper: 8081,
+            embedding: 8083,
+            request_token: "token".to_string(),
+        };
+        assert_eq!(unlock_event(&Ok(ports)), Some("server-ready"));
+        let timeout = pm::PmError::new(
+            pm::PmErrorKind::ServerNotReady,
+            format!("{}: timed out after 30s", pm::SERVER_NOT_READY_ERROR),
+        );
+        assert_eq!(unlock_event(&Err(timeout)), Some("server-start-timeout"));
+        let wrong_key = pm::PmError::new(pm::PmErrorKind::WrongKey, pm::WRONG_KEY_ERROR);
+        assert_eq!(unlock_event(&Err(wrong_key)), None);
+        // The kind decides, not the wording.
+        let reworded = pm::PmError::from(format!("{} elsewhere", pm::SERVER_NOT_READY_ERROR));
+        assert_eq!(unlock_event(&Err(reworded)), None);
+    }
+
+    /// Records the switches made and undone instead of touching processes.
+    #[derive(Default)]
+    struct MockSelector {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl ModelSelector for MockSelector {
+        fn begin_switch(
+            &self,
+            service: &str,
+            filename: &str,
+        ) -> Result<pm::ModelSwitch, pm::PmError> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("begin {} {}", service, filename));
+            Ok(pm::ModelSwitch {
+                pid: 42,
+                port: 8082,
+                previous: Some("old.gguf".to_string()),
+                was_running: true,
+            })
+        }
+
+        fn undo_switch(&self, switch: &pm::ModelSwitch) {
+            self.calls.lock().unwrap().push(format!(
+                "undo to {:?} restart={}",
+                switch.previous, switch.was_running
+            ));
+        }
+    }
+
+    /// Serve `body` to one request with a 200.
+    fn mock_download_server(body: &'static [u8]) -> u16 {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(body).unwrap();
+        });
+        port
+    }
+
+    #[test]
+    fn test_install_rolls_back_when_model_never_ready() {
+        let dir = std::env::temp_dir().join(format!("phlox-install-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("new.gguf");
+        let port = mock_download_server(b"GGUF model bytes");
+        tauri::async_runtime::block_on(download::download_to(
+            &tauri_plugin_http::reqwest::Client::new(),
+            &format!("http://127.0.0.1:{}/new.gguf", port),
+            &dest,
+            None,
+            |_, _| true,
+        ))
+        .unwrap();
+        assert!(dest.exists());
+
+        let selector = MockSelector::default();
+        let err = select_until_ready(&selector, "llama", "new.gguf", |port| {
+            assert_eq!(port, 8082);
+            Err("Service on port 8082 not ready after 120s".to_string())
+        })
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::Internal);
+        assert!(err.message.contains("not ready"), "{}", err.message);
+        assert_eq!(
+            *selector.calls.lock().unwrap(),
+            [
+                "begin llama new.gguf",
+                "undo to Some(\"old.gguf\") restart=true"
+            ]
+        );
+
+        let selector = MockSelector::default();
+        assert_eq!(
+            select_until_ready(&selector, "llama", "new.gguf", |_| Ok(())),
+            Ok((42, 8082))
+        );
+        assert_eq!(*selector.calls.lock().unwrap(), ["begin llama new.gguf"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_command_error_codes() {
+        let err = CommandError::pm(
+            "Failed to unlock server",
+            pm::PmError::new(pm::PmErrorKind::WrongKey, pm::WRONG_KEY_ERROR),
+        );
+        assert_eq!(err.code, ErrorCode::WrongPassphrase);
+        assert_eq!(err.message, "Failed to unlock server: Wrong encryption key");
+
+        for (kind, code) in [
+            (
+                pm::PmErrorKind::ServerNotRunning,
+                ErrorCode::ServerNotRunning,
+            ),
+            (pm::PmErrorKind::ServerNotReady, ErrorCode::ServerNotReady),
+            (pm::PmErrorKind::ModelMissing, ErrorCode::ModelMissing),
+            (pm::PmErrorKind::Updating, ErrorCode::Updating),
+            (pm::PmErrorKind::Other, ErrorCode::Internal),
+        ] {
+            let error = pm::PmError::new(kind, "failed");
+            assert_eq!(CommandError::pm("ctx", error).code, code, "{:?}", kind);
+        }
+        // Untyped errors never pick up a code from their wording.
+        assert_eq!(
+            CommandError::pm("ctx", pm::PmError::from("No LLM model found")).code,
+            ErrorCode::Internal
+        );
+
+        let err = CommandError::from(EncryptionError::PassphraseTooShort { min: 12 });
+        assert_eq!(err.code, ErrorCode::PassphraseTooShort);
+        assert_eq!(err.message, "Passphrase must be at least 12 characters");
+        assert_eq!(
+            CommandError::from(EncryptionError::PassphraseRequired).code,
+            ErrorCode::PassphraseRequired
+        );
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({
+                "code": "passphrase_too_short",
+                "message": "Passphrase must be at least 12 characters",
+            })
+        );
+    }
+
+    #[test]
+    fn test_unlock_throttle_schedule() {
+        let mut throttle = UnlockThrottle::default();
+        let delays: Vec<u64> = (0..9)
+            .map(|_| throttle.record_failure().as_secs())
+            .collect();
+        assert_eq!(delays, vec![0, 0, 1, 2, 4, 8, 16, 30, 30]);
+
+        throttle.record_success();
+        assert_eq!(throttle.record_failure(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_cached_status_reused_within_ttl() {
+        let cache = CachedServiceStatus::default();
+        let start = Instant::now();
+        let mut refreshes = 0;
+
+        cache.get_or_refresh(start, || {
+            refreshes += 1;
+            StatusData::default()
+        });
+        cache.get_or_refresh(start + Duration::from_millis(1500), || {
+            refreshes += 1;
+            StatusData::default()
+        });
+        assert_eq!(refreshes, 1);
+
+        cache.get_or_refresh(start + CachedServiceStatus::TTL, || {
+            refreshes += 1;
+            StatusData::default()
+        });
+        assert_eq!(refreshes, 2);
+    }
+
+    fn tiers(recs: &[ModelRecommendation]) -> Vec<&str> {
+        recs.iter().map(|r| r.tier.as_str()).collect()
+    }
+
+    fn apple(tier: &str) -> AppleSiliconInfo {
+        AppleSiliconInfo {
+            is_apple_silicon: true,
+            generation: Some(3),
+            tier: Some(tier.to_string()),
+        }
+    }
+
+    fn nvidia(vram_gb: f64) -> GpuInfo {
+        GpuInfo {
+            name: "NVIDIA GeForce RTX".to_string(),
+            backend: hardware::GpuBackend::Cuda,
+            vram_gb: Some(vram_gb),
+        }
+    }
+
+    #[test]
+    fn test_recommend_models_apple_silicon() {
+        let max = recommend_models(64.0, None, Some(&apple("Max")));
+        assert_eq!(tiers(&max), vec!["32B Q4_K_M", "14B Q4_K_M", "7B Q4_K_M"]);
+        assert!(max[0].rationale.contains("unified memory"));
+
+        // Base and Pro chips are capped by memory bandwidth, not capacity
+        let pro = recommend_models(32.0, None, Some(&apple("Pro")));
+        assert_eq!(pro[0].tier, "14B Q4_K_M");
+
+        let base = recommend_models(8.0, None, Some(&apple("Base")));
+        assert_eq!(tiers(&base), vec!["3B Q4_K_M", "1B Q8_0"]);
+    }
+
+    #[test]
+    fn test_recommend_models_discrete_gpu() {
+        let big = recommend_models(32.0, Some(&nvidia(12.0)), None);
+        assert_eq!(tiers(&big), vec!["14B Q4_K_M", "7B Q4_K_M", "3B Q4_K_M"]);
+        assert!(big[0].rationale.contains("VRAM"));
+
+        // Tiers that fit in VRAM rank ahead of a larger one needing offload
+        let small = recommend_models(16.0, Some(&nvidia(6.0)), None);
+        assert_eq!(tiers(&small), vec!["7B Q4_K_M", "3B Q4_K_M", "1B Q8_0"]);
+    }
+
+    #[test]
+    fn test_recommend_models_cpu_only() {
+        // A synthesized perf class is not Apple Silicon and must be ignored
+        let synthesized = AppleSiliconInfo {
+            is_apple_silicon: false,
+            ..apple("Ultra")
+        };
+        let cpu = recommend_models(16.0, None, Some(&synthesized));
+        assert_eq!(tiers(&cpu), vec!["7B Q4_K_M", "3B Q4_K_M", "1B Q8_0"]);
+        assert!(cpu[0].rationale.contains("CPU"));
+
+        let tiny = recommend_models(4.0, None, None);
+        assert_eq!(tiers(&tiny), vec!["1B Q8_0"]);
+        assert!(tiny[0].rationale.contains("Below"));
+    }
 }
@@ -0,0 +1,357 @@
+//! Model downloads into the per-service models directories.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri_plugin_http::reqwest;
+
+/// Suffix of the in-progress file. It is kept on failure so the next
+/// attempt can resume with a Range request.
+const PARTIAL_SUFFIX: &str = ".part";
+
+/// Cancellation flags for in-flight downloads, keyed by destination filename.
+#[derive(Default)]
+pub struct ActiveDownloads(pub Mutex<HashMap<String, Arc<AtomicBool>>>);
+
+/// Payload of the `model-download-progress` event.
+#[derive(Serialize, Clone, Debug)]
+pub struct DownloadProgress {
+    pub service: String,
+    pub dest_filename: String,
+    pub downloaded: u64,
+    pub total: Option<u64>,
+}
+
+fn partial_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(PARTIAL_SUFFIX);
+    dest.with_file_name(name)
+}
+
+/// Normalize an expected SHA-256 to lowercase hex, rejecting anything else.
+pub fn parse_sha256(expected: &str) -> Result<String, String> {
+    let expected = expected.trim().to_lowercase();
+    if expected.len() != 64 || !expected.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("Invalid SHA-256 checksum: {:?}", expected));
+    }
+    Ok(expected)
+}
+
+/// Feed a file into `hasher` without loading it into memory.
+fn hash_file_into(path: &Path, hasher: &mut Sha256) -> Result<(), String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    io::copy(&mut BufReader::new(file), hasher)
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    Ok(())
+}
+
+/// SHA-256 of a file as lowercase hex.
+pub fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    hash_file_into(path, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Stream `url` into `dest`, resuming an earlier partial download when the
+/// server honours Range requests. The body goes to a `.part` file that is
+/// renamed into place once complete.
+///
+/// With `expected_sha256`, the body is hashed as it is written and a
+/// mismatch deletes the partial file instead of renaming it.
+///
+/// `on_progress` receives (downloaded, total) and returns `false` to cancel.
+pub async fn download_to(
+    client: &reqwest::Client,
+    url: &str,
+    dest: &Path,
+    expected_sha256: Option<&str>,
+    mut on_progress: impl FnMut(u64, Option<u64>) -> bool,
+) -> Result<(), String> {
+    let expected_sha256 = expected_sha256.map(parse_sha256).transpose()?;
+    let partial = partial_path(dest);
+    let mut existing = fs::metadata(&partial).map(|m| m.len()).unwrap_or(0);
+
+    let mut response = loop {
+        let mut request = client.get(url);
+        if existing > 0 {
+            request = request.header(reqwest::header::RANGE, format!("bytes={}-", existing));
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Download request failed: {}", e))?;
+
+        // The partial file no longer matches the remote one; start over
+        if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE && existing > 0 {
+            log::warn!("Server refused to resume {:?}, restarting", partial);
+            existing = 0;
+            continue;
+        }
+        break response;
+    };
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("Download failed with HTTP {}", status.as_u16()));
+    }
+
+    // A plain 200 means the server ignored the Range header
+    let resumed = existing > 0 && status == reqwest::StatusCode::PARTIAL_CONTENT;
+    let mut downloaded = if resumed { existing } else { 0 };
+    let total = response.content_length().map(|len| len + downloaded);
+    if resumed {
+        log::info!("Resuming {:?} at {} bytes", dest, existing);
+    }
+
+    let mut file = if resumed {
+        OpenOptions::new().append(true).open(&partial)
+    } else {
+        File::create(&partial)
+    }
+    .map_err(|e| format!("Failed to open {:?}: {}", partial, e))?;
+
+    let mut hasher = expected_sha256.as_ref().map(|_| Sha256::new());
+    if let (Some(hasher), true) = (hasher.as_mut(), resumed) {
+        hash_file_into(&partial, hasher)?;
+    }
+
+    if !on_progress(downloaded, total) {
+        return Err("Download cancelled".to_string());
+    }
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Download interrupted: {}", e))?
+    {
+        file.write_all(&chunk)
+            .map_err(|e| format!("Failed to write {:?}: {}", partial, e))?;
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&chunk);
+        }
+        downloaded += chunk.len() as u64;
+        if !on_progress(downloaded, total) {
+            return Err("Download cancelled".to_string());
+        }
+    }
+    file.sync_all()
+        .map_err(|e| format!("Failed to flush {:?}: {}", partial, e))?;
+    drop(file);
+
+    if let Some(total) = total {
+        if downloaded != total {
+            return Err(format!(
+                "Download incomplete: {} of {} bytes",
+                downloaded, total
+            ));
+        }
+    }
+
+    if let (Some(hasher), Some(expected)) = (hasher, expected_sha256) {
+        let actual = hex::encode(hasher.finalize());
+        if actual != expected {
+            let _ = fs::remove_file(&partial);
+            return Err(format!(
+                "Checksum mismatch: expected {}, got {}",
+                expected, actual
+            ));
+        }
+    }
+
+    fs::rename(&partial, dest).map_err(|e| format!("Failed to move {:?} into place: {}", dest, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+
+    /// Serve `body` once, honouring a `Range: bytes=N-` header with a 206.
+    /// Returns the port and a handle yielding the Range header seen, if any.
+    fn mock_file_server(body: Vec<u8>) -> (u16, std::thread::JoinHandle<Option<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut range = None;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+                if let Some(value) = line.to_lowercase().strip_prefix("range: bytes=") {
+                    range = Some(value.trim().trim_end_matches('-').to_string());
+                }
+            }
+
+            let start: usize = range.as_deref().map_or(0, |r| r.parse().unwrap());
+            let status = if start > 0 {
+                "206 Partial Content"
+            } else {
+                "200 OK"
+            };
+            let rest = &body[start..];
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                rest.len()
+            )
+            .unwrap();
+            stream.write_all(rest).unwrap();
+            range
+        });
+        (port, handle)
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("phlox-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_download_reports_content_length_progress() {
+        let body: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let (port, server) = mock_file_server(body.clone());
+        let dir = temp_dir("download");
+        let dest = dir.join("model.gguf");
+
+        let mut progress = Vec::new();
+        tauri::async_runtime::block_on(download_to(
+            &reqwest::Client::new(),
+            &format!("http://127.0.0.1:{}/model.gguf", port),
+            &dest,
+            None,
+            |downloaded, total| {
+                progress.push((downloaded, total));
+                true
+            },
+        ))
+        .unwrap();
+
+        assert_eq!(server.join().unwrap(), None);
+        assert_eq!(progress.first(), Some(&(0, Some(200_000))));
+        assert_eq!(progress.last(), Some(&(200_000, Some(200_000))));
+        assert!(progress.windows(2).all(|w| w[0].0 <= w[1].0));
+        assert_eq!(fs::read(&dest).unwrap(), body);
+        assert!(!partial_path(&dest).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_download_resumes_partial_file() {
+        let body: Vec<u8> = (0..50_000u32).map(|i| (i * 7) as u8).collect();
+        let (port, server) = mock_file_server(body.clone());
+        let dir = temp_dir("resume");
+        let dest = dir.join("model.gguf");
+        fs::write(partial_path(&dest), &body[..20_000]).unwrap();
+
+        let mut first = None;
+        tauri::async_runtime::block_on(download_to(
+            &reqwest::Client::new(),
+            &format!("http://127.0.0.1:{}/model.gguf", port),
+            &dest,
+            None,
+            |downloaded, total| {
+                first.get_or_insert((downloaded, total));
+                true
+            },
+        ))
+        .unwrap();
+
+        assert_eq!(server.join().unwrap().as_deref(), Some("20000"));
+        assert_eq!(first, Some((20_000, Some(50_000))));
+        assert_eq!(fs::read(&dest).unwrap(), body);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_cancelled_download_keeps_partial() {
+        let (port, server) = mock_file_server(vec![1u8; 100_000]);
+        let dir = temp_dir("cancel");
+        let dest = dir.join("model.gguf");
+
+        let result = tauri::async_runtime::block_on(download_to(
+            &reqwest::Client::new(),
+            &format!("http://127.0.0.1:{}/model.gguf", port),
+            &dest,
+            None,
+            |downloaded, _| downloaded == 0,
+        ));
+
+        assert_eq!(result.unwrap_err(), "Download cancelled");
+        assert!(!dest.exists());
+        assert!(partial_path(&dest).exists());
+        let _ = server.join();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// SHA-256 of "abc" (FIPS 180-2 test vector).
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn test_sha256_file_known_hash() {
+        let dir = temp_dir("sha256");
+        let path = dir.join("abc.bin");
+        fs::write(&path, b"abc").unwrap();
+        assert_eq!(sha256_file(&path).unwrap(), ABC_SHA256);
+        assert_eq!(
+            parse_sha256(&ABC_SHA256.to_uppercase()).unwrap(),
+            ABC_SHA256
+        );
+        assert!(parse_sha256("abc").is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_download_verifies_checksum_across_resume() {
+        let (port, server) = mock_file_server(b"abc".to_vec());
+        let dir = temp_dir("checksum");
+        let dest = dir.join("model.gguf");
+        fs::write(partial_path(&dest), b"a").unwrap();
+
+        tauri::async_runtime::block_on(download_to(
+            &reqwest::Client::new(),
+            &format!("http://127.0.0.1:{}/model.gguf", port),
+            &dest,
+            Some(ABC_SHA256),
+            |_, _| true,
+        ))
+        .unwrap();
+        assert_eq!(server.join().unwrap().as_deref(), Some("1"));
+        assert_eq!(fs::read(&dest).unwrap(), b"abc");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_download_checksum_mismatch_deletes_partial() {
+        let (port, server) = mock_file_server(b"abd".to_vec());
+        let dir = temp_dir("mismatch");
+        let dest = dir.join("model.gguf");
+
+        let err = tauri::async_runtime::block_on(download_to(
+            &reqwest::Client::new(),
+            &format!("http://127.0.0.1:{}/model.gguf", port),
+            &dest,
+            Some(ABC_SHA256),
+            |_, _| true,
+        ))
+        .unwrap_err();
+        let _ = server.join();
+
+        assert!(err.contains("Checksum mismatch"), "{}", err);
+        assert!(!dest.exists());
+        assert!(!partial_path(&dest).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
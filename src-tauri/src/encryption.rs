@@ -2,29 +2,115 @@
 //
 // SQLCipher handles all key derivation internally using PBKDF2-HMAC-SHA512.
 // This module just provides hex encoding for the passphrase.
+//
+// Callers own the plaintext and hex buffers and should hold them in
+// `zeroize::Zeroizing` so they are scrubbed on drop; the hex string handed to
+// the server is the only key material that should outlive a command.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use serde::Serialize;
 use thiserror::Error;
 
+use crate::keychain;
+use crate::pm;
+
 // =============================================================================
 // Error Types
 // =============================================================================
 #[derive(Error, Debug)]
 pub enum EncryptionError {
-    #[error("Passphrase too short (min 12 characters)")]
-    PassphraseTooShort,
+    #[error("Passphrase too short (min {min} characters)")]
+    PassphraseTooShort { min: usize },
     #[error("Passphrase required")]
     PassphraseRequired,
+    #[error("New passphrase must differ from the current passphrase")]
+    SamePassphrase,
+    #[error("No unencrypted database to migrate")]
+    NotPlaintext,
+    #[error("Database migration failed: {0}")]
+    Migration(String),
+}
+
+/// Per-field outcome of [`validate_passphrase_change`], shaped for the UI
+#[derive(Serialize, Debug)]
+pub struct PassphraseChangeValidation {
+    pub valid: bool,
+    pub old_passphrase_error: Option<String>,
+    pub new_passphrase_error: Option<String>,
+}
+
+/// Rough passphrase strength estimate, shaped for the UI
+#[derive(Serialize, Debug)]
+pub struct PassphraseStrength {
+    /// 0 (very weak) to 4 (strong), using the zxcvbn guess thresholds
+    pub score: u8,
+    pub guesses_log10: f64,
+    pub warnings: Vec<String>,
+}
+
+/// Minimum passphrase length, in characters
+pub const MIN_PASSPHRASE_LEN: usize = 12;
+
+/// Structural state of the database file, checked without decrypting it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatabaseFileState {
+    Missing,
+    Valid,
+    /// An unencrypted database from before encryption existed
+    Plaintext,
+    Corrupt,
 }
 
+/// Which screen the app should open on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum StartupState {
+    /// No database yet: first run, choose a passphrase
+    NeedsSetup,
+    /// Unencrypted database from an older version; choose a passphrase to
+    /// encrypt it with
+    NeedsMigration,
+    /// Database present; ask for the passphrase
+    NeedsUnlock,
+    /// Keychain caching is on but the cached key is gone, so the user has to
+    /// type the passphrase they may have stopped remembering
+    KeyMissingButDbPresent,
+    /// Database present and its key cached; unlock without prompting
+    Ready,
+    /// The database file is structurally damaged
+    DatabaseCorrupt,
+}
+
+/// SQLite page sizes are powers of two from 512 bytes up, so an intact
+/// database file is always a whole multiple of this
+const MIN_PAGE_SIZE: u64 = 512;
+
+/// Start of every unencrypted SQLite file; SQLCipher files begin with a
+/// random salt instead
+const PLAINTEXT_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Fragments that make a passphrase trivially guessable
+const COMMON_FRAGMENTS: &[&str] = &[
+    "password",
+    "passphrase",
+    "qwerty",
+    "asdf",
+    "letmein",
+    "welcome",
+    "admin",
+    "phlox",
+    "iloveyou",
+    "monkey",
+    "dragon",
+];
+
 // =============================================================================
 // Core Functions
 // =============================================================================
 
-/// Get the platform-specific data directory
-pub fn get_data_dir() -> Option<std::path::PathBuf> {
-    dirs::data_dir().map(|d| d.join("Phlox"))
-}
-
 /// Check if encryption has been set up (database file exists)
 pub fn has_encryption_setup() -> bool {
     database_exists()
@@ -32,17 +118,183 @@ pub fn has_encryption_setup() -> bool {
 
 /// Check if database file exists
 pub fn database_exists() -> bool {
-    if let Some(data_dir) = get_data_dir() {
+    if let Some(data_dir) = pm::phlox_dir() {
         let db_path = data_dir.join("phlox_database.sqlite");
         return db_path.exists();
     }
     false
 }
 
-/// Check if passphrase is cached in keychain
-/// Always returns false since we don't use keychain caching
+/// Structurally validate the database file (size, plus the header of an
+/// unencrypted one; SQLCipher files have no readable header to check)
+pub fn database_file_state() -> DatabaseFileState {
+    match pm::phlox_dir() {
+        Some(data_dir) => check_database_file(&data_dir.join("phlox_database.sqlite")),
+        None => DatabaseFileState::Missing,
+    }
+}
+
+fn check_database_file(db_path: &std::path::Path) -> DatabaseFileState {
+    let len = match std::fs::metadata(db_path) {
+        Ok(meta) => meta.len(),
+        Err(_) => return DatabaseFileState::Missing,
+    };
+
+    if len == 0 {
+        // An empty main file is fine while every page still lives in the WAL
+        let wal = db_path.with_extension("sqlite-wal");
+        let wal_has_data = std::fs::metadata(wal).map(|m| m.len() > 0).unwrap_or(false);
+        return if wal_has_data {
+            DatabaseFileState::Valid
+        } else {
+            DatabaseFileState::Corrupt
+        };
+    }
+
+    if len % MIN_PAGE_SIZE != 0 {
+        log::warn!("Database file size {} is not a whole number of pages", len);
+        return DatabaseFileState::Corrupt;
+    }
+
+    if is_plaintext_database(db_path) {
+        return DatabaseFileState::Plaintext;
+    }
+    DatabaseFileState::Valid
+}
+
+/// Check whether the database file is an unencrypted SQLite database
+pub fn is_plaintext_database(db_path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    File::open(db_path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok()
+        && &header == PLAINTEXT_HEADER
+}
+
+// =============================================================================
+// Plaintext Migration
+// =============================================================================
+
+/// Encrypt a plaintext database from before encryption existed
+/// Returns the hex key for start_server_command and the backup of the
+/// original, which is left for the user to delete
+pub fn migrate_plaintext_database(passphrase: &str) -> Result<(String, PathBuf), EncryptionError> {
+    let hex_passphrase = setup_encryption(passphrase)?;
+    let data_dir = pm::require_phlox_dir().map_err(EncryptionError::Migration)?;
+    let backup = migrate_plaintext_database_with(
+        &data_dir.join("phlox_database.sqlite"),
+        &hex_passphrase,
+        pm::encrypt_plaintext_database,
+    )?;
+    Ok((hex_passphrase, backup))
+}
+
+/// Encrypt the plaintext database at `db_path` with `encrypt`, which writes
+/// an encrypted copy to its second argument, then swap the copy in
+/// The original is moved to `backups/` rather than deleted
+pub fn migrate_plaintext_database_with(
+    db_path: &Path,
+    hex_key: &str,
+    encrypt: impl FnOnce(&Path, &Path, &str) -> Result<(), String>,
+) -> Result<PathBuf, EncryptionError> {
+    if !is_plaintext_database(db_path) {
+        return Err(EncryptionError::NotPlaintext);
+    }
+
+    let encrypted = db_path.with_extension("sqlite.encrypting");
+    // Left over from an interrupted attempt; the original is still in place
+    let _ = fs::remove_file(&encrypted);
+
+    let result = encrypt(db_path, &encrypted, hex_key).and_then(|()| {
+        let written = fs::metadata(&encrypted)
+            .map(|m| m.len() > 0)
+            .unwrap_or(false);
+        if !written || is_plaintext_database(&encrypted) {
+            return Err("Encrypted copy was not written".to_string());
+        }
+        discard_plaintext_sidecars(db_path)
+    });
+    if let Err(e) = result {
+        let _ = fs::remove_file(&encrypted);
+        return Err(EncryptionError::Migration(e));
+    }
+
+    let backup_dir = db_path.parent().unwrap_or(Path::new(".")).join("backups");
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let backup = backup_dir.join(format!("phlox_database.plaintext-{}.sqlite", stamp));
+
+    let moved = fs::create_dir_all(&backup_dir).and_then(|()| fs::rename(db_path, &backup));
+    if let Err(e) = moved {
+        let _ = fs::remove_file(&encrypted);
+        return Err(EncryptionError::Migration(format!(
+            "Failed to back up the original database: {}",
+            e
+        )));
+    }
+    if let Err(e) = fs::rename(&encrypted, db_path) {
+        let _ = fs::rename(&backup, db_path);
+        let _ = fs::remove_file(&encrypted);
+        return Err(EncryptionError::Migration(format!(
+            "Failed to swap in the encrypted database: {}",
+            e
+        )));
+    }
+
+    log::info!(
+        "Plaintext database encrypted; original moved to {:?}",
+        backup
+    );
+    Ok(backup)
+}
+
+/// Remove the plaintext database's `-shm` and empty `-wal` files, which
+/// would otherwise sit next to the encrypted one. A WAL still holding pages
+/// means the encrypted copy may be missing them.
+fn discard_plaintext_sidecars(db_path: &Path) -> Result<(), String> {
+    let wal = db_path.with_extension("sqlite-wal");
+    if fs::metadata(&wal).map(|m| m.len() > 0).unwrap_or(false) {
+        return Err("Unencrypted database still has unflushed changes".to_string());
+    }
+    let _ = fs::remove_file(wal);
+    let _ = fs::remove_file(db_path.with_extension("sqlite-shm"));
+    Ok(())
+}
+
+/// Decide the startup screen from the database file and the keychain
+pub fn startup_state_from(
+    database: DatabaseFileState,
+    caching_enabled: bool,
+    key_cached: bool,
+) -> StartupState {
+    match database {
+        // A cached key without a database is stale; setup replaces it
+        DatabaseFileState::Missing => StartupState::NeedsSetup,
+        DatabaseFileState::Corrupt => StartupState::DatabaseCorrupt,
+        // No key opens it yet, cached or typed
+        DatabaseFileState::Plaintext => StartupState::NeedsMigration,
+        DatabaseFileState::Valid if !caching_enabled => StartupState::NeedsUnlock,
+        DatabaseFileState::Valid if key_cached => StartupState::Ready,
+        DatabaseFileState::Valid => StartupState::KeyMissingButDbPresent,
+    }
+}
+
+/// The startup screen for the current data directory and keychain
+pub fn startup_state() -> StartupState {
+    let database = database_file_state();
+    let caching_enabled = keychain::caching_enabled();
+    // Skip the keychain lookup when it can't change the answer
+    let key_cached =
+        caching_enabled && database == DatabaseFileState::Valid && keychain::has_entry();
+    startup_state_from(database, caching_enabled, key_cached)
+}
+
+/// Check if the database key is cached in the OS keychain
+/// Only possible when the user opted in; caching is off by default (PHI)
 pub fn has_keychain_entry() -> bool {
-    false
+    keychain::caching_enabled() && keychain::has_entry()
 }
 
 /// Convert a string passphrase to hex for SQLCipher
@@ -56,8 +308,10 @@ pub fn passphrase_to_hex(passphrase: &str) -> String {
 pub fn setup_encryption(passphrase: &str) -> Result<String, EncryptionError> {
     log::info!("setup_encryption called");
 
-    if passphrase.len() < 12 {
-        return Err(EncryptionError::PassphraseTooShort);
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(EncryptionError::PassphraseTooShort {
+            min: MIN_PASSPHRASE_LEN,
+        });
     }
 
     let hex_passphrase = passphrase_to_hex(passphrase);
@@ -82,10 +336,199 @@ pub fn unlock_with_passphrase(passphrase: &str) -> Result<String, EncryptionErro
     Ok(hex_passphrase)
 }
 
+/// Pre-validate a passphrase change before anything is attempted
+/// The old passphrase can only be checked for presence here; SQLCipher
+/// verifies it when the server re-opens the database
+pub fn validate_passphrase_change(old: &str, new: &str) -> PassphraseChangeValidation {
+    let old_error = if old.is_empty() {
+        Some(EncryptionError::PassphraseRequired)
+    } else {
+        None
+    };
+
+    let new_error = if new.chars().count() < MIN_PASSPHRASE_LEN {
+        Some(EncryptionError::PassphraseTooShort {
+            min: MIN_PASSPHRASE_LEN,
+        })
+    } else if new == old {
+        Some(EncryptionError::SamePassphrase)
+    } else {
+        None
+    };
+
+    PassphraseChangeValidation {
+        valid: old_error.is_none() && new_error.is_none(),
+        old_passphrase_error: old_error.map(|e| e.to_string()),
+        new_passphrase_error: new_error.map(|e| e.to_string()),
+    }
+}
+
+/// Estimate passphrase strength from length, character classes, repetition,
+/// sequences and a handful of common fragments. Runs locally without a
+/// dictionary, so treat the score as guidance rather than a guarantee
+pub fn estimate_passphrase_strength(passphrase: &str) -> PassphraseStrength {
+    let chars: Vec<char> = passphrase.chars().collect();
+    let mut warnings = Vec::new();
+
+    let has_lower = chars.iter().any(|c| c.is_ascii_lowercase());
+    let has_upper = chars.iter().any(|c| c.is_ascii_uppercase());
+    let has_digit = chars.iter().any(|c| c.is_ascii_digit());
+    let has_symbol = chars
+        .iter()
+        .any(|c| c.is_ascii() && !c.is_ascii_alphanumeric());
+    let has_other = chars.iter().any(|c| !c.is_ascii());
+    let pool = [
+        (has_lower, 26),
+        (has_upper, 26),
+        (has_digit, 10),
+        (has_symbol, 33),
+        (has_other, 100),
+    ]
+    .iter()
+    .filter(|(present, _)| *present)
+    .map(|(_, size)| size)
+    .sum::<u32>()
+    .max(1);
+
+    // Characters that repeat or continue a sequence from the previous one add
+    // almost nothing for an attacker
+    let mut effective_len = 0.0;
+    let mut repeat_run = 1;
+    let mut sequence_run = 1;
+    let mut saw_repeat = false;
+    let mut saw_sequence = false;
+    for (i, &c) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|j| chars[j]);
+        let step = prev.map(|p| c as i64 - p as i64);
+        repeat_run = if step == Some(0) { repeat_run + 1 } else { 1 };
+        sequence_run = if matches!(step, Some(1) | Some(-1)) {
+            sequence_run + 1
+        } else {
+            1
+        };
+        saw_repeat |= repeat_run >= 3;
+        saw_sequence |= sequence_run >= 3;
+        effective_len += if repeat_run > 1 || sequence_run > 1 {
+            0.1
+        } else {
+            1.0
+        };
+    }
+
+    let lowered = passphrase.to_lowercase();
+    let common: Vec<&str> = COMMON_FRAGMENTS
+        .iter()
+        .copied()
+        .filter(|f| lowered.contains(f))
+        .collect();
+    for fragment in &common {
+        // Count the fragment as a single guess-worthy token instead of its letters
+        effective_len -= fragment.chars().count() as f64 - 1.0;
+    }
+
+    let guesses_log10 = (effective_len.max(0.0) * (pool as f64).log10() * 100.0).round() / 100.0;
+    let score = match guesses_log10 {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    };
+
+    if chars.len() < MIN_PASSPHRASE_LEN {
+        warnings.push(format!("Use at least {} characters", MIN_PASSPHRASE_LEN));
+    }
+    if [has_lower, has_upper, has_digit, has_symbol, has_other]
+        .iter()
+        .filter(|b| **b)
+        .count()
+        == 1
+    {
+        warnings.push("Mix in other kinds of characters, or use more words".to_string());
+    }
+    if saw_repeat {
+        warnings.push("Repeated characters like \"aaa\" are easy to guess".to_string());
+    }
+    if saw_sequence {
+        warnings.push("Sequences like \"abc\" or \"123\" are easy to guess".to_string());
+    }
+    if !common.is_empty() {
+        warnings.push("Avoid common words like \"password\"".to_string());
+    }
+
+    PassphraseStrength {
+        score,
+        guesses_log10,
+        warnings,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn plaintext_db(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("phlox-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("phlox_database.sqlite");
+        let mut page = PLAINTEXT_HEADER.to_vec();
+        page.resize(MIN_PAGE_SIZE as usize, 0);
+        fs::write(&db_path, page).unwrap();
+        db_path
+    }
+
+    #[test]
+    fn test_migrate_plaintext_database() {
+        let db_path = plaintext_db("migrate");
+        fs::write(db_path.with_extension("sqlite-shm"), b"shm").unwrap();
+
+        let backup = migrate_plaintext_database_with(&db_path, "6b6579", |src, dst, key| {
+            assert!(is_plaintext_database(src));
+            assert_eq!(key, "6b6579");
+            fs::write(dst, [0x5a; MIN_PAGE_SIZE as usize]).map_err(|e| e.to_string())
+        })
+        .unwrap();
+
+        assert!(!is_plaintext_database(&db_path));
+        assert_eq!(fs::read(&db_path).unwrap(), [0x5a; MIN_PAGE_SIZE as usize]);
+        assert!(is_plaintext_database(&backup));
+        assert!(!db_path.with_extension("sqlite-shm").exists());
+        assert!(!db_path.with_extension("sqlite.encrypting").exists());
+
+        // Already encrypted: the server step is never reached
+        let again = migrate_plaintext_database_with(&db_path, "6b6579", |_, _, _| {
+            panic!("encrypted twice")
+        });
+        assert!(matches!(again, Err(EncryptionError::NotPlaintext)));
+
+        fs::remove_dir_all(db_path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_failed_migration_keeps_original() {
+        let db_path = plaintext_db("migrate-fail");
+        let original = fs::read(&db_path).unwrap();
+
+        let result = migrate_plaintext_database_with(&db_path, "6b6579", |_, dst, _| {
+            fs::write(dst, b"partial").unwrap();
+            Err("export failed".to_string())
+        });
+        assert!(matches!(result, Err(EncryptionError::Migration(ref e)) if e == "export failed"));
+
+        // A step that claims success but leaves a plaintext copy is caught too
+        let result = migrate_plaintext_database_with(&db_path, "6b6579", |src, dst, _| {
+            fs::copy(src, dst).map(|_| ()).map_err(|e| e.to_string())
+        });
+        assert!(matches!(result, Err(EncryptionError::Migration(_))));
+
+        assert_eq!(fs::read(&db_path).unwrap(), original);
+        assert!(!db_path.with_extension("sqlite.encrypting").exists());
+        assert!(!db_path.parent().unwrap().join("backups").exists());
+
+        fs::remove_dir_all(db_path.parent().unwrap()).unwrap();
+    }
+
     #[test]
     fn test_passphrase_to_hex() {
         let hex = passphrase_to_hex("test");
@@ -101,7 +544,22 @@ mod tests {
     #[test]
     fn test_setup_encryption_too_short() {
         let result = setup_encryption("short");
-        assert!(matches!(result, Err(EncryptionError::PassphraseTooShort)));
+        assert!(matches!(
+            result,
+            Err(EncryptionError::PassphraseTooShort { min: 12 })
+        ));
+    }
+
+    #[test]
+    fn test_setup_encryption_one_short_of_minimum() {
+        let result = setup_encryption("elevenchars");
+        assert!(matches!(
+            result,
+            Err(EncryptionError::PassphraseTooShort {
+                min: MIN_PASSPHRASE_LEN
+            })
+        ));
+        assert!(setup_encryption("twelve_chars").is_ok());
     }
 
     #[test]
@@ -119,4 +577,142 @@ mod tests {
         let result = unlock_with_passphrase("");
         assert!(matches!(result, Err(EncryptionError::PassphraseRequired)));
     }
+
+    #[test]
+    fn test_validate_change_missing_old() {
+        let result = validate_passphrase_change("", "a_brand_new_passphrase");
+        assert!(!result.valid);
+        assert!(result.old_passphrase_error.is_some());
+        assert!(result.new_passphrase_error.is_none());
+    }
+
+    #[test]
+    fn test_validate_change_weak_new() {
+        let result = validate_passphrase_change("the_current_passphrase", "short");
+        assert!(!result.valid);
+        assert!(result.old_passphrase_error.is_none());
+        assert!(result.new_passphrase_error.is_some());
+    }
+
+    #[test]
+    fn test_validate_change_unchanged() {
+        let result = validate_passphrase_change("the_same_passphrase", "the_same_passphrase");
+        assert!(!result.valid);
+        assert_eq!(
+            result.new_passphrase_error.as_deref(),
+            Some("New passphrase must differ from the current passphrase")
+        );
+    }
+
+    #[test]
+    fn test_validate_change_both_valid() {
+        let result = validate_passphrase_change("the_current_passphrase", "a_brand_new_passphrase");
+        assert!(result.valid);
+        assert!(result.old_passphrase_error.is_none());
+        assert!(result.new_passphrase_error.is_none());
+    }
+
+    #[test]
+    fn test_strength_weak_patterns() {
+        let result = estimate_passphrase_strength("aaaaaaaaaaaa");
+        assert_eq!(result.score, 0);
+        assert!(result.warnings.iter().any(|w| w.contains("Repeated")));
+
+        let result = estimate_passphrase_strength("abcdef123456");
+        assert!(result.score <= 1);
+        assert!(result.warnings.iter().any(|w| w.contains("Sequences")));
+
+        let result = estimate_passphrase_strength("Password1234");
+        assert!(result.score <= 2);
+        assert!(result.warnings.iter().any(|w| w.contains("common words")));
+    }
+
+    #[test]
+    fn test_strength_strong_passphrase() {
+        let result = estimate_passphrase_strength("correct horse battery staple");
+        assert_eq!(result.score, 4);
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_strength_empty() {
+        let result = estimate_passphrase_strength("");
+        assert_eq!(result.score, 0);
+        assert_eq!(result.guesses_log10, 0.0);
+    }
+
+    #[test]
+    fn test_zeroizing_scrubs_passphrase_buffer() {
+        use zeroize::Zeroize;
+
+        let mut hex = passphrase_to_hex("this_is_a_valid_passphrase");
+        let len = hex.len();
+        // Scrub the contents in place so the length survives for checking
+        hex.as_mut_str().zeroize();
+        assert_eq!(hex.len(), len);
+        assert!(hex.bytes().all(|b| b == 0));
+
+        hex.zeroize();
+        assert!(hex.is_empty());
+    }
+
+    #[test]
+    fn test_startup_state_from() {
+        use DatabaseFileState::*;
+        use StartupState::*;
+
+        for (caching, cached) in [(false, false), (true, false), (true, true)] {
+            assert_eq!(startup_state_from(Missing, caching, cached), NeedsSetup);
+            assert_eq!(
+                startup_state_from(Corrupt, caching, cached),
+                DatabaseCorrupt
+            );
+            assert_eq!(
+                startup_state_from(Plaintext, caching, cached),
+                NeedsMigration
+            );
+        }
+        assert_eq!(startup_state_from(Valid, false, false), NeedsUnlock);
+        // Caching switched off ignores any leftover entry
+        assert_eq!(startup_state_from(Valid, false, true), NeedsUnlock);
+        assert_eq!(
+            startup_state_from(Valid, true, false),
+            KeyMissingButDbPresent
+        );
+        assert_eq!(startup_state_from(Valid, true, true), Ready);
+
+        assert_eq!(
+            serde_json::to_value(KeyMissingButDbPresent).unwrap(),
+            "KeyMissingButDbPresent"
+        );
+    }
+
+    #[test]
+    fn test_database_file_state() {
+        let dir = std::env::temp_dir().join(format!("phlox_db_state_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("phlox_database.sqlite");
+
+        assert_eq!(check_database_file(&db), DatabaseFileState::Missing);
+
+        std::fs::write(&db, b"").unwrap();
+        assert_eq!(check_database_file(&db), DatabaseFileState::Corrupt);
+
+        std::fs::write(&db, vec![0u8; 4096 + 100]).unwrap();
+        assert_eq!(check_database_file(&db), DatabaseFileState::Corrupt);
+
+        std::fs::write(&db, vec![0u8; 4096 * 3]).unwrap();
+        assert_eq!(check_database_file(&db), DatabaseFileState::Valid);
+
+        let mut plaintext = PLAINTEXT_HEADER.to_vec();
+        plaintext.resize(4096 * 3, 0);
+        std::fs::write(&db, plaintext).unwrap();
+        assert_eq!(check_database_file(&db), DatabaseFileState::Plaintext);
+        assert_eq!(
+            startup_state_from(check_database_file(&db), true, true),
+            StartupState::NeedsMigration
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
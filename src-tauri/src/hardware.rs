@@ -0,0 +1,698 @@
+//! Host hardware detection shared by the command layer and the process manager.
+
+use serde::{Deserialize, Serialize};
+
+// =========================================================================
+// Power source
+// =========================================================================
+
+/// Whether the machine is currently running on battery power.
+/// Returns `None` when the power source can't be determined (e.g. desktops, VMs).
+pub fn on_battery_power() -> Option<bool> {
+    #[cfg(target_os = "macos")]
+    {
+        let out = std::process::Command::new("pmset")
+            .args(["-g", "batt"])
+            .output()
+            .ok()?;
+        if !out.status.success() {
+            return None;
+        }
+        parse_pmset_batt(&String::from_utf8_lossy(&out.stdout))
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let entries = std::fs::read_dir("/sys/class/power_supply").ok()?;
+        let supplies: Vec<PowerSupply> = entries
+            .flatten()
+            .map(|entry| {
+                let read = |name: &str| {
+                    std::fs::read_to_string(entry.path().join(name))
+                        .ok()
+                        .map(|s| s.trim().to_string())
+                };
+                PowerSupply {
+                    kind: read("type").unwrap_or_default(),
+                    online: read("online").map(|s| s == "1"),
+                    scope: read("scope"),
+                }
+            })
+            .collect();
+        linux_on_battery(&supplies)
+    }
+
+    #[cfg(windows)]
+    {
+        use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+        let mut status = SYSTEM_POWER_STATUS::default();
+        unsafe { GetSystemPowerStatus(&mut status) }.ok()?;
+        match status.ACLineStatus {
+            0 => Some(true),
+            1 => Some(false),
+            _ => None,
+        }
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux", windows)))]
+    {
+        None
+    }
+}
+
+/// Parse `pmset -g batt` output, whose first line reads
+/// `Now drawing from 'Battery Power'` or `Now drawing from 'AC Power'`.
+#[cfg(any(target_os = "macos", test))]
+fn parse_pmset_batt(output: &str) -> Option<bool> {
+    let line = output.lines().find(|l| l.contains("drawing from"))?;
+    if line.contains("'Battery Power'") {
+        Some(true)
+    } else if line.contains("'AC Power'") || line.contains("'UPS Power'") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// One entry of `/sys/class/power_supply`.
+#[cfg(any(target_os = "linux", test))]
+struct PowerSupply {
+    /// Contents of `type`: `Mains`, `Battery`, `USB`, `UPS`, ...
+    kind: String,
+    /// Contents of `online` for external supplies.
+    online: Option<bool>,
+    /// Contents of `scope`; `Device` marks a peripheral's own battery.
+    scope: Option<String>,
+}
+
+/// Decide the power source from the Linux power-supply class entries.
+#[cfg(any(target_os = "linux", test))]
+fn linux_on_battery(supplies: &[PowerSupply]) -> Option<bool> {
+    // Mice, headsets and controllers report their own batteries; they don't
+    // power the machine.
+    let supplies: Vec<&PowerSupply> = supplies
+        .iter()
+        .filter(|s| s.scope.as_deref() != Some("Device"))
+        .collect();
+    let external_online = supplies
+        .iter()
+        .any(|s| s.kind != "Battery" && s.online == Some(true));
+    if external_online {
+        return Some(false);
+    }
+
+    if supplies.iter().any(|s| s.kind == "Battery") {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+// =========================================================================
+// GPU / accelerator
+// =========================================================================
+
+/// Apple Silicon generation and tier, also synthesized as a perf class elsewhere.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AppleSiliconInfo {
+    pub is_apple_silicon: bool,
+    pub generation: Option<u8>,
+    pub tier: Option<String>,
+}
+
+/// Parse an Apple Silicon CPU brand such as `Apple M3 Pro`.
+/// `tier` is `None` when the tier word isn't one we know.
+pub fn parse_apple_silicon(cpu_brand: &str) -> Option<AppleSiliconInfo> {
+    let brand = cpu_brand.trim();
+    // VMs report e.g. "Apple M2 (Virtual)"
+    let brand = brand.strip_suffix("(Virtual)").unwrap_or(brand).trim_end();
+
+    // Parse "Apple M3 Pro" -> gen=3, tier="Pro"
+    let after_m = brand.strip_prefix("Apple M")?;
+
+    let gen_len = after_m
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(after_m.len());
+    let generation: u8 = after_m[..gen_len].parse().ok()?;
+
+    // Parse tier (Pro/Max/Ultra) - case insensitive
+    let remaining = after_m[gen_len..].trim().to_lowercase();
+    let tier = match remaining.as_str() {
+        "" => Some("Base"),
+        "pro" => Some("Pro"),
+        "max" => Some("Max"),
+        "ultra" => Some("Ultra"),
+        _ => None,
+    };
+
+    Some(AppleSiliconInfo {
+        is_apple_silicon: true,
+        generation: Some(generation),
+        tier: tier.map(String::from),
+    })
+}
+
+/// VRAM of the largest discrete GPU, in MiB (Linux only). An NVIDIA card
+/// seen only on the PCI bus, with no driver tool to ask, is guessed at 8 GiB.
+#[cfg(target_os = "linux")]
+pub fn detect_dgpu_vram_mb() -> Option<u64> {
+    measured_dgpu_vram_mb().or_else(|| has_nvidia_pci_gpu().then_some(8 * 1024))
+}
+
+/// VRAM of the largest discrete GPU as reported by its driver, in MiB.
+#[cfg(target_os = "linux")]
+fn measured_dgpu_vram_mb() -> Option<u64> {
+    nvidia_vram_mb().or_else(amdgpu_vram_mb)
+}
+
+/// Whether an NVIDIA display or 3D controller is on the PCI bus.
+#[cfg(target_os = "linux")]
+fn has_nvidia_pci_gpu() -> bool {
+    if let Ok(pci) = std::fs::read_dir("/sys/bus/pci/devices") {
+        for entry in pci.flatten() {
+            let class_path = entry.path().join("class");
+            let vendor_path = entry.path().join("vendor");
+            let class_s = match std::fs::read_to_string(&class_path) {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+            let vendor_s = match std::fs::read_to_string(&vendor_path) {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+            let class_val = match u32::from_str_radix(class_s.trim().trim_start_matches("0x"), 16) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            let vendor_val = match u32::from_str_radix(vendor_s.trim().trim_start_matches("0x"), 16)
+            {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+
+            if (class_val == 0x030000 || class_val == 0x030200) && vendor_val == 0x10de {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+/// VRAM reported by the amdgpu driver, in MiB; skips small iGPU carve-outs.
+#[cfg(target_os = "linux")]
+fn amdgpu_vram_mb() -> Option<u64> {
+    if let Ok(drm) = std::fs::read_dir("/sys/class/drm") {
+        let mut max_vram: u64 = 0;
+        for entry in drm.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with("card") || name.contains('-') {
+                continue;
+            }
+            if let Ok(s) = std::fs::read_to_string(entry.path().join("device/mem_info_vram_total"))
+            {
+                if let Ok(bytes) = s.trim().parse::<u64>() {
+                    let mb = bytes / (1024 * 1024);
+                    if mb > max_vram {
+                        max_vram = mb;
+                    }
+                }
+            }
+        }
+        if max_vram >= 2048 {
+            return Some(max_vram);
+        }
+    }
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn nvidia_vram_mb() -> Option<u64> {
+    nvidia_smi_gpu().map(|(_, mb)| mb)
+}
+
+/// Name and VRAM (MiB) of the largest NVIDIA GPU, via `nvidia-smi`.
+#[cfg(any(target_os = "linux", windows))]
+fn nvidia_smi_gpu() -> Option<(String, u64)> {
+    let out = std::process::Command::new("nvidia-smi")
+        .args([
+            "--query-gpu=name,memory.total",
+            "--format=csv,noheader,nounits",
+        ])
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    parse_nvidia_smi(&String::from_utf8_lossy(&out.stdout))
+}
+
+/// Parse `nvidia-smi --query-gpu=name,memory.total --format=csv,noheader,nounits`
+/// output (one `name, MiB` line per GPU), keeping the GPU with the most VRAM.
+#[cfg(any(target_os = "linux", windows, test))]
+fn parse_nvidia_smi(output: &str) -> Option<(String, u64)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, mb) = line.rsplit_once(',')?;
+            Some((name.trim().to_string(), mb.trim().parse::<u64>().ok()?))
+        })
+        .max_by_key(|(_, mb)| *mb)
+}
+
+/// Compute backend llama.cpp uses for a GPU.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GpuBackend {
+    Metal,
+    #[serde(rename = "CUDA")]
+    Cuda,
+    #[serde(rename = "ROCm")]
+    Rocm,
+}
+
+/// Primary GPU as reported in `SystemSpecs`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GpuInfo {
+    pub name: String,
+    pub backend: GpuBackend,
+    /// Dedicated VRAM, or unified memory on Apple Silicon.
+    pub vram_gb: Option<f64>,
+}
+
+/// Detect the primary GPU. `None` when no usable GPU is found; missing
+/// tools such as `nvidia-smi` are treated the same way.
+pub fn detect_gpu(cpu_brand: &str, total_memory_gb: f64) -> Option<GpuInfo> {
+    #[cfg(target_os = "macos")]
+    {
+        // Unified memory: the GPU shares system RAM
+        parse_apple_silicon(cpu_brand).map(|_| GpuInfo {
+            name: cpu_brand.trim().to_string(),
+            backend: GpuBackend::Metal,
+            vram_gb: Some(total_memory_gb),
+        })
+    }
+
+    #[cfg(any(target_os = "linux", windows))]
+    {
+        let _ = (cpu_brand, total_memory_gb);
+        if let Some((name, mb)) = nvidia_smi_gpu() {
+            return Some(GpuInfo {
+                name,
+                backend: GpuBackend::Cuda,
+                vram_gb: Some(mb as f64 / 1024.0),
+            });
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(mb) = amdgpu_vram_mb() {
+            return Some(GpuInfo {
+                name: "AMD GPU".to_string(),
+                backend: GpuBackend::Rocm,
+                vram_gb: Some(mb as f64 / 1024.0),
+            });
+        }
+        None
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux", windows)))]
+    {
+        let _ = (cpu_brand, total_memory_gb);
+        None
+    }
+}
+
+/// What llama-server can offload layers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Accelerator {
+    /// Unified memory; Metal can take every layer.
+    AppleSilicon,
+    /// Discrete GPU with a known amount of VRAM.
+    Discrete { vram_mb: u64 },
+    /// No usable GPU was found.
+    CpuOnly,
+    /// Detection isn't available on this platform, or a GPU was found
+    /// whose VRAM can't be read.
+    Unknown,
+}
+
+/// "All layers" for llama-server; it clamps to the model's real layer count.
+pub const ALL_GPU_LAYERS: u32 = 99;
+
+/// VRAM left for the KV cache, compute buffers and the desktop.
+const VRAM_RESERVE_MB: u64 = 1536;
+
+/// Layer count assumed when only part of a model fits in VRAM. Small and
+/// mid-sized GGUF models mostly have 28-40 layers.
+const ASSUMED_MODEL_LAYERS: u64 = 32;
+
+/// Detect the accelerator llama-server will run on.
+pub fn detect_accelerator() -> Accelerator {
+    use sysinfo::{CpuRefreshKind, RefreshKind, System};
+
+    let sys = System::new_with_specifics(RefreshKind::new().with_cpu(CpuRefreshKind::new()));
+    let is_apple_silicon = sys
+        .cpus()
+        .first()
+        .and_then(|cpu| parse_apple_silicon(cpu.brand()))
+        .is_some();
+    if is_apple_silicon {
+        return Accelerator::AppleSilicon;
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        match measured_dgpu_vram_mb() {
+            Some(vram_mb) => Accelerator::Discrete { vram_mb },
+            // A card whose VRAM can't be read; don't size layers on a guess
+            None if has_nvidia_pci_gpu() => Accelerator::Unknown,
+            None => Accelerator::CpuOnly,
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        // Intel Macs: llama.cpp doesn't offload to their GPUs
+        Accelerator::CpuOnly
+    }
+    #[cfg(windows)]
+    {
+        match nvidia_smi_gpu() {
+            Some((_, vram_mb)) => Accelerator::Discrete { vram_mb },
+            // Other vendors' VRAM isn't queried
+            None => Accelerator::Unknown,
+        }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    {
+        Accelerator::Unknown
+    }
+}
+
+/// Decide `--n-gpu-layers` for a model of `model_bytes` on `accelerator`.
+/// Models that don't fit in VRAM get a proportional share of layers.
+pub fn gpu_layers(accelerator: Accelerator, model_bytes: Option<u64>) -> u32 {
+    match accelerator {
+        Accelerator::AppleSilicon => ALL_GPU_LAYERS,
+        Accelerator::CpuOnly => 0,
+        // Leave it to llama-server, which ignores layers it can't offload
+        Accelerator::Unknown => ALL_GPU_LAYERS,
+        Accelerator::Discrete { vram_mb } => {
+            let usable = vram_mb.saturating_sub(VRAM_RESERVE_MB);
+            let model_mb = match model_bytes {
+                Some(bytes) => bytes / (1024 * 1024),
+                None => return ALL_GPU_LAYERS,
+            };
+            if usable >= model_mb {
+                ALL_GPU_LAYERS
+            } else {
+                (ASSUMED_MODEL_LAYERS * usable / model_mb.max(1)) as u32
+            }
+        }
+    }
+}
+
+/// Physical core count (when the OS reports it) and logical CPU count.
+pub fn cpu_core_counts() -> (Option<usize>, usize) {
+    let logical = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    (sysinfo::System::new().physical_core_count(), logical)
+}
+
+// =========================================================================
+// CPU features
+// =========================================================================
+
+/// Every feature name `cpu_features` can report.
+pub const KNOWN_CPU_FEATURES: [&str; 10] = [
+    "sse4.2",
+    "avx",
+    "avx2",
+    "fma",
+    "f16c",
+    "avx512f",
+    "avx512bw",
+    "avx512vnni",
+    "neon",
+    "dotprod",
+];
+
+/// SIMD extensions relevant to CPU inference that this CPU supports.
+/// Empty on architectures we don't check.
+pub fn cpu_features() -> Vec<String> {
+    #[allow(unused_mut)]
+    let mut features: Vec<&str> = Vec::new();
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        macro_rules! detect {
+            ($($name:tt),*) => {
+                $(if std::arch::is_x86_feature_detected!($name) {
+                    features.push($name);
+                })*
+            };
+        }
+        detect!(
+            "sse4.2",
+            "avx",
+            "avx2",
+            "fma",
+            "f16c",
+            "avx512f",
+            "avx512bw",
+            "avx512vnni"
+        );
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    {
+        // NEON is mandatory on AArch64
+        features.push("neon");
+        if std::arch::is_aarch64_feature_detected!("dotprod") {
+            features.push("dotprod");
+        }
+    }
+
+    features.into_iter().map(String::from).collect()
+}
+
+// =========================================================================
+// Disk space
+// =========================================================================
+
+/// Free and total bytes on the filesystem holding `path`. A path that
+/// doesn't exist yet is resolved through its nearest existing ancestor.
+pub fn disk_space_for(path: &std::path::Path) -> Option<(u64, u64)> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let resolved = existing.canonicalize().ok()?;
+
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    let mounts: Vec<(&std::path::Path, u64, u64)> = disks
+        .list()
+        .iter()
+        .map(|d| (d.mount_point(), d.available_space(), d.total_space()))
+        .collect();
+    pick_disk(&mounts, &resolved)
+}
+
+/// The mount with the longest mount point containing `path`.
+fn pick_disk(
+    mounts: &[(&std::path::Path, u64, u64)],
+    path: &std::path::Path,
+) -> Option<(u64, u64)> {
+    mounts
+        .iter()
+        .filter(|(mount, _, _)| path.starts_with(mount))
+        .max_by_key(|(mount, _, _)| mount.components().count())
+        .map(|&(_, free, total)| (free, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_features_are_known() {
+        let features = cpu_features();
+        for feature in &features {
+            assert!(
+                KNOWN_CPU_FEATURES.contains(&feature.as_str()),
+                "{}",
+                feature
+            );
+        }
+        #[cfg(target_arch = "aarch64")]
+        assert!(features.iter().any(|f| f == "neon"));
+    }
+
+    fn supply(kind: &str, online: Option<bool>) -> PowerSupply {
+        PowerSupply {
+            kind: kind.to_string(),
+            online,
+            scope: None,
+        }
+    }
+
+    fn peripheral_battery() -> PowerSupply {
+        PowerSupply {
+            scope: Some("Device".to_string()),
+            ..supply("Battery", None)
+        }
+    }
+
+    #[test]
+    fn test_pmset_battery() {
+        let out = "Now drawing from 'Battery Power'\n -InternalBattery-0 (id=1234)\t85%; discharging; 5:12 remaining present: true\n";
+        assert_eq!(parse_pmset_batt(out), Some(true));
+    }
+
+    #[test]
+    fn test_pmset_ac() {
+        let out = "Now drawing from 'AC Power'\n -InternalBattery-0 (id=1234)\t100%; charged; 0:00 remaining present: true\n";
+        assert_eq!(parse_pmset_batt(out), Some(false));
+    }
+
+    #[test]
+    fn test_pmset_garbage() {
+        assert_eq!(parse_pmset_batt("No batteries available"), None);
+    }
+
+    #[test]
+    fn test_linux_laptop_unplugged() {
+        let supplies = [supply("Mains", Some(false)), supply("Battery", None)];
+        assert_eq!(linux_on_battery(&supplies), Some(true));
+    }
+
+    #[test]
+    fn test_linux_laptop_plugged_in() {
+        let supplies = [supply("Mains", Some(true)), supply("Battery", None)];
+        assert_eq!(linux_on_battery(&supplies), Some(false));
+    }
+
+    #[test]
+    fn test_linux_desktop_without_battery() {
+        assert_eq!(linux_on_battery(&[]), None);
+        assert_eq!(
+            linux_on_battery(&[supply("Mains", Some(true))]),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_linux_desktop_with_peripheral_battery() {
+        // e.g. hidpp_battery_0 from a wireless mouse
+        assert_eq!(linux_on_battery(&[peripheral_battery()]), None);
+        let supplies = [supply("Mains", Some(false)), peripheral_battery()];
+        assert_eq!(linux_on_battery(&supplies), None);
+
+        let supplies = [
+            supply("Mains", Some(false)),
+            supply("Battery", None),
+            peripheral_battery(),
+        ];
+        assert_eq!(linux_on_battery(&supplies), Some(true));
+    }
+
+    #[test]
+    fn test_parse_apple_silicon() {
+        let info = parse_apple_silicon("Apple M3 Pro").unwrap();
+        assert_eq!(info.generation, Some(3));
+        assert_eq!(info.tier.as_deref(), Some("Pro"));
+        assert!(parse_apple_silicon("Intel(R) Core(TM) i7-9750H").is_none());
+    }
+
+    #[test]
+    fn test_parse_apple_silicon_table() {
+        let cases = [
+            ("Apple M1", Some((1, Some("Base")))),
+            ("Apple M2 Pro", Some((2, Some("Pro")))),
+            ("Apple M3 Max", Some((3, Some("Max")))),
+            ("Apple M4 Max", Some((4, Some("Max")))),
+            ("Apple M2 Ultra", Some((2, Some("Ultra")))),
+            ("Apple M10 Ultra", Some((10, Some("Ultra")))),
+            ("Apple M2 (Virtual)", Some((2, Some("Base")))),
+            ("  Apple M3 Pro (Virtual) ", Some((3, Some("Pro")))),
+            ("Apple M5 Hyper", Some((5, None))),
+            ("Apple Mx", None),
+            ("AMD Ryzen 9 7950X", None),
+            ("Intel(R) Core(TM) i7-9750H", None),
+        ];
+        for (brand, expected) in cases {
+            let parsed =
+                parse_apple_silicon(brand).map(|info| (info.generation.unwrap(), info.tier));
+            let expected = expected.map(|(gen, tier)| (gen, tier.map(String::from)));
+            assert_eq!(parsed, expected, "{}", brand);
+        }
+    }
+
+    #[test]
+    fn test_pick_disk_prefers_deepest_mount() {
+        use std::path::Path;
+
+        let mounts = [
+            (Path::new("/"), 10, 100),
+            (Path::new("/home"), 20, 200),
+            (Path::new("/home/me/other"), 30, 300),
+        ];
+        assert_eq!(
+            pick_disk(&mounts, Path::new("/home/me/.local/share/Phlox")),
+            Some((20, 200))
+        );
+        assert_eq!(pick_disk(&mounts, Path::new("/var/lib")), Some((10, 100)));
+        assert_eq!(pick_disk(&[], Path::new("/var/lib")), None);
+    }
+
+    #[test]
+    fn test_disk_space_for_missing_dir() {
+        let missing = std::env::temp_dir()
+            .join("phlox-no-such-dir")
+            .join("models");
+        if let Some((free, total)) = disk_space_for(&missing) {
+            assert!(total > 0);
+            assert!(free <= total);
+        }
+    }
+
+    #[test]
+    fn test_parse_nvidia_smi() {
+        let out = "NVIDIA GeForce RTX 3060, 12288\nNVIDIA GeForce GT 1030, 2048\n";
+        assert_eq!(
+            parse_nvidia_smi(out),
+            Some(("NVIDIA GeForce RTX 3060".to_string(), 12288))
+        );
+        assert_eq!(
+            parse_nvidia_smi("NVIDIA RTX A4000, 16376"),
+            Some(("NVIDIA RTX A4000".to_string(), 16376))
+        );
+        assert_eq!(parse_nvidia_smi(""), None);
+        assert_eq!(
+            parse_nvidia_smi(
+                "NVIDIA-SMI has failed because it couldn't communicate with the NVIDIA driver."
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_gpu_layers_decision() {
+        const GB: u64 = 1024 * 1024 * 1024;
+        let model = Some(5 * GB);
+
+        assert_eq!(gpu_layers(Accelerator::AppleSilicon, model), ALL_GPU_LAYERS);
+        assert_eq!(gpu_layers(Accelerator::CpuOnly, model), 0);
+        assert_eq!(
+            gpu_layers(Accelerator::Discrete { vram_mb: 24576 }, model),
+            ALL_GPU_LAYERS
+        );
+        // 4 GiB card minus the reserve holds about half of a 5 GiB model
+        let partial = gpu_layers(Accelerator::Discrete { vram_mb: 4096 }, model);
+        assert!(partial > 0 && partial < ASSUMED_MODEL_LAYERS as u32);
+        assert_eq!(
+            gpu_layers(Accelerator::Discrete { vram_mb: 1024 }, model),
+            0
+        );
+        assert_eq!(
+            gpu_layers(Accelerator::Discrete { vram_mb: 4096 }, None),
+            ALL_GPU_LAYERS
+        );
+    }
+}
@@ -0,0 +1,420 @@
+//! Opt-in caching of the database key in the OS credential store.
+//!
+//! Off by default: Phlox handles PHI, so the passphrase is normally entered
+//! every session. When a user opts in on their own machine, the hex key the
+//! server accepted is cached in the macOS Keychain, the Secret Service
+//! (via `secret-tool`) on Linux, or Windows Credential Manager.
+
+use zeroize::Zeroizing;
+
+use crate::pm;
+
+/// Settings file holding the opt-in flag.
+pub const KEYCHAIN_CACHING_FILE: &str = "keychain_caching.txt";
+
+const SERVICE: &str = "io.bloodworks.phlox";
+const ACCOUNT: &str = "database-key";
+
+/// The credential store entry the key is cached in.
+trait KeyStore {
+    fn has_entry(&self) -> bool;
+    fn delete(&self) -> Result<(), String>;
+}
+
+/// The OS credential store.
+struct OsKeyStore;
+
+impl KeyStore for OsKeyStore {
+    fn has_entry(&self) -> bool {
+        platform::has_entry()
+    }
+
+    fn delete(&self) -> Result<(), String> {
+        platform::delete()
+    }
+}
+
+/// The opt-in flag in a settings dir together with the store it gates.
+/// The free functions use the data dir and the OS store; tests use a temp
+/// dir and an in-memory store.
+struct Keychain<S> {
+    paths: pm::PhloxPaths,
+    store: S,
+}
+
+impl<S: KeyStore> Keychain<S> {
+    fn caching_enabled(&self) -> bool {
+        self.paths
+            .read_setting(KEYCHAIN_CACHING_FILE)
+            .and_then(|v| pm::parse_bool_setting(&v))
+            .unwrap_or(false)
+    }
+
+    fn set_caching_enabled(&self, enabled: bool) -> Result<(), String> {
+        self.paths.write_setting(
+            KEYCHAIN_CACHING_FILE,
+            if enabled { "true" } else { "false" },
+        )?;
+        if !enabled {
+            self.store.delete()?;
+        }
+        Ok(())
+    }
+
+    fn has_entry(&self) -> bool {
+        self.caching_enabled() && self.store.has_entry()
+    }
+}
+
+fn os_keychain() -> Result<Keychain<OsKeyStore>, String> {
+    Ok(Keychain {
+        paths: pm::PhloxPaths::from_data_dir()?,
+        store: OsKeyStore,
+    })
+}
+
+/// Whether the user opted in to keychain caching (default off).
+pub fn caching_enabled() -> bool {
+    os_keychain().is_ok_and(|keychain| keychain.caching_enabled())
+}
+
+/// Persist the opt-in flag. Turning caching off also removes any cached key.
+pub fn set_caching_enabled(enabled: bool) -> Result<(), String> {
+    os_keychain()?.set_caching_enabled(enabled)
+}
+
+/// Whether a key is cached. Always false while caching is disabled, so a
+/// key left behind by an earlier opt-in is never used.
+pub fn has_entry() -> bool {
+    os_keychain().is_ok_and(|keychain| keychain.has_entry())
+}
+
+/// Cache the hex database key.
+pub fn store(hex_key: &str) -> Result<(), String> {
+    if !is_hex_key(hex_key) {
+        return Err("Refusing to cache a key that is not hex-encoded".to_string());
+    }
+    platform::store(hex_key)
+}
+
+/// Read the cached hex database key, if any.
+pub fn load() -> Result<Option<Zeroizing<String>>, String> {
+    let key = platform::load()?;
+    Ok(key.filter(|k| is_hex_key(k)))
+}
+
+/// Remove the cached key. Succeeds when nothing is cached.
+pub fn delete() -> Result<(), String> {
+    platform::delete()
+}
+
+/// Keys are always hex, which also keeps them safe to pass through
+/// line-based tools without quoting.
+fn is_hex_key(key: &str) -> bool {
+    !key.is_empty() && key.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{ACCOUNT, SERVICE};
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    use zeroize::Zeroizing;
+
+    pub fn has_entry() -> bool {
+        // Without -w this only reads attributes, so it never prompts.
+        Command::new("security")
+            .args(["find-generic-password", "-s", SERVICE, "-a", ACCOUNT])
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    }
+
+    pub fn store(hex_key: &str) -> Result<(), String> {
+        // Interactive mode reads the command from stdin, keeping the key out
+        // of the process list.
+        let mut child = Command::new("security")
+            .arg("-i")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to run security: {}", e))?;
+        let line = Zeroizing::new(format!(
+            "add-generic-password -U -s {} -a {} -w {}\n",
+            SERVICE, ACCOUNT, hex_key
+        ));
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(line.as_bytes())
+                .map_err(|e| format!("Failed to write to security: {}", e))?;
+        }
+        child
+            .wait()
+            .map_err(|e| format!("Failed to wait for security: {}", e))?;
+        if has_entry() {
+            Ok(())
+        } else {
+            Err("Keychain rejected the entry".to_string())
+        }
+    }
+
+    pub fn load() -> Result<Option<Zeroizing<String>>, String> {
+        let out = Command::new("security")
+            .args(["find-generic-password", "-s", SERVICE, "-a", ACCOUNT, "-w"])
+            .output()
+            .map_err(|e| format!("Failed to run security: {}", e))?;
+        let stdout = Zeroizing::new(out.stdout);
+        if !out.status.success() {
+            return Ok(None);
+        }
+        Ok(Some(Zeroizing::new(
+            String::from_utf8_lossy(&stdout).trim().to_string(),
+        )))
+    }
+
+    pub fn delete() -> Result<(), String> {
+        if !has_entry() {
+            return Ok(());
+        }
+        let out = Command::new("security")
+            .args(["delete-generic-password", "-s", SERVICE, "-a", ACCOUNT])
+            .output()
+            .map_err(|e| format!("Failed to run security: {}", e))?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err("Failed to delete keychain entry".to_string())
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{ACCOUNT, SERVICE};
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    use zeroize::Zeroizing;
+
+    const ATTRS: [&str; 4] = ["service", SERVICE, "account", ACCOUNT];
+
+    fn secret_tool() -> Command {
+        Command::new("secret-tool")
+    }
+
+    fn not_found(e: std::io::Error) -> String {
+        format!(
+            "Failed to run secret-tool ({}); install libsecret-tools to cache the key",
+            e
+        )
+    }
+
+    pub fn has_entry() -> bool {
+        matches!(load(), Ok(Some(_)))
+    }
+
+    pub fn store(hex_key: &str) -> Result<(), String> {
+        // secret-tool reads the secret from stdin.
+        let mut child = secret_tool()
+            .args(["store", "--label=Phlox database key"])
+            .args(ATTRS)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(not_found)?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(hex_key.as_bytes())
+                .map_err(|e| format!("Failed to write to secret-tool: {}", e))?;
+        }
+        let out = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to wait for secret-tool: {}", e))?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "secret-tool store failed: {}",
+                String::from_utf8_lossy(&out.stderr).trim()
+            ))
+        }
+    }
+
+    pub fn load() -> Result<Option<Zeroizing<String>>, String> {
+        let out = secret_tool()
+            .arg("lookup")
+            .args(ATTRS)
+            .output()
+            .map_err(not_found)?;
+        let stdout = Zeroizing::new(out.stdout);
+        if !out.status.success() || stdout.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Zeroizing::new(
+            String::from_utf8_lossy(&stdout).trim().to_string(),
+        )))
+    }
+
+    pub fn delete() -> Result<(), String> {
+        match secret_tool().arg("clear").args(ATTRS).output() {
+            Ok(_) => Ok(()),
+            // Nothing can be cached without secret-tool.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(not_found(e)),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{ACCOUNT, SERVICE};
+    use windows::core::{HSTRING, PCWSTR, PWSTR};
+    use windows::Win32::Foundation::ERROR_NOT_FOUND;
+    use windows::Win32::Security::Credentials::{
+        CredDeleteW, CredFree, CredReadW, CredWriteW, CREDENTIALW, CRED_PERSIST_LOCAL_MACHINE,
+        CRED_TYPE_GENERIC,
+    };
+    use zeroize::Zeroizing;
+
+    fn target() -> HSTRING {
+        HSTRING::from(format!("{}/{}", SERVICE, ACCOUNT))
+    }
+
+    pub fn has_entry() -> bool {
+        matches!(load(), Ok(Some(_)))
+    }
+
+    pub fn store(hex_key: &str) -> Result<(), String> {
+        let target = target();
+        let mut blob = Zeroizing::new(hex_key.as_bytes().to_vec());
+        let credential = CREDENTIALW {
+            Type: CRED_TYPE_GENERIC,
+            TargetName: PWSTR(target.as_ptr() as *mut u16),
+            CredentialBlobSize: blob.len() as u32,
+            CredentialBlob: blob.as_mut_ptr(),
+            Persist: CRED_PERSIST_LOCAL_MACHINE,
+            ..Default::default()
+        };
+        unsafe { CredWriteW(&credential, 0) }
+            .map_err(|e| format!("Failed to write credential: {}", e))
+    }
+
+    pub fn load() -> Result<Option<Zeroizing<String>>, String> {
+        let target = target();
+        let mut credential: *mut CREDENTIALW = std::ptr::null_mut();
+        match unsafe {
+            CredReadW(
+                PCWSTR(target.as_ptr()),
+                CRED_TYPE_GENERIC,
+                0,
+                &mut credential,
+            )
+        } {
+            Ok(()) => {
+                let key = unsafe {
+                    let blob = std::slice::from_raw_parts(
+                        (*credential).CredentialBlob,
+                        (*credential).CredentialBlobSize as usize,
+                    );
+                    let key = Zeroizing::new(String::from_utf8_lossy(blob).into_owned());
+                    CredFree(credential as *const _);
+                    key
+                };
+                Ok(Some(key))
+            }
+            Err(e) if e.code() == ERROR_NOT_FOUND.to_hresult() => Ok(None),
+            Err(e) => Err(format!("Failed to read credential: {}", e)),
+        }
+    }
+
+    pub fn delete() -> Result<(), String> {
+        let target = target();
+        match unsafe { CredDeleteW(PCWSTR(target.as_ptr()), CRED_TYPE_GENERIC, 0) } {
+            Ok(()) => Ok(()),
+            Err(e) if e.code() == ERROR_NOT_FOUND.to_hresult() => Ok(()),
+            Err(e) => Err(format!("Failed to delete credential: {}", e)),
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", windows)))]
+mod platform {
+    use zeroize::Zeroizing;
+
+    pub fn has_entry() -> bool {
+        false
+    }
+
+    pub fn store(_hex_key: &str) -> Result<(), String> {
+        Err("Keychain caching is not supported on this platform".to_string())
+    }
+
+    pub fn load() -> Result<Option<Zeroizing<String>>, String> {
+        Ok(None)
+    }
+
+    pub fn delete() -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_hex_key() {
+        assert!(is_hex_key("74657374"));
+        assert!(!is_hex_key(""));
+        assert!(!is_hex_key("7465 7374"));
+        assert!(!is_hex_key("74657374\n-w other"));
+    }
+
+    #[test]
+    fn test_store_rejects_non_hex() {
+        assert!(store("not hex at all").is_err());
+    }
+
+    /// In-memory stand-in for the OS store.
+    struct MemoryStore(std::cell::Cell<bool>);
+
+    impl KeyStore for MemoryStore {
+        fn has_entry(&self) -> bool {
+            self.0.get()
+        }
+
+        fn delete(&self) -> Result<(), String> {
+            self.0.set(false);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_caching_gates_cached_entry() {
+        let dir = std::env::temp_dir().join(format!("phlox-keychain-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let keychain = Keychain {
+            paths: pm::PhloxPaths::new(dir.clone()),
+            store: MemoryStore(std::cell::Cell::new(true)),
+        };
+
+        // Off by default: an entry from an earlier opt-in is not reported
+        assert!(!keychain.caching_enabled());
+        assert!(!keychain.has_entry());
+
+        keychain.set_caching_enabled(true).unwrap();
+        assert!(keychain.caching_enabled());
+        assert!(keychain.has_entry());
+
+        keychain.set_caching_enabled(false).unwrap();
+        assert!(!keychain.caching_enabled());
+        assert!(!keychain.store.has_entry(), "disabling kept the cached key");
+        assert!(!keychain.has_entry());
+
+        std::fs::write(dir.join(KEYCHAIN_CACHING_FILE), "maybe").unwrap();
+        assert!(!keychain.caching_enabled());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
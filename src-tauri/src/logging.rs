@@ -0,0 +1,175 @@
+//! Configuration of the app's own log (`phlox-app.log` in the log directory).
+
+use std::path::{Path, PathBuf};
+
+use log::LevelFilter;
+use tauri::Manager;
+use tauri_plugin_log::{RotationStrategy, Target, TargetKind};
+
+/// Log file name; the plugin appends `.log`.
+pub const LOG_FILE_STEM: &str = "phlox-app";
+/// Size at which the log is rolled over into a dated archive.
+pub const LOG_MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+/// Log files kept on disk, including the active one.
+pub const LOG_KEEP_FILES: usize = 5;
+
+/// Environment variable overriding the startup log level.
+pub const LOG_LEVEL_ENV: &str = "PHLOX_LOG_LEVEL";
+
+// Logs can hold PHI in error context, so keep the total footprint small.
+const _: () = match files_kept(&rotation_strategy()) {
+    Some(files) => assert!(LOG_MAX_FILE_BYTES * files as u64 <= 64 * 1024 * 1024),
+    None => panic!("old logs must be pruned"),
+};
+
+/// Log plugin writing to stdout and a size-capped, rotating file. The plugin
+/// rolls an oversized file over and prunes old archives when it starts.
+///
+/// The plugin itself passes everything; the effective level is the global
+/// max level, which `set_level` changes at runtime.
+pub fn plugin_builder() -> tauri_plugin_log::Builder {
+    tauri_plugin_log::Builder::default()
+        .targets([
+            Target::new(TargetKind::Stdout),
+            Target::new(TargetKind::LogDir {
+                file_name: Some(LOG_FILE_STEM.into()),
+            }),
+        ])
+        .max_file_size(LOG_MAX_FILE_BYTES as u128)
+        .rotation_strategy(rotation_strategy())
+        .level(LevelFilter::Trace)
+}
+
+/// Keep the active file and the newest archives, `LOG_KEEP_FILES` in all.
+/// `KeepSome(n)` counts archives only; the active file comes on top.
+const fn rotation_strategy() -> RotationStrategy {
+    RotationStrategy::KeepSome(LOG_KEEP_FILES - 1)
+}
+
+/// Log files left on disk under `strategy`, the active one included;
+/// `None` when archives are never pruned.
+const fn files_kept(strategy: &RotationStrategy) -> Option<usize> {
+    match strategy {
+        RotationStrategy::KeepAll => None,
+        RotationStrategy::KeepOne => Some(1),
+        RotationStrategy::KeepSome(archives) => Some(*archives + 1),
+    }
+}
+
+/// Parse a level name: `off`, `error`, `warn`, `info`, `debug` or `trace`.
+pub fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    match level.trim().to_lowercase().as_str() {
+        "off" => Ok(LevelFilter::Off),
+        "error" => Ok(LevelFilter::Error),
+        "warn" | "warning" => Ok(LevelFilter::Warn),
+        "info" => Ok(LevelFilter::Info),
+        "debug" => Ok(LevelFilter::Debug),
+        "trace" => Ok(LevelFilter::Trace),
+        _ => Err(format!(
+            "Unknown log level {:?} (expected off, error, warn, info, debug or trace)",
+            level
+        )),
+    }
+}
+
+/// Startup level: `PHLOX_LOG_LEVEL` when set and valid, otherwise `Debug`
+/// in debug builds and `Info` in release builds.
+pub fn initial_level() -> LevelFilter {
+    let default = if cfg!(debug_assertions) {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Info
+    };
+    match std::env::var(LOG_LEVEL_ENV) {
+        Ok(value) => parse_level(&value).unwrap_or_else(|e| {
+            eprintln!("Ignoring {}: {}", LOG_LEVEL_ENV, e);
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+/// Change the log level for the rest of the session.
+pub fn set_level(level: LevelFilter) {
+    log::set_max_level(level);
+}
+
+/// Path of the active log file inside `log_dir`.
+fn log_file_in(log_dir: &Path) -> PathBuf {
+    log_dir.join(format!("{}.log", LOG_FILE_STEM))
+}
+
+/// Path of the active log file.
+pub fn log_file_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let log_dir = app_handle
+        .path()
+        .app_log_dir()
+        .map_err(|e| format!("Could not determine log directory: {}", e))?;
+    Ok(log_file_in(&log_dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level("info"), Ok(LevelFilter::Info));
+        assert_eq!(parse_level(" DEBUG "), Ok(LevelFilter::Debug));
+        assert_eq!(parse_level("Warning"), Ok(LevelFilter::Warn));
+        assert_eq!(parse_level("off"), Ok(LevelFilter::Off));
+        assert_eq!(parse_level("trace"), Ok(LevelFilter::Trace));
+        assert!(parse_level("verbose").is_err());
+        assert!(parse_level("").is_err());
+    }
+
+    /// Mirrors the plugin's rollover: archive the active file, start a fresh
+    /// one, then prune the oldest archives down to the limit.
+    fn roll_over(files: &mut Vec<u32>, strategy: &RotationStrategy, next: &mut u32) {
+        *next += 1;
+        files.push(*next);
+        let archives = files.len() - 1;
+        let keep = match strategy {
+            RotationStrategy::KeepAll => archives,
+            RotationStrategy::KeepOne => 0,
+            RotationStrategy::KeepSome(n) => archives.min(*n),
+        };
+        files.drain(..archives - keep);
+    }
+
+    #[test]
+    fn test_rollover_and_retention_policy() {
+        let strategy = rotation_strategy();
+        let mut files = vec![0];
+        let mut next = 0;
+        for _ in 0..20 {
+            roll_over(&mut files, &strategy, &mut next);
+            assert!(files.len() <= LOG_KEEP_FILES, "{:?}", files);
+        }
+        // The newest files survive, the active one last
+        assert_eq!(files, [16, 17, 18, 19, 20]);
+        assert_eq!(files_kept(&strategy), Some(files.len()));
+
+        // Worst case on disk: every kept file at the rollover size
+        let footprint = LOG_MAX_FILE_BYTES * files.len() as u64;
+        assert_eq!(footprint, 50 * 1024 * 1024);
+
+        // Passing the file count straight through keeps one file too many
+        let mut files = vec![0];
+        let mut next = 0;
+        for _ in 0..20 {
+            roll_over(
+                &mut files,
+                &RotationStrategy::KeepSome(LOG_KEEP_FILES),
+                &mut next,
+            );
+        }
+        assert_eq!(files.len(), LOG_KEEP_FILES + 1);
+    }
+
+    #[test]
+    fn test_log_file_name_matches_plugin() {
+        let path = log_file_in(Path::new("/logs"));
+        assert_eq!(path, Path::new("/logs/phlox-app.log"));
+    }
+}
@@ -1,20 +1,23 @@
+mod audio;
+mod binary_manifest;
 mod commands;
+mod download;
 mod encryption;
+mod hardware;
+mod keychain;
+mod logging;
 mod pm;
 mod process;
 
-use log::LevelFilter;
 use std::thread;
-use std::time::Duration;
 use tauri::{Emitter, Manager};
-use tauri_plugin_log::{Target, TargetKind};
 
 use commands::{
     change_passphrase, clear_keychain, get_encryption_status, get_service_status, get_system_specs,
     has_database, has_encryption_setup, has_keychain_entry, restart_embedding, restart_llama,
     restart_whisper, send_passphrase_command, setup_encryption, start_embedding_service,
     start_llama_service, start_server_command, start_whisper_service, unlock_with_passphrase,
-    CachedServiceStatus,
+    validate_passphrase_change, CachedServiceStatus, UnlockThrottle,
 };
 use process::{cleanup_stale_files, kill_all_processes};
 
@@ -39,20 +42,14 @@ fn position_traffic_light_buttons(ns_window: &objc2_app_kit::NSWindow) {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let log_plugin = tauri_plugin_log::Builder::default()
-        .targets([
-            Target::new(TargetKind::Stdout),
-            Target::new(TargetKind::LogDir {
-                file_name: Some("phlox-app.log".into()),
-            }),
-        ])
-        .level(LevelFilter::Debug)
-        .build();
+    let log_plugin = logging::plugin_builder().build();
 
     tauri::Builder::default()
         .plugin(log_plugin)
         .plugin(tauri_plugin_http::init())
-        .manage(CachedServiceStatus(std::sync::Mutex::new(None)))
+        .manage(CachedServiceStatus::default())
+        .manage(std::sync::Mutex::new(UnlockThrottle::default()))
+        .manage(download::ActiveDownloads::default())
         .manage(pm::PmState(std::sync::Mutex::new(
             pm::ProcessManagerState::default(),
         )))
@@ -61,15 +58,50 @@ pub fn run() {
             commands::get_llm_port,
             commands::get_whisper_port,
             commands::get_embedding_port,
+            commands::get_all_ports,
             commands::get_request_token,
+            commands::get_install_type,
+            commands::get_log_path,
+            commands::get_data_dir,
+            commands::preflight_check,
+            commands::set_log_level,
+            commands::diagnose_port_consistency,
+            commands::get_last_failure,
+            commands::download_model,
+            commands::cancel_model_download,
+            commands::verify_model_checksum,
+            commands::wait_for_service_ready,
             get_service_status,
             get_system_specs,
+            commands::chunk_audio,
+            commands::get_battery_saver,
+            commands::set_battery_saver,
+            commands::set_flash_attention,
+            commands::set_server_grace_period,
+            commands::get_restart_policy,
+            commands::set_restart_policy,
+            commands::set_llm_context_size,
+            commands::set_llm_model,
+            commands::get_models_dir,
+            commands::set_models_dir,
+            commands::switch_model,
+            commands::prepare_for_update,
+            commands::recommend_model,
             restart_whisper,
             restart_llama,
             restart_embedding,
             start_llama_service,
             start_whisper_service,
             start_embedding_service,
+            commands::stop_llama_service,
+            commands::stop_whisper_service,
+            commands::stop_embedding_service,
+            commands::shutdown_process_manager,
+            commands::stop_all_services,
+            commands::list_killable_processes,
+            commands::verify_bundled_binaries,
+            commands::ensure_service_running,
+            commands::install_and_select_model,
             start_server_command,
             send_passphrase_command,
             // Encryption commands
@@ -77,10 +109,17 @@ pub fn run() {
             has_database,
             has_keychain_entry,
             setup_encryption,
+            commands::migrate_plaintext_database,
             unlock_with_passphrase,
             change_passphrase,
+            validate_passphrase_change,
+            commands::estimate_passphrase_strength,
             clear_keychain,
-            get_encryption_status
+            commands::get_keychain_caching_enabled,
+            commands::set_keychain_caching_enabled,
+            commands::unlock_with_keychain,
+            get_encryption_status,
+            commands::get_startup_state
         ])
         .setup(|app| {
             // Set transparent titlebar with custom dark background color on macOS
@@ -111,8 +150,13 @@ pub fn run() {
                 }
             }
 
+            let log_level = logging::initial_level();
+            logging::set_level(log_level);
+
             let app_handle = app.handle().clone();
-            log::info!("App setup started");
+            log::info!("App setup started (log level {})", log_level);
+            pm::migrate_legacy_data_dir();
+            pm::load_server_grace_period();
 
             #[cfg(target_os = "linux")]
             grant_webview_permissions(&app_handle);
@@ -130,6 +174,20 @@ pub fn run() {
                 monitor_service_health(app_handle_for_monitor);
             });
 
+            // Forward captured sidecar output to the frontend as `service-log` events
+            let logs = app_handle
+                .state::<pm::PmState>()
+                .0
+                .lock()
+                .unwrap()
+                .subscribe_logs(None);
+            let app_handle_for_logs = app_handle.clone();
+            thread::spawn(move || {
+                for line in logs {
+                    let _ = app_handle_for_logs.emit("service-log", line);
+                }
+            });
+
             Ok(())
         })
         .on_window_event(|window, event| {
@@ -178,8 +236,10 @@ pub fn run() {
 /// Install cleanup hooks for abnormal process termination.
 fn install_cleanup_hooks() {
     extern "C" fn on_signal(_sig: libc::c_int) {
-        crate::process::kill_all_processes();
-        std::process::exit(130);
+        // A repeated signal leaves the first handler to finish and exit
+        if crate::process::kill_all_processes_on_signal() {
+            std::process::exit(130);
+        }
     }
 
     #[cfg(unix)]
@@ -191,24 +251,78 @@ fn install_cleanup_hooks() {
     // Panic hook: kill children before unwinding so we don't orphan sidecars.
     let prev_hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |info| {
-        crate::process::kill_all_processes();
+        crate::process::kill_all_processes_on_panic();
         prev_hook(info);
     }));
 }
 
 fn monitor_service_health(app_handle: tauri::AppHandle) {
-    loop {
-        thread::sleep(Duration::from_secs(30));
-
-        let pm_state = app_handle.state::<pm::PmState>();
-        let mut state = pm_state.0.lock().unwrap();
-        let died = state.check_liveness();
-        drop(state);
-
-        for service in died {
+    let pm_state = app_handle.state::<pm::PmState>();
+    pm_state.run_monitor(pm::monitor_interval, |tick| {
+        for &service in &tick.died {
             log::warn!("Emitting service-died event for: {}", service);
             let _ = app_handle.emit("service-died", service);
+            emit_status_changed(&app_handle, service, false);
+        }
+        if tick.died.contains(&"server") {
+            restart_server_if_allowed(&app_handle);
+        }
+        for service in tick.restarted {
+            let _ = app_handle.emit("service-restarted", service);
+            emit_status_changed(&app_handle, service, true);
+        }
+        for service in tick.failing {
+            log::error!("Emitting service-failing event for: {}", service);
+            let _ = app_handle.emit("service-failing", service);
+        }
+        true
+    });
+}
+
+/// Tell the frontend a service went down or came back, without waiting for
+/// its next status poll.
+fn emit_status_changed(app_handle: &tauri::AppHandle, service: &'static str, running: bool) {
+    let _ = app_handle.emit(
+        "service-status-changed",
+        pm::ServiceStatusChanged { service, running },
+    );
+}
+
+/// Restart a crashed server and unlock it with the keychain-cached key.
+/// Without a cached key the server can't be unlocked unattended, so by
+/// default a dead server is left for the user to restart.
+fn restart_server_if_allowed(app_handle: &tauri::AppHandle) {
+    let caching = keychain::caching_enabled();
+    let policy_allows = app_handle
+        .state::<pm::PmState>()
+        .0
+        .lock()
+        .unwrap()
+        .server_restart_allowed();
+    let allowed =
+        pm::should_auto_restart_server(policy_allows, caching, caching && keychain::has_entry());
+    if !allowed {
+        log::info!("Server died; auto-restart is off, gave up, or no key is cached");
+        return;
+    }
+
+    log::info!("Server died; restarting with the cached key");
+    let result = (|| {
+        let key = keychain::load()?.ok_or("No key cached in keychain")?;
+        let pm_state = app_handle.state::<pm::PmState>();
+        pm_state.0.lock().unwrap().start_server()?;
+        let ports = pm_state.send_passphrase(key.to_string())?;
+        let _ = app_handle.emit("server-ready", ports.server);
+        // The server allocates fresh ports; move the sidecars onto them
+        pm_state.0.lock().unwrap().diagnose_ports(true)?;
+        Ok::<(), String>(())
+    })();
+    match result {
+        Ok(()) => {
+            let _ = app_handle.emit("service-restarted", "server");
+            emit_status_changed(app_handle, "server", true);
         }
+        Err(e) => log::error!("Failed to restart server: {}", e),
     }
 }
 
@@ -1,14 +1,25 @@
 //! In-process process manager for phlox sidecar services.
+//!
+//! This is the only place sidecars are started and monitored. `process`
+//! holds the recovery helpers that work from PID files alone (startup
+//! orphan sweep, signal and panic hooks) and reuses the PID file helpers here.
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::fs;
 use std::io::{BufRead, BufReader, Write};
 use std::path::PathBuf;
 use std::process::{Child, Command, Stdio};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+use crate::binary_manifest;
+use crate::download;
+use crate::hardware;
 
 /// Fixed fallback ports for the sidecar services.
 pub const LLAMA_PORT: u16 = 8082;
@@ -31,6 +42,21 @@ pub struct ServiceStatus {
     pub running: bool,
     pub pid: u32,
     pub port: u16,
+    /// Layers offloaded to the GPU (llama only; 0 means CPU).
+    pub gpu_layers: Option<u32>,
+    /// Whether flash attention was forced on or off (llama only; `None`
+    /// when left to llama.cpp's `auto`).
+    pub flash_attn: Option<bool>,
+    /// Seconds since this process was spawned.
+    pub uptime_seconds: u64,
+    /// Restarts this session, manual or automatic.
+    pub restart_count: u32,
+    /// Filename of the model being served, when known.
+    pub model_name: Option<String>,
+    /// Resident memory in MiB.
+    pub memory_mb: Option<u64>,
+    /// CPU use since the previous status call, as a percentage of one core.
+    pub cpu_percent: Option<f32>,
 }
 
 /// Status snapshot of all managed services, returned by [`ProcessManagerState::status`].
@@ -41,11 +67,211 @@ pub struct StatusData {
     pub server: Option<ServiceStatus>,
     pub embedding: Option<ServiceStatus>,
     pub request_token: Option<String>,
+    /// Last recorded exit per service, kept after the service is reaped.
+    pub last_exit: HashMap<&'static str, ExitInfo>,
+    /// Services that crashed too often and are no longer restarted.
+    pub failing: Vec<&'static str>,
+}
+
+/// Payload of the `service-status-changed` event the monitor emits when a
+/// service dies or comes back.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ServiceStatusChanged {
+    pub service: &'static str,
+    pub running: bool,
+}
+
+/// How a managed service exited, with a human-readable hint for the UI.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExitInfo {
+    pub code: Option<i32>,
+    /// Terminating signal (Unix only).
+    pub signal: Option<i32>,
+    pub hint: String,
+}
+
+impl ExitInfo {
+    fn from_status(status: std::process::ExitStatus) -> Self {
+        #[cfg(unix)]
+        let signal = {
+            use std::os::unix::process::ExitStatusExt;
+            status.signal()
+        };
+        #[cfg(not(unix))]
+        let signal = None;
+
+        let code = status.code();
+        ExitInfo {
+            code,
+            signal,
+            hint: exit_hint(code, signal),
+        }
+    }
+}
+
+/// The last unexpected exit of a service, with the stderr it wrote just
+/// before dying.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LastFailure {
+    #[serde(flatten)]
+    pub exit: ExitInfo,
+    /// Up to [`STDERR_TAIL_LINES`] of the service's most recent stderr.
+    pub stderr_tail: Vec<String>,
+    /// When the exit was noticed, in seconds since the Unix epoch.
+    pub when: u64,
+}
+
+/// Map an exit code / signal to a likely cause.
+fn exit_hint(code: Option<i32>, signal: Option<i32>) -> String {
+    match (code, signal) {
+        (_, Some(9)) => "killed by SIGKILL (likely out of memory)".to_string(),
+        (_, Some(11)) => "crashed with SIGSEGV (segmentation fault)".to_string(),
+        (_, Some(6)) => "aborted with SIGABRT (internal error)".to_string(),
+        (_, Some(15)) => "terminated by SIGTERM".to_string(),
+        (_, Some(sig)) => format!("terminated by signal {}", sig),
+        (Some(0), None) => "exited normally".to_string(),
+        // STATUS_ACCESS_VIOLATION on Windows
+        (Some(c), None) if c as u32 == 0xC000_0005 => {
+            "crashed with an access violation".to_string()
+        }
+        (Some(c), None) => format!("exited with code {}", c),
+        (None, None) => "exited with an unknown status".to_string(),
+    }
+}
+
+/// A sidecar whose running port or listening state disagrees with the
+/// ports the Python server allocated.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PortMismatch {
+    pub service: &'static str,
+    /// Port the Python server will call.
+    pub allocated: u16,
+    /// Port the managed process was started on.
+    pub running: u16,
+    pub listening: bool,
+    pub suggestion: String,
+}
+
+/// Result of [`ProcessManagerState::diagnose_ports`].
+#[derive(Debug, Clone, Serialize)]
+pub struct PortReport {
+    pub consistent: bool,
+    pub mismatches: Vec<PortMismatch>,
+    /// Services restarted on their allocated port (`fix` mode only).
+    pub fixed: Vec<&'static str>,
 }
 
 /// Managed Tauri state wrapping the supervisor mutex.
 pub struct PmState(pub Mutex<ProcessManagerState>);
 
+impl PmState {
+    /// Write passphrase to the server stdin and wait for the `PORTS:` line.
+    ///
+    /// BLOCKING — can take up to ~30s while the Python server boots. Callers
+    /// MUST wrap in `tokio::task::spawn_blocking`. The state lock is only held
+    /// to check the server handle out and back in, so status queries and other
+    /// commands are served while the server boots.
+    pub fn send_passphrase(&self, passphrase: String) -> Result<AllocatedPorts, PmError> {
+        self.send_passphrase_within(passphrase, SERVER_HEALTH_TIMEOUT)
+    }
+
+    /// [`Self::send_passphrase`], giving up on `/health` after `health_timeout`.
+    fn send_passphrase_within(
+        &self,
+        passphrase: String,
+        health_timeout: Duration,
+    ) -> Result<AllocatedPorts, PmError> {
+        // The hex passphrase is the database key; scrub it once written.
+        let passphrase = Zeroizing::new(passphrase);
+        let (mut proc, logs) = {
+            let mut state = self.0.lock().unwrap();
+            (state.begin_unlock()?, Arc::clone(&state.logs))
+        };
+        // The server prints its ports before uvicorn binds, so also wait for
+        // /health before calling it unlocked
+        let result =
+            send_passphrase_and_wait_for_ports(&mut proc, &passphrase, logs).and_then(|ports| {
+                let probe = ReadinessProbe::for_service("server").unwrap();
+                wait_for_http_ready(
+                    ports.server,
+                    &probe,
+                    health_timeout,
+                    Duration::from_millis(500),
+                )
+                .map(|()| ports)
+                .map_err(|e| {
+                    PmError::new(
+                        PmErrorKind::ServerNotReady,
+                        format!("{}: {}", SERVER_NOT_READY_ERROR, e),
+                    )
+                })
+            });
+        self.0.lock().unwrap().finish_unlock(proc, result)
+    }
+
+    /// Reap dead children and run due restarts every `interval()`, without
+    /// waiting for a status request. `on_tick` gets each pass's results
+    /// after the lock is released, and stops the loop by returning `false`.
+    pub fn run_monitor(
+        &self,
+        interval: impl Fn() -> Duration,
+        mut on_tick: impl FnMut(MonitorTick) -> bool,
+    ) {
+        loop {
+            // Re-read each pass so a changed interval applies without a relaunch
+            thread::sleep(interval());
+
+            let mut state = self.0.lock().unwrap();
+            state.restart_policies = restart_policies();
+            let died = state.check_liveness();
+            let (restarted, failing) = state.run_due_restarts(Instant::now());
+            drop(state);
+
+            if !on_tick(MonitorTick {
+                died,
+                restarted,
+                failing,
+            }) {
+                return;
+            }
+        }
+    }
+}
+
+/// What one pass of [`PmState::run_monitor`] found and did.
+#[derive(Debug, Default, PartialEq)]
+pub struct MonitorTick {
+    /// Services reaped because their process had exited.
+    pub died: Vec<&'static str>,
+    /// Sidecars restarted after their backoff elapsed.
+    pub restarted: Vec<&'static str>,
+    /// Services that just gave up after repeated crashes.
+    pub failing: Vec<&'static str>,
+}
+
+/// A model switch whose new process has started, from
+/// [`ProcessManagerState::begin_model_switch`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSwitch {
+    pub pid: u32,
+    pub port: u16,
+    /// Selection before the switch; `None` when none was saved.
+    pub previous: Option<String>,
+    /// Whether the old model was running.
+    pub was_running: bool,
+}
+
+/// Outcome of [`ProcessManagerState::stop_all`].
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct StopAllReport {
+    /// Services that were running and have been stopped.
+    pub stopped: Vec<&'static str>,
+    /// Services that were not running.
+    pub already_stopped: Vec<&'static str>,
+    /// Services still alive after stopping, killed by binary name instead.
+    pub killed_by_name: Vec<&'static str>,
+}
+
 /// A child process plus the bookkeeping needed to supervise it.
 pub struct ManagedProcess {
     pub child: Child,
@@ -54,6 +280,15 @@ pub struct ManagedProcess {
     pub drain_handles: Option<(JoinHandle<()>, JoinHandle<()>)>,
     /// Flag used to signal drain threads to stop.
     pub drain_shutdown: Option<Arc<AtomicBool>>,
+    /// `--n-gpu-layers` the process was started with (llama only).
+    pub gpu_layers: Option<u32>,
+    /// `--flash-attn` the process was started with (llama only; `None` when
+    /// no flag was passed).
+    pub flash_attn: Option<bool>,
+    /// When the process was spawned.
+    pub started_at: Instant,
+    /// Filename of the model the process was started with.
+    pub model_name: Option<String>,
 }
 
 /// Signal emitted by the Python server on stdout during startup.
@@ -72,55 +307,850 @@ pub struct ProcessManagerState {
     embedding: Option<ManagedProcess>,
     allocated_ports: Option<AllocatedPorts>,
     request_token: Option<String>,
+    /// Set by [`ProcessManagerState::prepare_for_update`]; refuses new starts.
+    updating: bool,
+    /// How each service last exited, recorded when it is reaped.
+    last_failure: HashMap<&'static str, LastFailure>,
+    /// The server handle is checked out by [`PmState::send_passphrase`].
+    unlocking: bool,
+    /// Captured sidecar output, fanned out to subscribers.
+    logs: Arc<LogHub>,
+    /// Crash history and pending auto-restarts per service.
+    restarts: HashMap<&'static str, RestartTracker>,
+    /// Re-read from [`RESTART_POLICY_FILE`] on every monitor pass.
+    restart_policies: RestartPolicies,
+    /// Restarts per service this session, manual or automatic.
+    restart_counts: HashMap<&'static str, u32>,
+    /// Kept between status calls so CPU use covers the interval between them.
+    sys: sysinfo::System,
 }
 
 // =========================================================================
 // Directory / PID file helpers
 // =========================================================================
 
-/// Get the phlox data directory.
+/// Get the phlox data directory. This must match the Python server's
+/// `platformdirs.user_data_dir("Phlox", "bloodworks.io")`, which on Windows
+/// is the local (non-roaming) app data dir with an author folder.
 pub fn phlox_dir() -> Option<PathBuf> {
-    dirs::data_dir().map(|dir| dir.join("Phlox"))
+    #[cfg(windows)]
+    {
+        dirs::data_local_dir().map(|dir| dir.join("bloodworks.io").join("Phlox"))
+    }
+    #[cfg(not(windows))]
+    {
+        dirs::data_dir().map(|dir| dir.join("Phlox"))
+    }
+}
+
+/// Error returned when the platform reports no data directory (for example
+/// `$HOME` unset on Linux).
+pub const NO_DATA_DIR_ERROR: &str = "Could not determine data directory";
+
+/// Turn a missing directory into [`NO_DATA_DIR_ERROR`].
+pub fn require_dir(dir: Option<PathBuf>) -> Result<PathBuf, String> {
+    dir.ok_or_else(|| NO_DATA_DIR_ERROR.to_string())
+}
+
+/// [`phlox_dir`], or an error for callers that cannot do without it.
+pub fn require_phlox_dir() -> Result<PathBuf, String> {
+    require_dir(phlox_dir())
 }
 
+/// A phlox data directory and the files kept in it. The free functions
+/// below use [`PhloxPaths::from_data_dir`]; tests point one at a temp dir.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhloxPaths {
+    root: PathBuf,
+}
+
+impl PhloxPaths {
+    pub fn new(root: PathBuf) -> Self {
+        PhloxPaths { root }
+    }
+
+    /// The real data directory, [`phlox_dir`].
+    pub fn from_data_dir() -> Result<Self, String> {
+        require_phlox_dir().map(Self::new)
+    }
+
+    pub fn root(&self) -> &std::path::Path {
+        &self.root
+    }
+
+    /// PID file for a service.
+    pub fn pid_file(&self, service: &str) -> PathBuf {
+        self.root.join(format!("{}.pid", service))
+    }
+
+    /// Read the PID recorded for a service, without checking it is alive.
+    pub fn read_pid_file(&self, service: &str) -> Option<u32> {
+        parse_pid(&fs::read_to_string(self.pid_file(service)).ok()?)
+    }
+
+    /// Record `pid` as the running process for a service.
+    pub fn write_pid_file(&self, service: &str, pid: u32) -> std::io::Result<()> {
+        fs::create_dir_all(&self.root)?;
+        atomic_write(&self.pid_file(service), pid.to_string().as_bytes())
+    }
+
+    /// Read a trimmed, non-empty settings file.
+    pub fn read_setting(&self, name: &str) -> Option<String> {
+        let value = fs::read_to_string(self.root.join(name)).ok()?;
+        let value = value.trim();
+        if value.is_empty() {
+            None
+        } else {
+            Some(value.to_string())
+        }
+    }
+
+    /// Write a settings file, creating the directory if needed.
+    pub fn write_setting(&self, name: &str, value: &str) -> Result<(), String> {
+        fs::create_dir_all(&self.root)
+            .map_err(|e| format!("Failed to create {:?}: {}", self.root, e))?;
+        atomic_write(&self.root.join(name), value.as_bytes())
+            .map_err(|e| format!("Failed to write {}: {}", name, e))
+    }
+
+    /// Put settings file `name` back to `previous`, deleting it when there
+    /// was none.
+    pub fn restore_setting(&self, name: &str, previous: Option<&str>) -> Result<(), String> {
+        match previous {
+            Some(value) => self.write_setting(name, value),
+            None => match fs::remove_file(self.root.join(name)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    Err(format!("Failed to remove {}: {}", name, e))
+                }
+                _ => Ok(()),
+            },
+        }
+    }
+
+    /// Directory holding the models subfolders: the `models_dir.txt`
+    /// override when valid, otherwise the root.
+    pub fn models_root(&self) -> PathBuf {
+        resolve_models_root(
+            self.root.clone(),
+            self.read_setting(MODELS_DIR_FILE).as_deref(),
+        )
+    }
+
+    /// Models directory for a service (`llm_models`, `whisper_models`, ...).
+    pub fn models_dir(&self, service: &str) -> Result<PathBuf, String> {
+        Ok(self.models_root().join(models_subdir(service)?))
+    }
+}
+
+/// Where older Windows builds kept the app's files (`%APPDATA%\Phlox`),
+/// apart from the database the server wrote to [`phlox_dir`].
+fn legacy_phlox_dir() -> Option<PathBuf> {
+    #[cfg(windows)]
+    {
+        dirs::data_dir().map(|dir| dir.join("Phlox"))
+    }
+    #[cfg(not(windows))]
+    {
+        None
+    }
+}
+
+/// Move files from the legacy data directory into [`phlox_dir`]. Called
+/// once at startup, before anything reads settings.
+pub fn migrate_legacy_data_dir() {
+    let (Some(legacy), Some(canonical)) = (legacy_phlox_dir(), phlox_dir()) else {
+        return;
+    };
+    match migrate_data_dir(&legacy, &canonical) {
+        Ok(0) => {}
+        Ok(moved) => log::info!(
+            "Moved {} item(s) from {:?} to {:?}",
+            moved,
+            legacy,
+            canonical
+        ),
+        Err(e) => log::warn!("Could not migrate {:?}: {}", legacy, e),
+    }
+}
+
+/// Move each entry of `legacy` into `canonical`, keeping anything already
+/// there, and remove `legacy` once empty. Returns how many entries moved.
+fn migrate_data_dir(
+    legacy: &std::path::Path,
+    canonical: &std::path::Path,
+) -> Result<usize, String> {
+    if !legacy.is_dir() || legacy == canonical {
+        return Ok(0);
+    }
+    fs::create_dir_all(canonical)
+        .map_err(|e| format!("Failed to create {:?}: {}", canonical, e))?;
+
+    let entries =
+        fs::read_dir(legacy).map_err(|e| format!("Failed to read {:?}: {}", legacy, e))?;
+    let mut moved = 0;
+    for entry in entries.flatten() {
+        let target = canonical.join(entry.file_name());
+        if target.exists() {
+            log::warn!("Keeping {:?}; {:?} already exists", entry.path(), target);
+            continue;
+        }
+        fs::rename(entry.path(), &target)
+            .map_err(|e| format!("Failed to move {:?}: {}", entry.path(), e))?;
+        moved += 1;
+    }
+    // Only succeeds once everything has moved
+    let _ = fs::remove_dir(legacy);
+    Ok(moved)
+}
+
+/// Every service that gets a PID file.
+pub const PID_FILE_SERVICES: [&str; 4] = ["llama", "whisper", "server", "embedding"];
+
 /// Get the PID file path for a service.
-fn pid_file(service: &str) -> Option<PathBuf> {
-    phlox_dir().map(|dir| dir.join(format!("{}.pid", service)))
+pub fn pid_file(service: &str) -> Option<PathBuf> {
+    Some(PhloxPaths::from_data_dir().ok()?.pid_file(service))
+}
+
+/// Parse PID file contents. Rejects 0, which would address a process group.
+pub fn parse_pid(contents: &str) -> Option<u32> {
+    contents.trim().parse().ok().filter(|&pid| pid != 0)
+}
+
+/// Read the PID recorded for a service, without checking it is alive.
+pub fn read_pid_file(service: &str) -> Option<u32> {
+    PhloxPaths::from_data_dir().ok()?.read_pid_file(service)
+}
+
+/// Replace `path` with `bytes` so readers see either the old contents or
+/// the new, never a partial write: the data goes to a temp file in the same
+/// directory, which is then renamed over `path`.
+pub fn atomic_write(path: &std::path::Path, bytes: &[u8]) -> std::io::Result<()> {
+    atomic_write_with(path, |file| file.write_all(bytes))
+}
+
+fn atomic_write_with(
+    path: &std::path::Path,
+    write: impl FnOnce(&mut fs::File) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let name = path.file_name().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "path has no file name")
+    })?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(format!(".tmp-{}", std::process::id()));
+    let tmp = path.with_file_name(tmp_name);
+
+    let result = fs::File::create(&tmp)
+        .and_then(|mut file| {
+            write(&mut file)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
 }
 
 /// Write a PID file.
 fn write_pid_file(service: &str, pid: u32) {
-    if let Some(dir) = phlox_dir() {
-        fs::create_dir_all(&dir).ok();
-    }
-    if let Some(pid_file) = pid_file(service) {
-        if let Err(e) = fs::write(&pid_file, pid.to_string()) {
-            log::warn!("Failed to write PID file for {}: {}", service, e);
-        } else {
-            log::debug!("Wrote PID file for {}: PID {}", service, pid);
+    let paths = match PhloxPaths::from_data_dir() {
+        Ok(paths) => paths,
+        Err(e) => {
+            log::warn!("Not writing PID file for {}: {}", service, e);
+            return;
         }
+    };
+    if let Err(e) = paths.write_pid_file(service, pid) {
+        log::warn!("Failed to write PID file for {}: {}", service, e);
+    } else {
+        log::debug!("Wrote PID file for {}: PID {}", service, pid);
     }
 }
 
 /// Remove a PID file.
-fn remove_pid_file(service: &str) {
+pub fn remove_pid_file(service: &str) {
     if let Some(pid_file) = pid_file(service) {
         let _ = fs::remove_file(&pid_file);
     }
 }
 
+// =========================================================================
+// Settings files
+// =========================================================================
+
+/// Settings file toggling battery-saver pacing for llama-server.
+pub const BATTERY_SAVER_FILE: &str = "llm_battery_saver.txt";
+
+/// Extra llama-server arguments applied while throttling for battery:
+/// a single slot and smaller batches cap throughput, heat and power draw.
+const BATTERY_SAVER_ARGS: [&str; 6] = [
+    "--parallel",
+    "1",
+    "--batch-size",
+    "256",
+    "--ubatch-size",
+    "128",
+];
+
+/// Read a trimmed, non-empty settings file from the phlox dir.
+pub fn read_setting(name: &str) -> Option<String> {
+    PhloxPaths::from_data_dir().ok()?.read_setting(name)
+}
+
+/// Write a settings file into the phlox dir.
+pub fn write_setting(name: &str, value: &str) -> Result<(), String> {
+    PhloxPaths::from_data_dir()?.write_setting(name, value)
+}
+
+/// Parse a boolean settings value (`true`/`false`, `1`/`0`, `on`/`off`, `yes`/`no`).
+pub fn parse_bool_setting(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "1" | "on" | "yes" => Some(true),
+        "false" | "0" | "off" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Whether the user enabled battery-saver pacing (default off).
+pub fn battery_saver_enabled() -> bool {
+    read_setting(BATTERY_SAVER_FILE)
+        .and_then(|v| parse_bool_setting(&v))
+        .unwrap_or(false)
+}
+
+/// Settings file holding the llama-server context size, in tokens.
+pub const LLM_CTX_SIZE_FILE: &str = "llm_ctx_size.txt";
+
+/// Context size used when no valid setting is present.
+pub const DEFAULT_LLM_CTX_SIZE: u32 = 16384;
+/// Smallest and largest context sizes we pass to llama-server.
+pub const MIN_LLM_CTX_SIZE: u32 = 512;
+pub const MAX_LLM_CTX_SIZE: u32 = 131072;
+
+/// Clamp a requested context size into the supported range.
+pub fn clamp_ctx_size(requested: u32) -> u32 {
+    requested.clamp(MIN_LLM_CTX_SIZE, MAX_LLM_CTX_SIZE)
+}
+
+/// The configured llama-server context size, clamped (default 16384).
+pub fn llm_ctx_size() -> u32 {
+    match read_setting(LLM_CTX_SIZE_FILE) {
+        Some(value) => match value.parse::<u32>() {
+            Ok(n) => clamp_ctx_size(n),
+            Err(e) => {
+                log::warn!("Ignoring {}: {}", LLM_CTX_SIZE_FILE, e);
+                DEFAULT_LLM_CTX_SIZE
+            }
+        },
+        None => DEFAULT_LLM_CTX_SIZE,
+    }
+}
+
+/// Settings file toggling llama-server flash attention.
+pub const LLM_FLASH_ATTN_FILE: &str = "llm_flash_attn.txt";
+
+/// Flash attention is on by default only for Metal, where it is a clear
+/// win; other backends vary too much by GPU and driver.
+pub fn default_flash_attn(accelerator: hardware::Accelerator) -> bool {
+    accelerator == hardware::Accelerator::AppleSilicon
+}
+
+/// The `--flash-attn` choice for llama-server: the setting when present and
+/// valid, otherwise on where [`default_flash_attn`] says so. `None` passes
+/// no flag and leaves llama.cpp's own `auto` detection in charge.
+pub fn flash_attn_mode(accelerator: hardware::Accelerator) -> Option<bool> {
+    resolve_flash_attn(
+        read_setting(LLM_FLASH_ATTN_FILE).and_then(|v| parse_bool_setting(&v)),
+        accelerator,
+    )
+}
+
+fn resolve_flash_attn(setting: Option<bool>, accelerator: hardware::Accelerator) -> Option<bool> {
+    setting.or_else(|| default_flash_attn(accelerator).then_some(true))
+}
+
+/// Settings files overriding the llama-server and whisper-server thread counts.
+pub const LLM_THREADS_FILE: &str = "llm_threads.txt";
+pub const WHISPER_THREADS_FILE: &str = "whisper_threads.txt";
+
+/// Default inference threads: one per physical core (logical CPUs when the
+/// OS doesn't report cores), leaving one free for the UI on multi-core
+/// machines. Hyperthreads add little to matmul throughput.
+pub fn default_threads(physical_cores: Option<usize>, cpu_count: usize) -> usize {
+    let cores = physical_cores.unwrap_or(cpu_count);
+    clamp_threads(cores.saturating_sub(1), cpu_count)
+}
+
+/// Clamp a thread count into `[1, cpu_count]`.
+pub fn clamp_threads(requested: usize, cpu_count: usize) -> usize {
+    requested.clamp(1, cpu_count.max(1))
+}
+
+/// The thread count for a service: the override in `file` when valid,
+/// otherwise [`default_threads`].
+fn service_threads(file: &str) -> usize {
+    let (physical_cores, cpu_count) = hardware::cpu_core_counts();
+    let default = default_threads(physical_cores, cpu_count);
+    match read_setting(file) {
+        Some(value) => match value.parse::<usize>() {
+            Ok(n) => clamp_threads(n, cpu_count),
+            Err(e) => {
+                log::warn!("Ignoring {}: {}", file, e);
+                default
+            }
+        },
+        None => default,
+    }
+}
+
+/// Settings files holding extra llama-server and whisper-server arguments,
+/// appended after ours so they can override them.
+pub const LLM_EXTRA_ARGS_FILE: &str = "llm_extra_args.txt";
+pub const WHISPER_EXTRA_ARGS_FILE: &str = "whisper_extra_args.txt";
+
+/// Split `line` into arguments the way a POSIX shell would, without
+/// expansion: whitespace separates, single quotes are literal, and double
+/// quotes and backslashes escape. On Windows a backslash is an ordinary
+/// character, so paths like `C:\models\x.gguf` pass through unchanged;
+/// quote arguments containing spaces there. NUL bytes are refused.
+pub fn split_args(line: &str) -> Result<Vec<String>, String> {
+    split_args_with(line, !cfg!(windows))
+}
+
+/// [`split_args`], with backslash escapes on or off.
+fn split_args_with(line: &str, backslash_escapes: bool) -> Result<Vec<String>, String> {
+    if line.contains('\0') {
+        return Err("Arguments must not contain NUL bytes".to_string());
+    }
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                args.extend(current.take());
+            }
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("Unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') if backslash_escapes => match chars.next() {
+                            Some(c @ ('"' | '\\')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err("Unterminated double quote".to_string()),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err("Unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' if backslash_escapes => {
+                let escaped = chars.next().ok_or("Trailing backslash")?;
+                current.get_or_insert_with(String::new).push(escaped);
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// Extra arguments from `file`, or none when it is missing or invalid.
+fn extra_args(file: &str) -> Vec<String> {
+    let Some(value) = read_setting(file) else {
+        return Vec::new();
+    };
+    match split_args(&value) {
+        Ok(args) => {
+            log::info!(
+                "Appending extra arguments from {}: {}",
+                file,
+                redacted_command_line(&args)
+            );
+            args
+        }
+        Err(e) => {
+            log::warn!("Ignoring {}: {}", file, e);
+            Vec::new()
+        }
+    }
+}
+
+/// `args` joined for logging, with model paths cut down to their file name
+/// so the log doesn't record where the user keeps their models.
+fn redacted_command_line(args: &[String]) -> String {
+    let mut shown = Vec::with_capacity(args.len());
+    let mut redact_next = false;
+    for arg in args {
+        if std::mem::take(&mut redact_next) {
+            let name = std::path::Path::new(arg)
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            shown.push(format!(".../{}", name));
+        } else {
+            redact_next = matches!(arg.as_str(), "--model" | "-m" | "--mmproj");
+            shown.push(arg.clone());
+        }
+    }
+    shown.join(" ")
+}
+
+/// Settings file holding the service monitor's poll interval, in seconds.
+pub const MONITOR_INTERVAL_FILE: &str = "monitor_interval_secs.txt";
+
+/// Poll interval used when no valid setting is present.
+pub const DEFAULT_MONITOR_INTERVAL_SECS: u64 = 30;
+/// Bounds on the poll interval, so a typo can't spin or stall the monitor.
+pub const MIN_MONITOR_INTERVAL_SECS: u64 = 5;
+pub const MAX_MONITOR_INTERVAL_SECS: u64 = 600;
+
+/// Parse a poll interval setting, clamped into the supported range.
+pub fn parse_monitor_interval(value: &str) -> Option<Duration> {
+    let secs: u64 = value.trim().parse().ok()?;
+    Some(Duration::from_secs(
+        secs.clamp(MIN_MONITOR_INTERVAL_SECS, MAX_MONITOR_INTERVAL_SECS),
+    ))
+}
+
+/// The configured service monitor poll interval (default 30s).
+pub fn monitor_interval() -> Duration {
+    match read_setting(MONITOR_INTERVAL_FILE) {
+        Some(value) => parse_monitor_interval(&value).unwrap_or_else(|| {
+            log::warn!("Ignoring invalid {}: {:?}", MONITOR_INTERVAL_FILE, value);
+            Duration::from_secs(DEFAULT_MONITOR_INTERVAL_SECS)
+        }),
+        None => Duration::from_secs(DEFAULT_MONITOR_INTERVAL_SECS),
+    }
+}
+
+/// Settings file opting in to restarting a crashed server with the
+/// keychain-cached key.
+pub const SERVER_AUTO_RESTART_FILE: &str = "server_auto_restart.txt";
+
+/// Whether the user opted in to server auto-restart (default off).
+pub fn server_auto_restart_enabled() -> bool {
+    read_setting(SERVER_AUTO_RESTART_FILE)
+        .and_then(|v| parse_bool_setting(&v))
+        .unwrap_or(false)
+}
+
+/// Settings file with the per-service [`RestartPolicies`].
+pub const RESTART_POLICY_FILE: &str = "restart_policy.json";
+
+/// When a crashed service is restarted automatically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestartPolicy {
+    pub auto_restart: bool,
+    /// Automatic restarts allowed within the window before giving up.
+    pub max_restarts: u32,
+    /// Length of the sliding window crashes are counted over.
+    pub window_secs: u64,
+}
+
+impl RestartPolicy {
+    /// Built-in policy: restart up to [`MAX_RESTARTS_IN_WINDOW`] times per
+    /// [`RESTART_WINDOW`].
+    pub const fn new(auto_restart: bool) -> Self {
+        RestartPolicy {
+            auto_restart,
+            max_restarts: MAX_RESTARTS_IN_WINDOW as u32,
+            window_secs: RESTART_WINDOW.as_secs(),
+        }
+    }
+
+    fn window(&self) -> Duration {
+        Duration::from_secs(self.window_secs)
+    }
+}
+
+/// Restart policy per service, stored in [`RESTART_POLICY_FILE`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestartPolicies {
+    pub llama: RestartPolicy,
+    pub whisper: RestartPolicy,
+    pub embedding: RestartPolicy,
+    /// Also needs a keychain-cached key; see [`should_auto_restart_server`].
+    pub server: RestartPolicy,
+}
+
+impl Default for RestartPolicies {
+    fn default() -> Self {
+        RestartPolicies {
+            llama: RestartPolicy::new(true),
+            whisper: RestartPolicy::new(true),
+            embedding: RestartPolicy::new(true),
+            server: RestartPolicy::new(false),
+        }
+    }
+}
+
+impl RestartPolicies {
+    /// Policy for `service`; unknown names get the sidecar default.
+    pub fn get(&self, service: &str) -> RestartPolicy {
+        match service {
+            "llama" => self.llama,
+            "whisper" => self.whisper,
+            "embedding" => self.embedding,
+            "server" => self.server,
+            _ => RestartPolicy::new(true),
+        }
+    }
+}
+
+/// Parse [`RESTART_POLICY_FILE`]. Services it leaves out keep their
+/// default; the server's follows `server_opt_in`, the older
+/// [`SERVER_AUTO_RESTART_FILE`] setting.
+pub fn parse_restart_policies(json: &str, server_opt_in: bool) -> Result<RestartPolicies, String> {
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Partial {
+        llama: Option<RestartPolicy>,
+        whisper: Option<RestartPolicy>,
+        embedding: Option<RestartPolicy>,
+        server: Option<RestartPolicy>,
+    }
+    let partial: Partial = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let defaults = RestartPolicies {
+        server: RestartPolicy::new(server_opt_in),
+        ..RestartPolicies::default()
+    };
+    let policies = RestartPolicies {
+        llama: partial.llama.unwrap_or(defaults.llama),
+        whisper: partial.whisper.unwrap_or(defaults.whisper),
+        embedding: partial.embedding.unwrap_or(defaults.embedding),
+        server: partial.server.unwrap_or(defaults.server),
+    };
+    validate_restart_policies(&policies)?;
+    Ok(policies)
+}
+
+/// Reject a zero-length crash window, which would never count a crash.
+pub fn validate_restart_policies(policies: &RestartPolicies) -> Result<(), String> {
+    for service in ["llama", "whisper", "embedding", "server"] {
+        if policies.get(service).window_secs == 0 {
+            return Err(format!(
+                "{}: window_secs must be greater than zero",
+                service
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The configured restart policies, or the defaults when the file is
+/// missing or invalid.
+pub fn restart_policies() -> RestartPolicies {
+    let server_opt_in = server_auto_restart_enabled();
+    match read_setting(RESTART_POLICY_FILE) {
+        Some(json) => parse_restart_policies(&json, server_opt_in).unwrap_or_else(|e| {
+            log::warn!("Ignoring invalid {}: {}", RESTART_POLICY_FILE, e);
+            parse_restart_policies("{}", server_opt_in).unwrap()
+        }),
+        None => parse_restart_policies("{}", server_opt_in).unwrap(),
+    }
+}
+
+/// A dead server can only be restarted unattended when the user opted in
+/// and a cached key is available to unlock it.
+pub fn should_auto_restart_server(opted_in: bool, caching_enabled: bool, key_cached: bool) -> bool {
+    opted_in && caching_enabled && key_cached
+}
+
+/// Throttle only when battery saver is on and we know we're on battery.
+fn should_throttle_for_battery(battery_saver: bool, on_battery: Option<bool>) -> bool {
+    battery_saver && on_battery == Some(true)
+}
+
 // =========================================================================
 // Binary / model discovery
 // =========================================================================
 
+/// How the app was installed, inferred from the executable's location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallType {
+    /// `cargo run` / `tauri dev` out of a `target/` directory.
+    Dev,
+    /// macOS `.app` bundle (shipped in the dmg).
+    Dmg,
+    /// Linux AppImage, mounted under a temporary `.mount_*` directory.
+    AppImage,
+    /// Linux deb/rpm install under `/usr`.
+    SystemPackage,
+    /// Windows installer (msi/nsis).
+    Msi,
+    Unknown,
+}
+
+/// Detected install type plus the directory bundled resources live in.
+#[derive(Debug, Clone, Serialize)]
+pub struct InstallInfo {
+    pub install_type: InstallType,
+    pub resource_root: Option<PathBuf>,
+}
+
+/// Classify an executable path into an [`InstallInfo`].
+/// `appimage` is the value of the `APPIMAGE` env var set by the AppImage runtime.
+fn classify_install(exe_path: &std::path::Path, appimage: Option<&str>) -> InstallInfo {
+    let normalized = exe_path.to_string_lossy().replace('\\', "/");
+    let exe_dir = exe_path.parent().map(|p| p.to_path_buf());
+
+    let install_type =
+        if normalized.contains("/target/debug/") || normalized.contains("/target/release/") {
+            InstallType::Dev
+        } else if normalized.contains(".app/Contents/MacOS/") {
+            InstallType::Dmg
+        } else if appimage.is_some_and(|a| !a.is_empty()) || normalized.contains("/.mount_") {
+            InstallType::AppImage
+        } else if normalized.to_lowercase().ends_with(".exe") {
+            InstallType::Msi
+        } else if normalized.starts_with("/usr/") {
+            InstallType::SystemPackage
+        } else {
+            InstallType::Unknown
+        };
+
+    let resource_root = match install_type {
+        // The `phlox-server` wrapper points at ../Resources/server_dist/server
+        InstallType::Dmg => exe_dir
+            .as_ref()
+            .and_then(|d| d.parent())
+            .map(|contents| contents.join("Resources")),
+        // Tauri installs Linux resources to <prefix>/lib/<productName>
+        InstallType::AppImage | InstallType::SystemPackage => exe_dir
+            .as_ref()
+            .and_then(|d| d.parent())
+            .map(|prefix| prefix.join("lib").join("Phlox")),
+        InstallType::Dev | InstallType::Msi => exe_dir,
+        InstallType::Unknown => None,
+    };
+
+    InstallInfo {
+        install_type,
+        resource_root,
+    }
+}
+
+/// Detect how the running app was installed.
+pub fn detect_install() -> InstallInfo {
+    match std::env::current_exe() {
+        Ok(exe) => classify_install(&exe, std::env::var("APPIMAGE").ok().as_deref()),
+        Err(e) => {
+            log::warn!("Failed to resolve current executable: {}", e);
+            InstallInfo {
+                install_type: InstallType::Unknown,
+                resource_root: None,
+            }
+        }
+    }
+}
+
+/// Directory holding the app executable and its bundled sidecars.
+fn exe_dir() -> Option<PathBuf> {
+    Some(std::env::current_exe().ok()?.parent()?.to_path_buf())
+}
+
+fn llama_server_in(exe_dir: &std::path::Path) -> PathBuf {
+    if cfg!(target_os = "windows") {
+        exe_dir.join("phlox-llama-server.exe")
+    } else {
+        exe_dir.join("phlox-llama-server")
+    }
+}
+
+fn whisper_server_in(exe_dir: &std::path::Path) -> PathBuf {
+    if cfg!(target_os = "windows") {
+        exe_dir.join("phlox-whisper-server.exe")
+    } else {
+        exe_dir.join("phlox-whisper-server")
+    }
+}
+
+/// The 'phlox-server' binary is a wrapper that points to ../Resources/server_dist/server.
+fn python_server_in(exe_dir: &std::path::Path) -> PathBuf {
+    exe_dir.join("phlox-server")
+}
+
+/// Outcome of [`verify_bundled_binaries`].
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct BinaryVerification {
+    /// Binaries matching the manifest.
+    pub ok: Vec<String>,
+    /// Binaries whose SHA-256 differs from the manifest.
+    pub mismatched: Vec<String>,
+    /// Binaries in the manifest that are not next to the app.
+    pub missing: Vec<String>,
+}
+
+/// Check the sidecars next to the app against the `binaries.sha256`
+/// manifest build.rs recorded, which ships as a bundle resource
+/// (`Contents/Resources` on macOS).
+pub fn verify_bundled_binaries(
+    resource_dir: &std::path::Path,
+) -> Result<BinaryVerification, String> {
+    verify_binaries_in(
+        &resource_dir.join(binary_manifest::MANIFEST_FILE),
+        &exe_dir().ok_or("Could not determine the app directory")?,
+    )
+}
+
+fn verify_binaries_in(
+    manifest_path: &std::path::Path,
+    dir: &std::path::Path,
+) -> Result<BinaryVerification, String> {
+    let text = fs::read_to_string(manifest_path)
+        .map_err(|e| format!("Failed to read {:?}: {}", manifest_path, e))?;
+    let recorded = binary_manifest::parse_manifest(&text)?;
+
+    let mut report = BinaryVerification::default();
+    let mut current = binary_manifest::Manifest::new();
+    for name in recorded.keys() {
+        let path = dir.join(name);
+        if !path.exists() {
+            report.missing.push(name.clone());
+            continue;
+        }
+        current.insert(name.clone(), download::sha256_file(&path)?);
+    }
+    report.mismatched = binary_manifest::changed_hashes(&recorded, &current)
+        .iter()
+        .map(|change| change.name.to_string())
+        .collect();
+    report.ok = current
+        .into_keys()
+        .filter(|name| !report.mismatched.contains(name))
+        .collect();
+
+    if !report.mismatched.is_empty() || !report.missing.is_empty() {
+        log::warn!(
+            "Bundled binaries do not match {}: mismatched {:?}, missing {:?}",
+            binary_manifest::MANIFEST_FILE,
+            report.mismatched,
+            report.missing
+        );
+    }
+    Ok(report)
+}
+
 /// Find the phlox-llama-server binary path.
 fn find_llama_server() -> Option<PathBuf> {
-    let exe_dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
-
-    #[cfg(target_os = "windows")]
-    let path = exe_dir.join("phlox-llama-server.exe");
-    #[cfg(not(target_os = "windows"))]
-    let path = exe_dir.join("phlox-llama-server");
+    let path = llama_server_in(&exe_dir()?);
 
     if path.exists() {
         Some(path)
@@ -132,12 +1162,7 @@ fn find_llama_server() -> Option<PathBuf> {
 
 /// Find the phlox-whisper-server binary path.
 fn find_whisper_server() -> Option<PathBuf> {
-    let exe_dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
-
-    #[cfg(target_os = "windows")]
-    let path = exe_dir.join("phlox-whisper-server.exe");
-    #[cfg(not(target_os = "windows"))]
-    let path = exe_dir.join("phlox-whisper-server");
+    let path = whisper_server_in(&exe_dir()?);
 
     if path.exists() {
         Some(path)
@@ -148,10 +1173,8 @@ fn find_whisper_server() -> Option<PathBuf> {
 }
 
 /// Find the server (Python) binary path.
-/// The 'phlox-server' binary is a wrapper that points to ../Resources/server_dist/server.
 fn find_python_server() -> Option<PathBuf> {
-    let exe_dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
-    let path = exe_dir.join("phlox-server");
+    let path = python_server_in(&exe_dir()?);
 
     if path.exists() {
         Some(path)
@@ -161,61 +1184,238 @@ fn find_python_server() -> Option<PathBuf> {
     }
 }
 
+/// Settings file pointing the models directories somewhere other than the
+/// phlox dir, e.g. an external drive. It names a directory that holds the
+/// usual `llm_models`, `whisper_models` and `embedding_models` folders.
+/// The Python server resolves it the same way (`get_models_dir` in
+/// server/constants.py).
+pub const MODELS_DIR_FILE: &str = "models_dir.txt";
+
+/// Models subfolder for a service.
+pub fn models_subdir(service: &str) -> Result<&'static str, String> {
+    match service {
+        "llama" => Ok("llm_models"),
+        "whisper" => Ok("whisper_models"),
+        "embedding" => Ok("embedding_models"),
+        _ => Err(format!("Unknown service: {}", service)),
+    }
+}
+
+/// Check a models directory override: it must be an absolute path to an
+/// existing directory.
+pub fn validate_models_root(path: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(path.trim());
+    if !path.is_absolute() {
+        return Err(format!(
+            "Models directory {:?} must be an absolute path",
+            path
+        ));
+    }
+    if !path.is_dir() {
+        return Err(format!(
+            "Models directory {:?} is not an existing directory",
+            path
+        ));
+    }
+    Ok(path)
+}
+
+/// The override when it is set and valid, otherwise `default`.
+fn resolve_models_root(default: PathBuf, override_path: Option<&str>) -> PathBuf {
+    match override_path.map(validate_models_root) {
+        Some(Ok(path)) => path,
+        Some(Err(e)) => {
+            log::warn!("Ignoring {}: {}", MODELS_DIR_FILE, e);
+            default
+        }
+        None => default,
+    }
+}
+
+/// Directory holding the models subfolders: the `models_dir.txt` override
+/// when valid, otherwise the phlox dir.
+pub fn models_root() -> Option<PathBuf> {
+    Some(PhloxPaths::from_data_dir().ok()?.models_root())
+}
+
+/// Models directory for a service (`llm_models`, `whisper_models`, ...).
+pub fn models_dir(service: &str) -> Result<PathBuf, String> {
+    PhloxPaths::from_data_dir()?.models_dir(service)
+}
+
+/// Ending of every "no model downloaded" error, so callers can tell them apart.
+pub const MISSING_MODEL_HINT: &str = "download one first";
+
+fn missing_model_error(kind: &str) -> PmError {
+    PmError::new(
+        PmErrorKind::ModelMissing,
+        format!("No {} model found; {}", kind, MISSING_MODEL_HINT),
+    )
+}
+
+/// Selection file naming the active LLM model inside `llm_models`.
+pub const LLM_MODEL_FILE: &str = "llm_model.txt";
+
+/// Only llama has a selection file; whisper and embedding use the first
+/// model found.
+pub fn ensure_model_switchable(service: &str) -> Result<(), String> {
+    if service == "llama" {
+        Ok(())
+    } else {
+        Err(format!("Model switching is not supported for {}", service))
+    }
+}
+
+/// Check that a model selection is a bare filename: no path separators,
+/// drive prefixes, `..`, or NUL bytes.
+pub fn sanitize_model_filename(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Model filename is empty".to_string());
+    }
+    if name == "." || name == ".." || name.contains(['/', '\\', ':', '\0']) {
+        return Err(format!("Invalid model filename: {:?}", name));
+    }
+    Ok(name)
+}
+
+/// Check that a model selection is a relative path inside the models
+/// directory, e.g. `qwen/model.gguf`. Each component must be a valid bare
+/// filename. Returns the path with `/` separators.
+pub fn sanitize_model_path(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Model filename is empty".to_string());
+    }
+    let components = name
+        .split(['/', '\\'])
+        .map(|part| match sanitize_model_filename(part) {
+            Ok(part) if part.len() == part.trim().len() => Ok(part),
+            _ => Err(format!("Invalid model filename: {:?}", name)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(components.join("/"))
+}
+
+/// Resolve a selected model inside `models_dir`, refusing anything that
+/// canonicalizes outside of it (e.g. through a symlink).
+fn resolve_model_in_dir(models_dir: &std::path::Path, name: &str) -> Result<PathBuf, PmError> {
+    let name = sanitize_model_path(name)?;
+    let dir = models_dir
+        .canonicalize()
+        .map_err(|e| format!("Models directory {:?} unavailable: {}", models_dir, e))?;
+    let path = dir.join(&name).canonicalize().map_err(|e| {
+        PmError::new(
+            PmErrorKind::ModelMissing,
+            format!("Model {} not found: {}", name, e),
+        )
+    })?;
+
+    if !path.starts_with(&dir) {
+        return Err(format!("Model {} resolves outside the models directory", name).into());
+    }
+    if !path.is_file() {
+        return Err(format!("Model {} is not a file", name).into());
+    }
+    Ok(path)
+}
+
+/// Validate `filename` (a path relative to `llm_models`) and write it to the
+/// selection file.
+pub fn select_llama_model(filename: &str) -> Result<PathBuf, PmError> {
+    let models_dir = models_dir("llama")?;
+    let path = resolve_model_in_dir(&models_dir, filename)?;
+    write_setting(LLM_MODEL_FILE, &sanitize_model_path(filename)?)?;
+    log::info!("Selected LLM model: {:?}", path);
+    Ok(path)
+}
+
 /// Find a llama model in the models directory.
 fn find_llama_model() -> Option<PathBuf> {
-    let models_dir = phlox_dir()?.join("llm_models");
+    let models_dir = models_dir("llama").ok()?;
+    find_llama_model_in(&models_dir, read_setting(LLM_MODEL_FILE).as_deref())
+}
 
+/// Resolve `selection` inside `models_dir`, or scan when there is none.
+/// The selection file is writable by the UI, so one that fails validation
+/// (a path escaping the directory, a missing file) is logged and ignored.
+fn find_llama_model_in(models_dir: &std::path::Path, selection: Option<&str>) -> Option<PathBuf> {
     // Prefer Python's explicit selection file over a directory scan
-    let model_file = phlox_dir()?.join("llm_model.txt");
-    if let Ok(model_name) = fs::read_to_string(&model_file) {
-        let model_path = models_dir.join(model_name.trim());
-        if model_path.exists() {
-            return Some(model_path);
+    if let Some(model_name) = selection {
+        match resolve_model_in_dir(models_dir, model_name) {
+            Ok(model_path) => return Some(model_path),
+            Err(e) => log::warn!("Ignoring {}: {}", LLM_MODEL_FILE, e),
         }
     }
 
     // Scan for any .gguf file that isn't a multimodal projector
-    if let Ok(entries) = fs::read_dir(&models_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.extension()?.to_str()? == "gguf" {
-                let name = path.file_name()?.to_str()?.to_lowercase();
-                if !name.contains("mmproj") {
-                    return Some(path);
-                }
-            }
-        }
-    }
+    find_gguf_files(models_dir, MODEL_SCAN_DEPTH)
+        .into_iter()
+        .find(|path| !is_mmproj(path))
+}
 
-    None
+/// Find the companion multimodal projector (mmproj) for `model`. Only the
+/// model's own folder is searched, so another model's projector in a
+/// sibling subfolder is never paired with it.
+fn find_llama_mmproj(model: &std::path::Path) -> Option<PathBuf> {
+    find_gguf_files(model.parent()?, 0)
+        .into_iter()
+        .find(|path| is_mmproj(path))
 }
 
-/// Find the companion multimodal projector (mmproj) for the loaded model.
-fn find_llama_mmproj() -> Option<PathBuf> {
-    let models_dir = phlox_dir()?.join("llm_models");
+fn is_mmproj(path: &std::path::Path) -> bool {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_lowercase().contains("mmproj"))
+        .unwrap_or(false)
+}
 
-    if let Ok(entries) = fs::read_dir(&models_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.extension()?.to_str()? == "gguf"
-                && path
-                    .file_name()?
-                    .to_str()?
-                    .to_lowercase()
-                    .contains("mmproj")
-            {
-                return Some(path);
+/// How many directory levels below a models directory are scanned, so
+/// models can be grouped into subfolders (e.g. by family).
+const MODEL_SCAN_DEPTH: usize = 3;
+
+/// `.gguf` files under `dir`, shallowest first and sorted by path within a
+/// level. Hidden entries are skipped and symlinked directories are not
+/// followed, so a link loop can't trap the scan.
+fn find_gguf_files(dir: &std::path::Path, max_depth: usize) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut level = vec![dir.to_path_buf()];
+
+    for _ in 0..=max_depth {
+        let mut subdirs = Vec::new();
+        let mut files = Vec::new();
+        for dir in &level {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                if entry.file_name().to_string_lossy().starts_with('.') {
+                    continue;
+                }
+                let Ok(file_type) = entry.file_type() else {
+                    continue;
+                };
+                let path = entry.path();
+                if file_type.is_dir() {
+                    subdirs.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "gguf") && path.is_file() {
+                    files.push(path);
+                }
             }
         }
+        files.sort();
+        found.extend(files);
+        subdirs.sort();
+        level = subdirs;
     }
-
-    None
+    found
 }
 
 /// Find the STT in the models directory.
 fn find_whisper_model() -> Option<PathBuf> {
-    let models_dir = phlox_dir()?.join("whisper_models");
+    find_whisper_model_in(&models_dir("whisper").ok()?)
+}
 
+fn find_whisper_model_in(models_dir: &std::path::Path) -> Option<PathBuf> {
     // Primary: the fixed Omi Med STT q8_0 GGUF.
     let primary = models_dir.join("omi-med-stt-v1-q8_0.gguf");
     if primary.exists() {
@@ -223,91 +1423,425 @@ fn find_whisper_model() -> Option<PathBuf> {
     }
 
     // Fallback: any .gguf file in the models directory.
-    if let Ok(entries) = fs::read_dir(&models_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.extension()?.to_str()? == "gguf" {
-                return Some(path);
-            }
+    find_gguf_files(models_dir, MODEL_SCAN_DEPTH)
+        .into_iter()
+        .next()
+}
+
+/// One line of the preflight report.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PreflightItem {
+    pub name: &'static str,
+    pub ok: bool,
+    /// Where the item was found, or where it was expected for binaries.
+    pub path: Option<String>,
+    pub message: String,
+}
+
+impl PreflightItem {
+    pub fn new(name: &'static str, ok: bool, path: Option<String>, message: String) -> Self {
+        PreflightItem {
+            name,
+            ok,
+            path,
+            message,
         }
     }
+}
+
+fn binary_item(name: &'static str, path: PathBuf) -> PreflightItem {
+    let ok = path.is_file();
+    let message = if ok {
+        "Found".to_string()
+    } else {
+        format!("{} binary is missing; reinstall Phlox", name)
+    };
+    PreflightItem::new(name, ok, Some(path.to_string_lossy().into_owned()), message)
+}
+
+fn model_item(
+    name: &'static str,
+    found: Option<PathBuf>,
+    models_dir: &std::path::Path,
+) -> PreflightItem {
+    match found {
+        Some(path) => PreflightItem::new(
+            name,
+            true,
+            Some(path.to_string_lossy().into_owned()),
+            "Found".to_string(),
+        ),
+        None => PreflightItem::new(
+            name,
+            false,
+            None,
+            format!("No .gguf model in {:?}; {}", models_dir, MISSING_MODEL_HINT),
+        ),
+    }
+}
+
+/// Check the bundled binaries in `exe_dir` and the models under
+/// `models_root`, without starting anything.
+fn preflight_in(
+    exe_dir: &std::path::Path,
+    models_root: &std::path::Path,
+    llm_selection: Option<&str>,
+) -> Result<Vec<PreflightItem>, String> {
+    let llm_dir = models_root.join(models_subdir("llama")?);
+    let whisper_dir = models_root.join(models_subdir("whisper")?);
+    Ok(vec![
+        binary_item("server", python_server_in(exe_dir)),
+        binary_item("llama-server", llama_server_in(exe_dir)),
+        binary_item("whisper-server", whisper_server_in(exe_dir)),
+        model_item(
+            "llm-model",
+            find_llama_model_in(&llm_dir, llm_selection),
+            &llm_dir,
+        ),
+        model_item(
+            "whisper-model",
+            find_whisper_model_in(&whisper_dir),
+            &whisper_dir,
+        ),
+    ])
+}
 
-    None
+/// Report which binaries and models the services need are present.
+pub fn preflight() -> Result<Vec<PreflightItem>, String> {
+    let exe_dir = exe_dir().ok_or("Could not determine the app directory")?;
+    let models_root = require_dir(models_root())?;
+    preflight_in(
+        &exe_dir,
+        &models_root,
+        read_setting(LLM_MODEL_FILE).as_deref(),
+    )
+}
+
+/// Bare filename of a resolved model, for status reporting.
+fn model_file_name(path: &std::path::Path) -> Option<String> {
+    path.file_name().map(|n| n.to_string_lossy().into_owned())
+}
+
+/// Highest GGUF format version we know how to load.
+const MAX_GGUF_VERSION: u32 = 3;
+
+/// Check that `path` starts with a GGUF header (magic plus a known version)
+/// so a truncated or mislabelled download fails with a clear error instead
+/// of an opaque one from the server.
+fn validate_gguf(path: &std::path::Path) -> Result<(), String> {
+    use std::io::Read;
+
+    let mut header = [0u8; 8];
+    fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .map_err(|e| format!("Model {:?} is unreadable or truncated: {}", path, e))?;
+
+    if &header[..4] != b"GGUF" {
+        return Err(format!(
+            "Model {:?} is not a GGUF file (bad magic {:02x?}); re-download it",
+            path,
+            &header[..4]
+        ));
+    }
+    let version = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    if version == 0 || version > MAX_GGUF_VERSION {
+        return Err(format!(
+            "Model {:?} has unsupported GGUF version {}",
+            path, version
+        ));
+    }
+    Ok(())
 }
 
 /// Find an embedding model in the models directory.
 fn find_embedding_model() -> Option<PathBuf> {
-    let models_dir = phlox_dir()?.join("embedding_models");
+    let models_dir = models_dir("embedding").ok()?;
+
+    find_gguf_files(&models_dir, MODEL_SCAN_DEPTH)
+        .into_iter()
+        .next()
+}
+
+// =========================================================================
+// Output capture
+// =========================================================================
+
+/// One line of captured output from a managed process.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    pub service: &'static str,
+    /// `stdout` or `stderr`.
+    pub stream: &'static str,
+    pub line: String,
+}
+
+/// Lines of stderr kept per service for [`LastFailure`] reports.
+pub const STDERR_TAIL_LINES: usize = 50;
+
+/// Fans captured child output out to any number of subscribers.
+#[derive(Default)]
+pub struct LogHub {
+    /// Service filter (`None` = all) and the subscriber's channel.
+    subscribers: Mutex<Vec<(Option<String>, Sender<LogLine>)>>,
+    /// The most recent stderr lines per service.
+    stderr_tails: Mutex<HashMap<&'static str, VecDeque<String>>>,
+}
+
+impl LogHub {
+    /// Subscribe to one service's output, or to every service with `None`.
+    /// Dropping the receiver unsubscribes.
+    pub fn subscribe(&self, service: Option<&str>) -> Receiver<LogLine> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers
+            .lock()
+            .unwrap()
+            .push((service.map(str::to_string), tx));
+        rx
+    }
+
+    /// The most recent stderr lines of `service`, oldest first.
+    pub fn stderr_tail(&self, service: &str) -> Vec<String> {
+        self.stderr_tails
+            .lock()
+            .unwrap()
+            .get(service)
+            .map(|tail| tail.iter().cloned().collect())
+            .unwrap_or_default()
+    }
 
-    if let Ok(entries) = fs::read_dir(&models_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.extension()?.to_str()? == "gguf" {
-                return Some(path);
+    fn publish(&self, line: LogLine) {
+        if line.stream == "stderr" {
+            let mut tails = self.stderr_tails.lock().unwrap();
+            let tail = tails.entry(line.service).or_default();
+            if tail.len() == STDERR_TAIL_LINES {
+                tail.pop_front();
             }
+            tail.push_back(line.line.clone());
+        }
+
+        // A failed send means the receiver is gone; drop that subscriber.
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|(filter, tx)| match filter {
+                Some(service) if service != line.service => true,
+                _ => tx.send(line.clone()).is_ok(),
+            });
+    }
+
+    /// Take the child's piped stdout/stderr and forward each line to the log
+    /// and to subscribers. The threads end when the child closes its pipes.
+    /// The stderr tail starts over with each new process.
+    fn attach(self: &Arc<Self>, service: &'static str, child: &mut Child) {
+        self.stderr_tails.lock().unwrap().remove(service);
+        if let Some(stdout) = child.stdout.take() {
+            self.pump(service, "stdout", stdout);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            self.pump(service, "stderr", stderr);
         }
     }
 
-    None
+    fn pump(
+        self: &Arc<Self>,
+        service: &'static str,
+        stream: &'static str,
+        reader: impl std::io::Read + Send + 'static,
+    ) {
+        let hub = Arc::clone(self);
+        thread::spawn(move || {
+            for line in BufReader::new(reader).lines().map_while(Result::ok) {
+                log::debug!("[{} {}] {}", service, stream, line);
+                hub.publish(LogLine {
+                    service,
+                    stream,
+                    line,
+                });
+            }
+        });
+    }
 }
 
 // =========================================================================
 // Spawn helpers (free functions)
 // =========================================================================
 
+/// Extra llama-server arguments for model families that need them, keyed
+/// by a lowercase filename substring. Every matching entry applies, in order.
+const MODEL_QUIRKS: &[(&str, &[&str])] = &[
+    // Qwen3 reasons out loud by default; notes need the answer only
+    (
+        "qwen3",
+        &["--chat-template-kwargs", r#"{"enable_thinking": false}"#],
+    ),
+];
+
+/// The [`MODEL_QUIRKS`] arguments that apply to a model filename.
+fn model_quirk_args(filename: &str) -> Vec<&'static str> {
+    let filename = filename.to_lowercase();
+    MODEL_QUIRKS
+        .iter()
+        .filter(|(pattern, _)| filename.contains(pattern))
+        .flat_map(|(_, args)| args.iter().copied())
+        .collect()
+}
+
+/// Options for one llama-server launch, shared by the chat and embedding
+/// services.
+struct LlamaLaunch<'a> {
+    port: u16,
+    model: &'a std::path::Path,
+    ctx_size: u32,
+    gpu_layers: u32,
+    threads: Option<usize>,
+    flash_attn: Option<bool>,
+    /// Serve embeddings instead of chat completions. Chat-only options
+    /// (templates, model quirks, mmproj, battery pacing) are left out.
+    embeddings: bool,
+    mmproj: Option<&'a std::path::Path>,
+    battery_saver: bool,
+}
+
+/// Build the llama-server argument list for `launch`.
+fn llama_server_args(launch: &LlamaLaunch) -> Vec<String> {
+    let mut args: Vec<String> = vec![
+        "--port".into(),
+        launch.port.to_string(),
+        "--host".into(),
+        "127.0.0.1".into(),
+        "--model".into(),
+        launch.model.to_string_lossy().into_owned(),
+        "--ctx-size".into(),
+        launch.ctx_size.to_string(),
+        "--n-gpu-layers".into(),
+        launch.gpu_layers.to_string(),
+    ];
+    if let Some(threads) = launch.threads {
+        args.extend(["--threads".into(), threads.to_string()]);
+        args.extend(["--threads-batch".into(), threads.to_string()]);
+    }
+    if let Some(flash_attn) = launch.flash_attn {
+        args.extend([
+            "--flash-attn".into(),
+            if flash_attn { "on" } else { "off" }.into(),
+        ]);
+    }
+    args.extend(["--cache-type-k", "q8_0"].map(String::from));
+    // llama-server refuses a quantized V cache with flash attention off
+    if launch.flash_attn != Some(false) {
+        args.extend(["--cache-type-v", "q8_0"].map(String::from));
+    }
+
+    if launch.embeddings {
+        args.push("--embedding".into());
+        return args;
+    }
+
+    args.push("--jinja".into());
+    if launch.battery_saver {
+        args.extend(BATTERY_SAVER_ARGS.map(String::from));
+    }
+    if let Some(filename) = launch.model.file_name().and_then(|n| n.to_str()) {
+        args.extend(model_quirk_args(filename).into_iter().map(String::from));
+    }
+    if let Some(mmproj) = launch.mmproj {
+        args.extend(["--mmproj".into(), mmproj.to_string_lossy().into_owned()]);
+    }
+    args
+}
+
+/// Build the whisper-server argument list. The server exits on any
+/// option it doesn't know.
+fn whisper_server_args(port: u16, model: &std::path::Path, threads: usize) -> Vec<String> {
+    vec![
+        "--port".into(),
+        port.to_string(),
+        "--host".into(),
+        "127.0.0.1".into(),
+        "--model".into(),
+        model.to_string_lossy().into_owned(),
+        "--threads".into(),
+        threads.to_string(),
+        "--max-seconds".into(),
+        "240".into(),
+        "--chunk-seconds".into(),
+        "240".into(),
+        "--overlap".into(),
+        "5".into(),
+    ]
+}
+
 /// Start the llama server (returns a raw [`ManagedProcess`]).
-fn start_llama(port: Option<u16>) -> Result<ManagedProcess, String> {
+fn start_llama(port: Option<u16>) -> Result<ManagedProcess, PmError> {
     let server_path = find_llama_server().ok_or("phlox-llama-server binary not found")?;
-    let model_path = find_llama_model().ok_or("No LLM model found")?;
+    let model_path = find_llama_model().ok_or_else(|| missing_model_error("LLM"))?;
+    validate_gguf(&model_path)?;
 
     let actual_port = port.unwrap_or(LLAMA_PORT);
+    let ctx_size = llm_ctx_size();
+    let accelerator = hardware::detect_accelerator();
+    let model_bytes = fs::metadata(&model_path).ok().map(|m| m.len());
+    let gpu_layers = hardware::gpu_layers(accelerator, model_bytes);
+    let threads = service_threads(LLM_THREADS_FILE);
+    let flash_attn = flash_attn_mode(accelerator);
 
     log::info!("Starting phlox-llama-server from: {:?}", server_path);
     log::info!(
-        "phlox-llama-server model: {:?}, port: {}",
+        "phlox-llama-server model: {:?}, port: {}, ctx-size: {}, threads: {}",
         model_path,
-        actual_port
+        actual_port,
+        ctx_size,
+        threads
     );
-
-    let mut cmd = Command::new(&server_path);
-    cmd.arg("--port")
-        .arg(actual_port.to_string())
-        .arg("--host")
-        .arg("127.0.0.1")
-        .arg("--model")
-        .arg(model_path.to_string_lossy().as_ref())
-        .arg("--ctx-size")
-        .arg("16384")
-        .arg("--n-gpu-layers")
-        .arg("99")
-        .arg("--jinja")
-        .arg("--cache-type-k")
-        .arg("q8_0")
-        .arg("--cache-type-v")
-        .arg("q8_0");
-
-    // Check for Qwen3 model
-    if let Some(filename) = model_path.file_name().and_then(|n| n.to_str()) {
-        if filename.to_lowercase().contains("qwen3") {
-            cmd.arg("--chat-template-kwargs")
-                .arg(r#"{"enable_thinking": false}"#);
+    log::info!(
+        "phlox-llama-server accelerator: {:?}, gpu layers: {}, flash attention: {}",
+        accelerator,
+        gpu_layers,
+        match flash_attn {
+            Some(true) => "on",
+            Some(false) => "off",
+            None => "auto",
         }
+    );
+
+    ensure_port_free(actual_port, "llama", "phlox-llama-server")?;
+
+    let battery_saver =
+        should_throttle_for_battery(battery_saver_enabled(), hardware::on_battery_power());
+    if battery_saver {
+        log::info!("Battery saver active: limiting llama-server throughput");
     }
 
     // Load the multimodal projector (vision models) if a companion mmproj is present.
-    if let Some(mmproj_path) = find_llama_mmproj() {
+    let mmproj_path = find_llama_mmproj(&model_path);
+    if let Some(mmproj_path) = &mmproj_path {
         log::info!("Loading multimodal projector: {:?}", mmproj_path);
-        cmd.arg("--mmproj")
-            .arg(mmproj_path.to_string_lossy().as_ref());
     }
 
+    let mut args = llama_server_args(&LlamaLaunch {
+        port: actual_port,
+        model: &model_path,
+        ctx_size,
+        gpu_layers,
+        threads: Some(threads),
+        flash_attn,
+        embeddings: false,
+        mmproj: mmproj_path.as_deref(),
+        battery_saver,
+    });
+    args.extend(extra_args(LLM_EXTRA_ARGS_FILE));
+    log::debug!("phlox-llama-server args: {}", redacted_command_line(&args));
+
+    let mut cmd = Command::new(&server_path);
+    cmd.args(&args);
+
     #[cfg(unix)]
     {
         use std::os::unix::process::CommandExt;
         cmd.process_group(0);
     }
 
-    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
 
     let child = cmd
         .spawn()
@@ -322,36 +1856,41 @@ fn start_llama(port: Option<u16>) -> Result<ManagedProcess, String> {
         port: actual_port,
         drain_handles: None,
         drain_shutdown: None,
+        gpu_layers: Some(gpu_layers),
+        flash_attn,
+        started_at: Instant::now(),
+        model_name: model_file_name(&model_path),
     })
 }
 
 /// Start the whisper server (returns a raw [`ManagedProcess`]).
-fn start_whisper(port: Option<u16>) -> Result<ManagedProcess, String> {
+fn start_whisper(port: Option<u16>) -> Result<ManagedProcess, PmError> {
     let server_path = find_whisper_server().ok_or("phlox-whisper-server binary not found")?;
-    let model_path = find_whisper_model().ok_or("No Whisper model found")?;
+    let model_path = find_whisper_model().ok_or_else(|| missing_model_error("Whisper"))?;
+    validate_gguf(&model_path)?;
 
     let actual_port = port.unwrap_or(WHISPER_PORT);
+    let threads = service_threads(WHISPER_THREADS_FILE);
 
     log::info!("Starting phlox-whisper-server from: {:?}", server_path);
     log::info!(
-        "phlox-whisper-server model: {:?}, port: {}",
+        "phlox-whisper-server model: {:?}, port: {}, threads: {}",
         model_path,
-        actual_port
+        actual_port,
+        threads
+    );
+
+    ensure_port_free(actual_port, "whisper", "phlox-whisper-server")?;
+
+    let mut args = whisper_server_args(actual_port, &model_path, threads);
+    args.extend(extra_args(WHISPER_EXTRA_ARGS_FILE));
+    log::debug!(
+        "phlox-whisper-server args: {}",
+        redacted_command_line(&args)
     );
 
     let mut cmd = Command::new(&server_path);
-    cmd.arg("--port")
-        .arg(actual_port.to_string())
-        .arg("--host")
-        .arg("127.0.0.1")
-        .arg("--model")
-        .arg(model_path.to_string_lossy().as_ref())
-        .arg("--max-seconds")
-        .arg("240")
-        .arg("--chunk-seconds")
-        .arg("240")
-        .arg("--overlap")
-        .arg("5");
+    cmd.args(&args);
 
     #[cfg(unix)]
     {
@@ -359,7 +1898,7 @@ fn start_whisper(port: Option<u16>) -> Result<ManagedProcess, String> {
         cmd.process_group(0);
     }
 
-    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
 
     let child = cmd
         .spawn()
@@ -374,35 +1913,41 @@ fn start_whisper(port: Option<u16>) -> Result<ManagedProcess, String> {
         port: actual_port,
         drain_handles: None,
         drain_shutdown: None,
+        gpu_layers: None,
+        flash_attn: None,
+        started_at: Instant::now(),
+        model_name: model_file_name(&model_path),
     })
 }
 
+/// Context size for the embedding server; note chunks are short.
+const EMBEDDING_CTX_SIZE: u32 = 1024;
+
 /// Start the embedding server (returns a raw [`ManagedProcess`]).
-fn start_embedding(port: Option<u16>) -> Result<ManagedProcess, String> {
+fn start_embedding(port: Option<u16>) -> Result<ManagedProcess, PmError> {
     let server_path = find_llama_server().ok_or("phlox-llama-server binary not found")?;
-    let model_path = find_embedding_model().ok_or("No embedding model found")?;
+    let model_path = find_embedding_model().ok_or_else(|| missing_model_error("embedding"))?;
+    validate_gguf(&model_path)?;
 
     let actual_port = port.unwrap_or(EMBEDDING_PORT);
 
     log::info!("Starting embedding server from: {:?}", server_path);
     log::info!("embedding model: {:?}, port: {}", model_path, actual_port);
 
+    ensure_port_free(actual_port, "embedding", "phlox-llama-server")?;
+
     let mut cmd = Command::new(&server_path);
-    cmd.arg("--port")
-        .arg(actual_port.to_string())
-        .arg("--host")
-        .arg("127.0.0.1")
-        .arg("--model")
-        .arg(model_path.to_string_lossy().as_ref())
-        .arg("--embedding")
-        .arg("--n-gpu-layers")
-        .arg("99")
-        .arg("--ctx-size")
-        .arg("1024")
-        .arg("--cache-type-k")
-        .arg("q8_0")
-        .arg("--cache-type-v")
-        .arg("q8_0");
+    cmd.args(llama_server_args(&LlamaLaunch {
+        port: actual_port,
+        model: &model_path,
+        ctx_size: EMBEDDING_CTX_SIZE,
+        gpu_layers: hardware::ALL_GPU_LAYERS,
+        threads: None,
+        flash_attn: None,
+        embeddings: true,
+        mmproj: None,
+        battery_saver: false,
+    }));
 
     #[cfg(unix)]
     {
@@ -410,7 +1955,7 @@ fn start_embedding(port: Option<u16>) -> Result<ManagedProcess, String> {
         cmd.process_group(0);
     }
 
-    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
 
     let child = cmd
         .spawn()
@@ -425,6 +1970,10 @@ fn start_embedding(port: Option<u16>) -> Result<ManagedProcess, String> {
         port: actual_port,
         drain_handles: None,
         drain_shutdown: None,
+        gpu_layers: None,
+        flash_attn: None,
+        started_at: Instant::now(),
+        model_name: model_file_name(&model_path),
     })
 }
 
@@ -432,12 +1981,28 @@ fn start_embedding(port: Option<u16>) -> Result<ManagedProcess, String> {
 // Python server stdout/stdin signal protocol
 // =========================================================================
 
+/// Lines of server stderr quoted in a startup error; enough for a Python
+/// traceback's final frames and the exception itself.
+const STARTUP_STDERR_TAIL_LINES: usize = 20;
+
+/// `msg` followed by the tail of the server's stderr, if it wrote any.
 fn with_stderr(msg: &str, stderr_buffer: &[u8]) -> String {
-    let stderr = String::from_utf8_lossy(stderr_buffer).trim().to_string();
-    if stderr.is_empty() {
-        msg.to_string()
+    let stderr = String::from_utf8_lossy(stderr_buffer);
+    let lines: Vec<&str> = stderr.trim().lines().collect();
+    if lines.iter().all(|line| line.trim().is_empty()) {
+        return msg.to_string();
+    }
+    let tail = &lines[lines.len().saturating_sub(STARTUP_STDERR_TAIL_LINES)..];
+    let omitted = lines.len() - tail.len();
+    if omitted > 0 {
+        format!(
+            "{}\n[... {} earlier line(s)]\n{}",
+            msg,
+            omitted,
+            tail.join("\n")
+        )
     } else {
-        format!("{}\n{}", msg, stderr)
+        format!("{}\n{}", msg, tail.join("\n"))
     }
 }
 
@@ -460,9 +2025,163 @@ fn set_nonblocking(fd: std::os::unix::io::RawFd, nonblocking: bool) -> std::io::
     Ok(())
 }
 
+/// What went wrong in a [`PmError`], for callers that branch on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmErrorKind {
+    /// The server could not decrypt the database with the given key.
+    WrongKey,
+    /// No server has been started to receive the passphrase.
+    ServerNotRunning,
+    /// The server reported its ports but never answered `/health`.
+    ServerNotReady,
+    /// The service has no model downloaded.
+    ModelMissing,
+    /// [`ProcessManagerState::prepare_for_update`] is in effect.
+    Updating,
+    Other,
+}
+
+/// Error from starting, stopping or unlocking a service: a kind to branch
+/// on and a message for display. Plain string errors convert to
+/// [`PmErrorKind::Other`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PmError {
+    pub kind: PmErrorKind,
+    pub message: String,
+}
+
+impl PmError {
+    pub fn new(kind: PmErrorKind, message: impl Into<String>) -> Self {
+        PmError {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for PmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<String> for PmError {
+    fn from(message: String) -> Self {
+        PmError::new(PmErrorKind::Other, message)
+    }
+}
+
+impl From<&str> for PmError {
+    fn from(message: &str) -> Self {
+        PmError::new(PmErrorKind::Other, message)
+    }
+}
+
+impl From<PmError> for String {
+    fn from(error: PmError) -> Self {
+        error.message
+    }
+}
+
+/// How long the server gets to print `WAITING_FOR_PASSPHRASE` after spawn.
+const SERVER_SIGNAL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the server may take to answer `/health` after printing its ports.
+const SERVER_HEALTH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Prefix of the unlock error when the server reported its ports but never
+/// answered `/health`.
+pub const SERVER_NOT_READY_ERROR: &str = "Server did not become ready";
+
+/// Unlock error when no server has been started to receive the passphrase.
+pub const SERVER_NOT_RUNNING_ERROR: &str = "Server is not running";
+
+/// Start error while [`ProcessManagerState::prepare_for_update`] is in effect.
+pub const UPDATING_ERROR: &str = "App is preparing to update; services can't be started";
+
+/// Env override (seconds) for how long to wait for `PORTS:` after unlock;
+/// opening a large encrypted database on a cold start can take a while.
+const PORTS_TIMEOUT_ENV: &str = "PHLOX_PORTS_TIMEOUT_SECONDS";
+const DEFAULT_PORTS_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn ports_timeout() -> Duration {
+    parse_ports_timeout(std::env::var(PORTS_TIMEOUT_ENV).ok().as_deref())
+}
+
+fn parse_ports_timeout(value: Option<&str>) -> Duration {
+    match value.map(str::trim) {
+        None | Some("") => DEFAULT_PORTS_TIMEOUT,
+        Some(v) => match v.parse::<u64>() {
+            Ok(secs) if secs > 0 => Duration::from_secs(secs),
+            _ => {
+                log::warn!(
+                    "Ignoring invalid {}={:?}; using {:?}",
+                    PORTS_TIMEOUT_ENV,
+                    v,
+                    DEFAULT_PORTS_TIMEOUT
+                );
+                DEFAULT_PORTS_TIMEOUT
+            }
+        },
+    }
+}
+
+/// Size of each read from the server's pipes while waiting for a signal.
+const SIGNAL_READ_CHUNK: usize = 4096;
+
+/// Collects bytes read from a pipe and hands back complete lines, so a line
+/// split across reads is only looked at once it is whole.
+#[derive(Default)]
+struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Append `bytes` and return every line it completed, without the line
+    /// ending.
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(bytes);
+        let mut lines = Vec::new();
+        while let Some(pos) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line[..pos]);
+            lines.push(line.trim_end_matches('\r').to_string());
+        }
+        lines
+    }
+
+    fn pending(&self) -> String {
+        String::from_utf8_lossy(&self.pending).into_owned()
+    }
+}
+
+/// Unlock error when the server could not decrypt the database.
+pub const WRONG_KEY_ERROR: &str = "Wrong encryption key";
+
+/// Whether the server's stderr says the database key was wrong.
+fn is_wrong_key_error(stderr: &str) -> bool {
+    stderr.contains("Wrong encryption key?")
+        || stderr.contains("wrong key?")
+        || stderr.contains("Cannot decrypt database")
+}
+
+/// What a line of server stdout means during startup: a signal, an error,
+/// or `None` for ordinary progress output.
+fn parse_signal_line(line: &str) -> Option<Result<ServerSignal, String>> {
+    let line = line.trim();
+    if line == "WAITING_FOR_PASSPHRASE" {
+        Some(Ok(ServerSignal::WaitingForPassphrase))
+    } else if line.starts_with("PORTS:") {
+        Some(parse_ports_line(line).map(ServerSignal::Ports))
+    } else {
+        line.strip_prefix("ERROR:").map(|msg| Err(msg.to_string()))
+    }
+}
+
 /// Wait for the server to output a signal via stdout.
 /// Also monitors stderr for specific error messages like "wrong key".
-fn wait_for_server_signal(child: &mut Child) -> Result<ServerSignal, String> {
+/// Other stdout lines are forwarded to the log as startup progress.
+fn wait_for_server_signal(child: &mut Child, timeout: Duration) -> Result<ServerSignal, PmError> {
     use std::io::Read;
 
     let stdout = child.stdout.as_mut().ok_or("Failed to capture stdout")?;
@@ -475,124 +2194,118 @@ fn wait_for_server_signal(child: &mut Child) -> Result<ServerSignal, String> {
         let _ = set_nonblocking(stderr.as_raw_fd(), true);
     }
 
-    let mut stdout_reader = std::io::BufReader::new(stdout);
-    let mut stderr_reader = std::io::BufReader::new(stderr);
-
     log::info!("Waiting for signal from server stdout...");
 
     let start = std::time::Instant::now();
-    let mut stdout_buffer = Vec::new();
+    let mut stdout_lines = LineBuffer::default();
     let mut stderr_buffer = Vec::new();
-    let timeout = Duration::from_secs(10);
+    let mut stderr_open = true;
+    let mut chunk = [0u8; SIGNAL_READ_CHUNK];
 
     loop {
         if start.elapsed() > timeout {
             log::warn!("Timeout waiting for server signal");
-            log::warn!(
-                "Stdout content: {}",
-                String::from_utf8_lossy(&stdout_buffer)
-            );
+            log::warn!("Stdout content: {}", stdout_lines.pending());
             log::warn!(
                 "Stderr content: {}",
                 String::from_utf8_lossy(&stderr_buffer)
             );
-            return Err(with_stderr(
+            return Err(PmError::from(with_stderr(
                 "Timeout waiting for server to start",
                 &stderr_buffer,
-            ));
+            )));
         }
 
+        let mut progressed = false;
+
         // Check stderr for "wrong key" error message
-        let mut stderr_byte = [0u8; 1];
-        match stderr_reader.read(&mut stderr_byte) {
-            Ok(0) => {
-                let stderr_content = String::from_utf8_lossy(&stderr_buffer);
-                if stderr_content.contains("Wrong encryption key?")
-                    || stderr_content.contains("wrong key?")
-                    || stderr_content.contains("Cannot decrypt database")
-                {
-                    return Err("Wrong encryption key".to_string());
+        if stderr_open {
+            match stderr.read(&mut chunk) {
+                Ok(0) => stderr_open = false,
+                Ok(n) => {
+                    progressed = true;
+                    stderr_buffer.extend_from_slice(&chunk[..n]);
+                    if is_wrong_key_error(&String::from_utf8_lossy(&stderr_buffer)) {
+                        log::error!("Detected wrong encryption key in stderr");
+                        return Err(PmError::new(PmErrorKind::WrongKey, WRONG_KEY_ERROR));
+                    }
                 }
-                // If stderr ended but no error detected, continue reading stdout
-            }
-            Ok(_) => {
-                stderr_buffer.push(stderr_byte[0]);
-                let stderr_content = String::from_utf8_lossy(&stderr_buffer);
-
-                if stderr_content.contains("Wrong encryption key?")
-                    || stderr_content.contains("wrong key?")
-                    || stderr_content.contains("Cannot decrypt database")
-                {
-                    log::error!("Detected wrong encryption key in stderr");
-                    return Err("Wrong encryption key".to_string());
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => {
+                    log::error!("Error reading from server stderr: {}", e);
+                    stderr_open = false;
                 }
             }
-            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
-            Err(e) => {
-                log::error!("Error reading from server stderr: {}", e);
-            }
         }
 
         // Read stdout for signals
-        let mut stdout_byte = [0u8; 1];
-        match stdout_reader.read(&mut stdout_byte) {
+        match stdout.read(&mut chunk) {
             Ok(0) => {
                 log::warn!("EOF reached while waiting for server signal");
-                log::warn!(
-                    "Stdout content: {}",
-                    String::from_utf8_lossy(&stdout_buffer)
-                );
-                let _ = stderr_reader.read_to_end(&mut stderr_buffer);
+                log::warn!("Stdout content: {}", stdout_lines.pending());
+                // Pick up the rest of a traceback still in flight
+                let deadline = Instant::now() + Duration::from_millis(500);
+                while stderr_open && Instant::now() < deadline {
+                    match stderr.read(&mut chunk) {
+                        Ok(0) => break,
+                        Ok(n) => stderr_buffer.extend_from_slice(&chunk[..n]),
+                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                            std::thread::sleep(Duration::from_millis(10));
+                        }
+                        Err(_) => break,
+                    }
+                }
                 log::warn!(
                     "Stderr content: {}",
                     String::from_utf8_lossy(&stderr_buffer)
                 );
-                return Err(with_stderr(
+                if is_wrong_key_error(&String::from_utf8_lossy(&stderr_buffer)) {
+                    return Err(PmError::new(PmErrorKind::WrongKey, WRONG_KEY_ERROR));
+                }
+                return Err(PmError::from(with_stderr(
                     "Server exited before sending signal",
                     &stderr_buffer,
-                ));
+                )));
             }
-            Ok(_) => {
-                stdout_buffer.push(stdout_byte[0]);
-                let content = String::from_utf8_lossy(&stdout_buffer);
-
-                if let Some(newline_pos) = content.find('\n') {
-                    let line = &content[..newline_pos];
-                    log::debug!("Read line from stdout: {}", line);
-
-                    if line.trim() == "WAITING_FOR_PASSPHRASE" {
-                        log::info!("Server is waiting for passphrase");
-                        return Ok(ServerSignal::WaitingForPassphrase);
-                    }
-
-                    if line.trim().starts_with("PORTS:") {
-                        let ports = parse_ports_line(line)?;
-                        return Ok(ServerSignal::Ports(ports));
-                    }
-
-                    if line.trim().starts_with("ERROR:") {
-                        let error_msg = line
-                            .trim()
-                            .strip_prefix("ERROR:")
-                            .unwrap_or("Unknown error");
-                        return Err(error_msg.to_string());
+            Ok(n) => {
+                progressed = true;
+                let mut lines = stdout_lines.push(&chunk[..n]).into_iter();
+                while let Some(line) = lines.next() {
+                    match parse_signal_line(&line) {
+                        Some(Ok(signal)) => {
+                            if matches!(signal, ServerSignal::WaitingForPassphrase) {
+                                log::info!("Server is waiting for passphrase");
+                            }
+                            // Output that arrived in the same read still gets logged
+                            for line in lines.filter(|l| !l.trim().is_empty()) {
+                                log::info!("[server stdout] {}", line);
+                            }
+                            return Ok(signal);
+                        }
+                        Some(Err(e)) => return Err(e.into()),
+                        None if !line.trim().is_empty() => {
+                            log::info!("[server stdout] {}", line);
+                        }
+                        None => {}
                     }
-
-                    stdout_buffer = content[newline_pos + 1..].as_bytes().to_vec();
                 }
             }
-            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                std::thread::sleep(Duration::from_millis(50));
-            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
             Err(e) => {
                 log::error!("Error reading from server stdout: {}", e);
-                return Err(format!("Error reading from server stdout: {}", e));
+                return Err(format!("Error reading from server stdout: {}", e).into());
             }
         }
+
+        if !progressed {
+            std::thread::sleep(Duration::from_millis(10));
+        }
     }
 }
 
-/// Parse a `PORTS:s,ll,w,e|TOKEN:xyz` line into [`AllocatedPorts`].
+/// Parse a `PORTS:` line into [`AllocatedPorts`]. The JSON form
+/// `PORTS:{"server":s,"llama":l,"whisper":w,"embedding":e}|TOKEN:xyz` is
+/// preferred; the positional `PORTS:s,l,w,e|TOKEN:xyz` is still accepted.
 fn parse_ports_line(line: &str) -> Result<AllocatedPorts, String> {
     let trimmed = line.trim();
     let ports_part = trimmed.strip_prefix("PORTS:").ok_or("Invalid PORTS line")?;
@@ -602,30 +2315,10 @@ fn parse_ports_line(line: &str) -> Result<AllocatedPorts, String> {
         return Err("PORTS line missing token".to_string());
     }
 
-    let ports: Vec<&str> = parts[0].split(',').collect();
-    if ports.len() < 3 {
-        return Err(format!("PORTS line has too few ports: {:?}", ports));
-    }
-
-    let server = ports[0]
-        .trim()
-        .parse::<u16>()
-        .map_err(|e| format!("Failed to parse server port: {}", e))?;
-    let llama = ports[1]
-        .trim()
-        .parse::<u16>()
-        .map_err(|e| format!("Failed to parse llama port: {}", e))?;
-    let whisper = ports[2]
-        .trim()
-        .parse::<u16>()
-        .map_err(|e| format!("Failed to parse whisper port: {}", e))?;
-    let embedding = if ports.len() >= 4 {
-        ports[3]
-            .trim()
-            .parse::<u16>()
-            .map_err(|e| format!("Failed to parse embedding port: {}", e))?
+    let [server, llama, whisper, embedding] = if parts[0].trim_start().starts_with('{') {
+        parse_ports_json(parts[0])?
     } else {
-        EMBEDDING_PORT
+        parse_ports_csv(parts[0])?
     };
 
     let token = parts[1]
@@ -656,9 +2349,63 @@ fn parse_ports_line(line: &str) -> Result<AllocatedPorts, String> {
     })
 }
 
+/// Parse the positional `s,l,w[,e]` port list (embedding is optional).
+fn parse_ports_csv(list: &str) -> Result<[u16; 4], String> {
+    let ports: Vec<&str> = list.split(',').collect();
+    if ports.len() < 3 {
+        return Err(format!("PORTS line has too few ports: {:?}", ports));
+    }
+
+    let parse = |value: &str, name: &str| {
+        value
+            .trim()
+            .parse::<u16>()
+            .map_err(|e| format!("Failed to parse {} port: {}", name, e))
+    };
+    Ok([
+        parse(ports[0], "server")?,
+        parse(ports[1], "llama")?,
+        parse(ports[2], "whisper")?,
+        match ports.get(3) {
+            Some(value) => parse(value, "embedding")?,
+            None => EMBEDDING_PORT,
+        },
+    ])
+}
+
+/// Parse the named JSON port object. Unknown fields are ignored and
+/// `embedding` is optional.
+fn parse_ports_json(object: &str) -> Result<[u16; 4], String> {
+    let map: serde_json::Map<String, serde_json::Value> = serde_json::from_str(object.trim())
+        .map_err(|e| format!("PORTS JSON is not an object: {}", e))?;
+
+    let field = |name: &str| -> Result<Option<u16>, String> {
+        match map.get(name) {
+            None => Ok(None),
+            Some(value) => value
+                .as_u64()
+                .and_then(|n| u16::try_from(n).ok())
+                .map(Some)
+                .ok_or_else(|| format!("Failed to parse {} port: {}", name, value)),
+        }
+    };
+    let required =
+        |name: &str| field(name)?.ok_or_else(|| format!("PORTS JSON missing {} port", name));
+
+    Ok([
+        required("server")?,
+        required("llama")?,
+        required("whisper")?,
+        field("embedding")?.unwrap_or(EMBEDDING_PORT),
+    ])
+}
+
 /// Wait for the server to output its allocated ports via stdout.
-fn wait_for_allocated_ports(child: &mut Child) -> Result<AllocatedPorts, String> {
-    match wait_for_server_signal(child)? {
+fn wait_for_allocated_ports(
+    child: &mut Child,
+    timeout: Duration,
+) -> Result<AllocatedPorts, String> {
+    match wait_for_server_signal(child, timeout)? {
         ServerSignal::Ports(ports) => Ok(ports),
         ServerSignal::WaitingForPassphrase => {
             Err("Unexpected WAITING_FOR_PASSPHRASE signal".to_string())
@@ -669,7 +2416,10 @@ fn wait_for_allocated_ports(child: &mut Child) -> Result<AllocatedPorts, String>
 /// Spawn background threads to continuously drain stdout and stderr from the
 /// server process, preventing the pipe buffer (~64KB) from filling up and
 /// blocking the child.
-fn spawn_drain_threads(child: &mut Child) -> (JoinHandle<()>, JoinHandle<()>, Arc<AtomicBool>) {
+fn spawn_drain_threads(
+    child: &mut Child,
+    logs: Arc<LogHub>,
+) -> (JoinHandle<()>, JoinHandle<()>, Arc<AtomicBool>) {
     let shutdown = Arc::new(AtomicBool::new(false));
 
     let stdout = child.stdout.take();
@@ -688,6 +2438,7 @@ fn spawn_drain_threads(child: &mut Child) -> (JoinHandle<()>, JoinHandle<()>, Ar
     }
 
     let shutdown_stdout = Arc::clone(&shutdown);
+    let logs_stdout = Arc::clone(&logs);
     let stdout_handle = thread::spawn(move || {
         if let Some(stdout) = stdout {
             let reader = BufReader::new(stdout);
@@ -696,6 +2447,11 @@ fn spawn_drain_threads(child: &mut Child) -> (JoinHandle<()>, JoinHandle<()>, Ar
                     break;
                 }
                 log::info!("[server stdout] {}", line);
+                logs_stdout.publish(LogLine {
+                    service: "server",
+                    stream: "stdout",
+                    line,
+                });
             }
         }
         log::debug!("Stdout drain thread exiting");
@@ -710,6 +2466,11 @@ fn spawn_drain_threads(child: &mut Child) -> (JoinHandle<()>, JoinHandle<()>, Ar
                     break;
                 }
                 log::warn!("[server stderr] {}", line);
+                logs.publish(LogLine {
+                    service: "server",
+                    stream: "stderr",
+                    line,
+                });
             }
         }
         log::debug!("Stderr drain thread exiting");
@@ -733,6 +2494,45 @@ fn stop_drain_threads(process: &mut ManagedProcess) {
     }
 }
 
+/// Write an encrypted copy of the plaintext database at `src` to `dst`,
+/// using a one-off server process given `key_hex` on stdin.
+pub fn encrypt_plaintext_database(
+    src: &std::path::Path,
+    dst: &std::path::Path,
+    key_hex: &str,
+) -> Result<(), String> {
+    let server_path = find_python_server().ok_or("Server binary not found")?;
+    log::info!("Encrypting plaintext database with {:?}", server_path);
+
+    let mut child = Command::new(&server_path)
+        .arg("--encrypt-plaintext-db")
+        .arg(src)
+        .arg(dst)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to spawn server: {}", e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "{}", key_hex)
+            .map_err(|e| format!("Failed to send key to server: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to wait for server: {}", e))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if let Some(error) = stdout.lines().find_map(|l| l.strip_prefix("ERROR:")) {
+        return Err(error.to_string());
+    }
+    if output.status.success() && stdout.lines().any(|l| l == "ENCRYPTED") {
+        Ok(())
+    } else {
+        Err(format!("Database encryption failed ({})", output.status))
+    }
+}
+
 /// Start the Python server (waits for passphrase via stdin).
 /// Returns the process once it has confirmed `WAITING_FOR_PASSPHRASE`.
 fn start_server() -> Result<ManagedProcess, String> {
@@ -777,7 +2577,7 @@ fn start_server() -> Result<ManagedProcess, String> {
     );
     write_pid_file("server", pid);
 
-    match wait_for_server_signal(&mut child)? {
+    match wait_for_server_signal(&mut child, SERVER_SIGNAL_TIMEOUT)? {
         ServerSignal::WaitingForPassphrase => {
             log::info!("Server confirmed ready for passphrase");
             Ok(ManagedProcess {
@@ -785,6 +2585,10 @@ fn start_server() -> Result<ManagedProcess, String> {
                 port: 0,
                 drain_handles: None,
                 drain_shutdown: None,
+                gpu_layers: None,
+                flash_attn: None,
+                started_at: Instant::now(),
+                model_name: None,
             })
         }
         ServerSignal::Ports(_) => {
@@ -797,7 +2601,8 @@ fn start_server() -> Result<ManagedProcess, String> {
 fn send_passphrase_and_wait_for_ports(
     process: &mut ManagedProcess,
     passphrase: &str,
-) -> Result<AllocatedPorts, String> {
+    logs: Arc<LogHub>,
+) -> Result<AllocatedPorts, PmError> {
     if let Some(ref mut stdin) = process.child.stdin {
         writeln!(stdin, "{}", passphrase)
             .map_err(|e| format!("Failed to write passphrase to stdin: {}", e))?;
@@ -805,13 +2610,13 @@ fn send_passphrase_and_wait_for_ports(
             .flush()
             .map_err(|e| format!("Failed to flush stdin: {}", e))?;
     } else {
-        return Err("Server stdin not available".to_string());
+        return Err("Server stdin not available".into());
     }
 
-    let ports = wait_for_allocated_ports(&mut process.child)?;
+    let ports = wait_for_allocated_ports(&mut process.child, ports_timeout())?;
     process.port = ports.server;
 
-    let (stdout_handle, stderr_handle, shutdown) = spawn_drain_threads(&mut process.child);
+    let (stdout_handle, stderr_handle, shutdown) = spawn_drain_threads(&mut process.child, logs);
     process.drain_handles = Some((stdout_handle, stderr_handle));
     process.drain_shutdown = Some(shutdown);
 
@@ -829,6 +2634,58 @@ fn send_passphrase_and_wait_for_ports(
 // Kill helpers
 // =========================================================================
 
+/// Env override (seconds) for how long the server gets to exit after
+/// SIGTERM / CTRL_BREAK before it is killed.
+const SERVER_GRACE_ENV: &str = "PHLOX_SERVER_GRACE_SECONDS";
+pub const DEFAULT_SERVER_GRACE_SECS: u64 = 5;
+/// Bounds on the grace period: long enough to flush the encrypted database,
+/// short enough not to hang app exit.
+pub const MIN_SERVER_GRACE_SECS: u64 = 2;
+pub const MAX_SERVER_GRACE_SECS: u64 = 60;
+
+/// Current server grace period; changed at runtime by
+/// [`set_server_grace_period`].
+static SERVER_GRACE_SECS: AtomicU64 = AtomicU64::new(DEFAULT_SERVER_GRACE_SECS);
+
+/// Parse a grace period in seconds, clamped to the supported range.
+fn parse_grace_seconds(value: Option<&str>) -> u64 {
+    match value.map(str::trim) {
+        None | Some("") => DEFAULT_SERVER_GRACE_SECS,
+        Some(v) => match v.parse::<u64>() {
+            Ok(secs) => secs.clamp(MIN_SERVER_GRACE_SECS, MAX_SERVER_GRACE_SECS),
+            Err(_) => {
+                log::warn!(
+                    "Ignoring invalid {}={:?}; using {}s",
+                    SERVER_GRACE_ENV,
+                    v,
+                    DEFAULT_SERVER_GRACE_SECS
+                );
+                DEFAULT_SERVER_GRACE_SECS
+            }
+        },
+    }
+}
+
+/// Apply `PHLOX_SERVER_GRACE_SECONDS`, if set. Called once at startup.
+pub fn load_server_grace_period() {
+    let secs = parse_grace_seconds(std::env::var(SERVER_GRACE_ENV).ok().as_deref());
+    SERVER_GRACE_SECS.store(secs, Ordering::Relaxed);
+    log::info!("Server shutdown grace period: {}s", secs);
+}
+
+/// Change the server grace period for the rest of the session. Returns the
+/// effective value after clamping.
+pub fn set_server_grace_period(secs: u64) -> u64 {
+    let secs = secs.clamp(MIN_SERVER_GRACE_SECS, MAX_SERVER_GRACE_SECS);
+    SERVER_GRACE_SECS.store(secs, Ordering::Relaxed);
+    log::info!("Server shutdown grace period set to {}s", secs);
+    secs
+}
+
+pub fn server_grace_period() -> Duration {
+    Duration::from_secs(SERVER_GRACE_SECS.load(Ordering::Relaxed))
+}
+
 /// Send a graceful-shutdown signal, poll for exit up to `grace`, then force kill.
 fn kill_with_grace(child: &mut Child, grace: Duration, name: &str) {
     let pid = child.id();
@@ -907,28 +2764,193 @@ fn kill_by_name_inner(pattern: &str, service_name: &str) -> bool {
         .unwrap_or(false)
 }
 
+// =========================================================================
+// Crash-loop backoff
+// =========================================================================
+
+/// Delay before the first automatic restart; doubles with each crash.
+const RESTART_BASE_DELAY: Duration = Duration::from_secs(15);
+/// Longest delay between automatic restarts.
+const RESTART_MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+/// Crashes are counted over this sliding window.
+const RESTART_WINDOW: Duration = Duration::from_secs(15 * 60);
+/// Automatic restarts allowed within the window before giving up.
+const MAX_RESTARTS_IN_WINDOW: usize = 5;
+
+/// Backoff before restart attempt `attempt` (0-based), capped at
+/// [`RESTART_MAX_DELAY`].
+fn restart_backoff(attempt: u32) -> Duration {
+    RESTART_BASE_DELAY
+        .saturating_mul(1 << attempt.min(16))
+        .min(RESTART_MAX_DELAY)
+}
+
+/// Auto-restart bookkeeping for one sidecar.
+#[derive(Debug, Default)]
+struct RestartTracker {
+    /// Recent crash times, pruned to the policy's window.
+    crashes: Vec<Instant>,
+    /// When the next restart attempt is due.
+    next_attempt: Option<Instant>,
+    /// Gave up after too many crashes within the window.
+    failing: bool,
+    /// `failing` has been reported by [`ProcessManagerState::run_due_restarts`].
+    reported: bool,
+}
+
+impl RestartTracker {
+    /// Record a crash (or failed restart) and schedule the next attempt,
+    /// or give up once the window holds more than `policy` allows.
+    fn record_crash(&mut self, now: Instant, policy: &RestartPolicy) {
+        self.crashes
+            .retain(|t| now.saturating_duration_since(*t) < policy.window());
+        self.crashes.push(now);
+        if !policy.auto_restart {
+            self.next_attempt = None;
+        } else if self.crashes.len() > policy.max_restarts as usize {
+            self.failing = true;
+            self.next_attempt = None;
+        } else {
+            self.next_attempt = Some(now + restart_backoff(self.crashes.len() as u32 - 1));
+        }
+    }
+
+    fn is_due(&self, now: Instant) -> bool {
+        !self.failing && self.next_attempt.is_some_and(|t| now >= t)
+    }
+}
+
+/// Whether `port` can be bound on 127.0.0.1.
+fn is_port_free(port: u16) -> bool {
+    std::net::TcpListener::bind(("127.0.0.1", port)).is_ok()
+}
+
+/// Make sure `port` is free before spawning `service`, killing an orphan
+/// left over from a previous run if something still holds it.
+fn ensure_port_free(port: u16, service: &str, binary: &str) -> Result<(), String> {
+    free_port_with(port, || kill_port_orphan(port, service, binary))
+}
+
+/// Check `port`; if occupied, run `kill_orphan` and check once more,
+/// allowing the OS a moment to release the socket.
+fn free_port_with(port: u16, kill_orphan: impl FnOnce()) -> Result<(), String> {
+    if is_port_free(port) {
+        return Ok(());
+    }
+    log::warn!(
+        "Port {} is already in use, looking for an orphaned process",
+        port
+    );
+    kill_orphan();
+
+    let deadline = Instant::now() + Duration::from_secs(2);
+    loop {
+        if is_port_free(port) {
+            log::info!("Port {} freed", port);
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(format!("port {} in use", port));
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Kill whatever a previous run left on `port`: first the process named in
+/// the service's PID file, then anything running `binary` on that port.
+fn kill_port_orphan(port: u16, service: &str, binary: &str) {
+    if let Some(pid) = crate::process::is_process_running_from_pid(service) {
+        if let Err(e) = crate::process::kill_process_by_pid(pid, service) {
+            log::warn!("Could not kill orphaned {}: {}", service, e);
+        }
+        remove_pid_file(service);
+        if is_port_free(port) {
+            return;
+        }
+    }
+    kill_orphan_on_port_inner(port, binary);
+}
+
+/// The llama binary also serves embeddings, so match on the port argument
+/// to avoid killing a sibling we still manage.
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn kill_orphan_on_port_inner(port: u16, binary: &str) {
+    let pattern = format!("{} --port {}( |$)", binary, port);
+    log::info!("Killing orphaned processes matching: {}", pattern);
+    let _ = Command::new("pkill").arg("-f").arg(&pattern).output();
+}
+
+/// `taskkill` can only match on the image name, which would also hit
+/// siblings we still manage, so only the PID file is used on Windows.
+#[cfg(target_os = "windows")]
+fn kill_orphan_on_port_inner(port: u16, binary: &str) {
+    log::warn!(
+        "No PID file for the {} holding port {}; not killing by name",
+        binary,
+        port
+    );
+}
+
+/// Memory and CPU use of one process, as sampled by [`sample_usage`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ProcessUsage {
+    memory_mb: u64,
+    cpu_percent: f32,
+}
+
+/// Refresh `sys` for just `pids` and read back their usage. Pids that have
+/// exited are left out. CPU use is measured since the previous refresh, so
+/// the first sample of a process reads 0.
+fn sample_usage(sys: &mut sysinfo::System, pids: &[u32]) -> HashMap<u32, ProcessUsage> {
+    let refresh = sysinfo::ProcessRefreshKind::new().with_memory().with_cpu();
+    pids.iter()
+        .filter_map(|&pid| {
+            let pid = sysinfo::Pid::from_u32(pid);
+            if !sys.refresh_process_specifics(pid, refresh) {
+                return None;
+            }
+            let process = sys.process(pid)?;
+            Some((
+                pid.as_u32(),
+                ProcessUsage {
+                    memory_mb: process.memory() / (1024 * 1024),
+                    cpu_percent: process.cpu_usage(),
+                },
+            ))
+        })
+        .collect()
+}
+
 /// Build a [`StatusData`] snapshot from the currently-managed processes.
 fn create_status_data(
-    llama: Option<&ManagedProcess>,
-    whisper: Option<&ManagedProcess>,
-    server: Option<&ManagedProcess>,
-    embedding: Option<&ManagedProcess>,
-    request_token: Option<&String>,
+    state: &ProcessManagerState,
+    usage: &HashMap<u32, ProcessUsage>,
 ) -> StatusData {
-    fn status_for(p: &ManagedProcess) -> ServiceStatus {
-        ServiceStatus {
-            running: true,
-            pid: p.child.id(),
-            port: p.port,
-        }
-    }
+    let status_for = |service: &str, p: &ManagedProcess| ServiceStatus {
+        running: true,
+        pid: p.child.id(),
+        memory_mb: usage.get(&p.child.id()).map(|u| u.memory_mb),
+        cpu_percent: usage.get(&p.child.id()).map(|u| u.cpu_percent),
+        port: p.port,
+        gpu_layers: p.gpu_layers,
+        flash_attn: p.flash_attn,
+        uptime_seconds: p.started_at.elapsed().as_secs(),
+        restart_count: state.restart_counts.get(service).copied().unwrap_or(0),
+        model_name: p.model_name.clone(),
+    };
 
     StatusData {
-        llama: llama.map(status_for),
-        whisper: whisper.map(status_for),
-        server: server.map(status_for),
-        embedding: embedding.map(status_for),
-        request_token: request_token.cloned(),
+        llama: state.llama.as_ref().map(|p| status_for("llama", p)),
+        whisper: state.whisper.as_ref().map(|p| status_for("whisper", p)),
+        server: state.server.as_ref().map(|p| status_for("server", p)),
+        embedding: state.embedding.as_ref().map(|p| status_for("embedding", p)),
+        request_token: state.request_token.clone(),
+        last_exit: state
+            .last_failure
+            .iter()
+            .map(|(service, failure)| (*service, failure.exit.clone()))
+            .collect(),
+        failing: state.failing_services(),
     }
 }
 
@@ -938,19 +2960,21 @@ fn create_status_data(
 
 impl ProcessManagerState {
     /// Spawn llama.cpp with the loaded model. Returns `(pid, port)`.
-    pub fn start_llama(&mut self, port: Option<u16>) -> Result<(u32, u16), String> {
+    pub fn start_llama(&mut self, port: Option<u16>) -> Result<(u32, u16), PmError> {
+        self.ensure_not_updating()?;
         if self.llama.is_some() {
-            return Err("Llama server is already running".to_string());
+            return Err("Llama server is already running".into());
         }
         let port = port.or_else(|| self.allocated_ports.as_ref().map(|p| p.llama));
         let mut proc = start_llama(port)?;
+        self.logs.attach("llama", &mut proc.child);
         // Give the process a moment to start, then verify it didn't exit immediately.
         thread::sleep(Duration::from_millis(500));
         match proc.child.try_wait() {
             Ok(Some(status)) => {
                 log::error!("Llama process exited immediately: {:?}", status);
                 remove_pid_file("llama");
-                Err("Llama server failed to start".to_string())
+                Err("Llama server failed to start".into())
             }
             Ok(None) => {
                 let pid = proc.child.id();
@@ -960,24 +2984,26 @@ impl ProcessManagerState {
             }
             Err(e) => {
                 log::error!("Failed to check llama process: {}", e);
-                Err("Failed to verify llama server status".to_string())
+                Err("Failed to verify llama server status".into())
             }
         }
     }
 
     /// Spawn whisper.cpp with the loaded model. Returns `(pid, port)`.
-    pub fn start_whisper(&mut self, port: Option<u16>) -> Result<(u32, u16), String> {
+    pub fn start_whisper(&mut self, port: Option<u16>) -> Result<(u32, u16), PmError> {
+        self.ensure_not_updating()?;
         if self.whisper.is_some() {
-            return Err("Whisper server is already running".to_string());
+            return Err("Whisper server is already running".into());
         }
         let port = port.or_else(|| self.allocated_ports.as_ref().map(|p| p.whisper));
         let mut proc = start_whisper(port)?;
+        self.logs.attach("whisper", &mut proc.child);
         thread::sleep(Duration::from_millis(500));
         match proc.child.try_wait() {
             Ok(Some(status)) => {
                 log::error!("Whisper process exited immediately: {:?}", status);
                 remove_pid_file("whisper");
-                Err("Whisper server failed to start".to_string())
+                Err("Whisper server failed to start".into())
             }
             Ok(None) => {
                 let pid = proc.child.id();
@@ -987,24 +3013,26 @@ impl ProcessManagerState {
             }
             Err(e) => {
                 log::error!("Failed to check whisper process: {}", e);
-                Err("Failed to verify whisper server status".to_string())
+                Err("Failed to verify whisper server status".into())
             }
         }
     }
 
     /// Spawn llama.cpp in embedding mode. Returns `(pid, port)`.
-    pub fn start_embedding(&mut self, port: Option<u16>) -> Result<(u32, u16), String> {
+    pub fn start_embedding(&mut self, port: Option<u16>) -> Result<(u32, u16), PmError> {
+        self.ensure_not_updating()?;
         if self.embedding.is_some() {
-            return Err("Embedding server is already running".to_string());
+            return Err("Embedding server is already running".into());
         }
         let port = port.or_else(|| self.allocated_ports.as_ref().map(|p| p.embedding));
         let mut proc = start_embedding(port)?;
+        self.logs.attach("embedding", &mut proc.child);
         thread::sleep(Duration::from_millis(500));
         match proc.child.try_wait() {
             Ok(Some(status)) => {
                 log::error!("Embedding process exited immediately: {:?}", status);
                 remove_pid_file("embedding");
-                Err("Embedding server failed to start".to_string())
+                Err("Embedding server failed to start".into())
             }
             Ok(None) => {
                 let pid = proc.child.id();
@@ -1014,13 +3042,18 @@ impl ProcessManagerState {
             }
             Err(e) => {
                 log::error!("Failed to check embedding process: {}", e);
-                Err("Failed to verify embedding server status".to_string())
+                Err("Failed to verify embedding server status".into())
             }
         }
     }
 
     /// Spawn the Python server and wait for `WAITING_FOR_PASSPHRASE` on stdout.
-    pub fn start_server(&mut self) -> Result<(), String> {
+    pub fn start_server(&mut self) -> Result<(), PmError> {
+        self.ensure_not_updating()?;
+        if self.unlocking {
+            // Alive and mid-unlock; the handle comes back when it finishes.
+            return Ok(());
+        }
         let already_alive = self
             .server
             .as_mut()
@@ -1040,7 +3073,7 @@ impl ProcessManagerState {
             Ok(Some(status)) => {
                 log::error!("Server process exited immediately: {:?}", status);
                 remove_pid_file("server");
-                Err("Server failed to start".to_string())
+                Err("Server failed to start".into())
             }
             Ok(None) => {
                 self.server = Some(proc);
@@ -1048,50 +3081,70 @@ impl ProcessManagerState {
             }
             Err(e) => {
                 log::error!("Failed to check server process: {}", e);
-                Err("Failed to verify server status".to_string())
+                Err("Failed to verify server status".into())
             }
         }
     }
 
-    /// Write passphrase to the server stdin and wait for the `PORTS:` line.
-    ///
-    /// BLOCKING — can take up to ~30s while the Python server boots. Callers
-    /// MUST wrap in `tokio::task::spawn_blocking`.
-    pub fn send_passphrase(&mut self, passphrase: String) -> Result<AllocatedPorts, String> {
-        match self.server.take() {
-            Some(mut proc) => {
-                let pid = proc.child.id();
-                match send_passphrase_and_wait_for_ports(&mut proc, &passphrase) {
-                    Ok(ports) => {
-                        self.request_token = Some(ports.request_token.clone());
-                        self.allocated_ports = Some(ports.clone());
-                        self.server = Some(proc);
-                        log::info!(
-                            "Server PID {} unlocked; ports: server={}, llama={}, whisper={}, embedding={}",
-                            pid,
-                            ports.server,
-                            ports.llama,
-                            ports.whisper,
-                            ports.embedding
-                        );
-                        Ok(ports)
-                    }
-                    Err(e) => {
-                        log::error!("Failed to send passphrase: {}", e);
-                        stop_drain_threads(&mut proc);
-                        let _ = proc.child.kill();
-                        let _ = proc.child.wait();
-                        remove_pid_file("server");
-                        Err(e)
-                    }
-                }
+    /// Check the server handle out for an unlock so the wait for `PORTS:`
+    /// can run without holding the state lock.
+    fn begin_unlock(&mut self) -> Result<ManagedProcess, PmError> {
+        self.ensure_not_updating()?;
+        let proc = self.server.take().ok_or_else(|| {
+            PmError::new(
+                PmErrorKind::ServerNotRunning,
+                format!("{}. Call start_server first.", SERVER_NOT_RUNNING_ERROR),
+            )
+        })?;
+        self.unlocking = true;
+        Ok(proc)
+    }
+
+    /// Return the server handle after an unlock attempt and record the outcome.
+    fn finish_unlock(
+        &mut self,
+        mut proc: ManagedProcess,
+        result: Result<AllocatedPorts, PmError>,
+    ) -> Result<AllocatedPorts, PmError> {
+        if !std::mem::take(&mut self.unlocking) {
+            // shutdown() ran while we were waiting; don't resurrect the server.
+            stop_drain_threads(&mut proc);
+            let _ = proc.child.kill();
+            let _ = proc.child.wait();
+            remove_pid_file("server");
+            return Err("Server was shut down during unlock".into());
+        }
+        let pid = proc.child.id();
+        match result {
+            Ok(ports) => {
+                self.request_token = Some(ports.request_token.clone());
+                self.allocated_ports = Some(ports.clone());
+                self.server = Some(proc);
+                log::info!(
+                    "Server PID {} unlocked; ports: server={}, llama={}, whisper={}, embedding={}",
+                    pid,
+                    ports.server,
+                    ports.llama,
+                    ports.whisper,
+                    ports.embedding
+                );
+                Ok(ports)
+            }
+            Err(e) => {
+                log::error!("Failed to send passphrase: {}", e);
+                stop_drain_threads(&mut proc);
+                let _ = proc.child.kill();
+                let _ = proc.child.wait();
+                remove_pid_file("server");
+                Err(e)
             }
-            None => Err("Server is not running. Call start_server first.".to_string()),
         }
     }
 
-    /// Stop a specific service.
-    pub fn stop(&mut self, service: &str) -> Result<(), String> {
+    /// Stop a specific service. Stopping one that isn't running is not an error.
+    pub fn stop(&mut self, service: &str) -> Result<(), PmError> {
+        // An explicit stop or restart supersedes any pending auto-restart
+        self.restarts.remove(service);
         match service {
             "llama" => stop_managed(&mut self.llama, "llama"),
             "whisper" => stop_managed(&mut self.whisper, "whisper"),
@@ -1099,28 +3152,232 @@ impl ProcessManagerState {
             "server" => {
                 if let Some(mut proc) = self.server.take() {
                     stop_drain_threads(&mut proc);
-                    let _ = proc.child.kill();
-                    let _ = proc.child.wait();
+                    kill_with_grace(&mut proc.child, server_grace_period(), "server");
                     remove_pid_file("server");
-                    Ok(())
                 } else {
-                    Err("Server is not running".to_string())
+                    log::debug!("stop(server): not running");
                 }
+                Ok(())
+            }
+            _ => Err(format!("Unknown service: {}", service).into()),
+        }
+    }
+
+    /// Start a sidecar unless it is already up, in which case its existing
+    /// `(pid, port)` is returned. The `start_*` methods refuse a second start.
+    pub fn ensure_running(&mut self, service: &str) -> Result<(u32, u16), PmError> {
+        // Reap first so a dead process isn't reported as running
+        self.check_liveness();
+        let running = match service {
+            "llama" => self.llama.as_ref(),
+            "whisper" => self.whisper.as_ref(),
+            "embedding" => self.embedding.as_ref(),
+            _ => return Err(format!("Cannot start service: {}", service).into()),
+        };
+        if let Some(proc) = running {
+            return Ok((proc.child.id(), proc.port));
+        }
+        match service {
+            "llama" => self.start_llama(None),
+            "whisper" => self.start_whisper(None),
+            _ => self.start_embedding(None),
+        }
+    }
+
+    /// Stop a sidecar if it is running and start a fresh one on its
+    /// allocated port, all under one lock so the liveness watcher never sees
+    /// the gap. A service that isn't running is simply started.
+    /// Returns `(pid, port)`.
+    pub fn restart(&mut self, service: &str) -> Result<(u32, u16), PmError> {
+        let running = match service {
+            "llama" | "whisper" | "embedding" => self.is_running(service),
+            _ => return Err(format!("Cannot restart service: {}", service).into()),
+        };
+        // A manual restart also clears a "failing" service
+        self.restarts.remove(service);
+        if running {
+            self.stop(service)?;
+        }
+        let (service, started) = match service {
+            "llama" => ("llama", self.start_llama(None)?),
+            "whisper" => ("whisper", self.start_whisper(None)?),
+            _ => ("embedding", self.start_embedding(None)?),
+        };
+        *self.restart_counts.entry(service).or_default() += 1;
+        Ok(started)
+    }
+
+    /// Switch `service` to another model from its models directory and
+    /// restart it. Returns the new `(pid, port)`.
+    ///
+    /// The selection is validated before the running process is touched. The
+    /// old process is stopped through [`Self::stop`], so the liveness monitor
+    /// never sees it die. If the new model fails to start, the previous
+    /// selection is restored and the old model is started again.
+    pub fn switch_model(&mut self, service: &str, filename: &str) -> Result<(u32, u16), PmError> {
+        self.begin_model_switch(service, filename)
+            .map(|switch| (switch.pid, switch.port))
+    }
+
+    /// [`Self::switch_model`], returning what [`Self::undo_model_switch`]
+    /// needs to roll back a model that started but never became ready.
+    pub fn begin_model_switch(
+        &mut self,
+        service: &str,
+        filename: &str,
+    ) -> Result<ModelSwitch, PmError> {
+        ensure_model_switchable(service)?;
+        self.ensure_not_updating()?;
+
+        let previous = read_setting(LLM_MODEL_FILE);
+        let was_running = self.is_running("llama");
+        select_llama_model(filename)?;
+
+        match self.restart("llama") {
+            Ok((pid, port)) => Ok(ModelSwitch {
+                pid,
+                port,
+                previous,
+                was_running,
+            }),
+            Err(e) => {
+                log::error!("Failed to start llama with {}: {}", filename.trim(), e);
+                self.restore_previous_model(previous.as_deref(), was_running);
+                Err(e)
+            }
+        }
+    }
+
+    /// Roll back a switch whose model started but never became ready: stop
+    /// it, restore the previous selection and start the old model again if
+    /// it was running.
+    pub fn undo_model_switch(&mut self, switch: &ModelSwitch) {
+        if let Err(e) = self.stop("llama") {
+            log::warn!("Could not stop the new model: {}", e);
+        }
+        self.restore_previous_model(switch.previous.as_deref(), switch.was_running);
+    }
+
+    fn restore_previous_model(&mut self, previous: Option<&str>, was_running: bool) {
+        let restored = PhloxPaths::from_data_dir()
+            .and_then(|paths| paths.restore_setting(LLM_MODEL_FILE, previous));
+        if let Err(err) = restored {
+            log::warn!("Could not restore {}: {}", LLM_MODEL_FILE, err);
+        } else if was_running {
+            if let Err(err) = self.start_llama(None) {
+                log::warn!("Could not restart previous model: {}", err);
+            }
+        }
+    }
+
+    fn is_running(&self, service: &str) -> bool {
+        match service {
+            "llama" => self.llama.is_some(),
+            "whisper" => self.whisper.is_some(),
+            "embedding" => self.embedding.is_some(),
+            "server" => self.server.is_some(),
+            _ => false,
+        }
+    }
+
+    /// Stop the sidecars and then the Python server, check that nothing is
+    /// left running, and only then fall back to killing by binary name.
+    pub fn stop_all(&mut self) -> StopAllReport {
+        self.stop_all_with(|service| crate::process::is_process_running_from_pid(service).is_some())
+    }
+
+    /// [`Self::stop_all`], with `orphan_alive` reporting whether a PID file
+    /// still names a live process for a service.
+    fn stop_all_with(&mut self, orphan_alive: impl Fn(&str) -> bool) -> StopAllReport {
+        let mut report = StopAllReport::default();
+        for service in ["llama", "whisper", "embedding", "server"] {
+            if !self.is_running(service) {
+                report.already_stopped.push(service);
+            } else if let Err(e) = self.stop(service) {
+                log::warn!("Failed to stop {}: {}", service, e);
+            } else {
+                report.stopped.push(service);
+            }
+        }
+
+        let status = self.status();
+        for (service, listed, binary) in [
+            ("llama", status.llama.is_some(), "phlox-llama-server"),
+            ("whisper", status.whisper.is_some(), "phlox-whisper-server"),
+            (
+                "embedding",
+                status.embedding.is_some(),
+                "phlox-llama-server",
+            ),
+            ("server", status.server.is_some(), "phlox-server"),
+        ] {
+            if listed || orphan_alive(service) {
+                log::warn!("{} still running after stop; killing by name", service);
+                kill_process_by_name(binary, binary);
+                remove_pid_file(service);
+                report.killed_by_name.push(service);
             }
-            _ => Err(format!("Unknown service: {}", service)),
         }
+        log::info!("Stopped all services: {:?}", report);
+        report
+    }
+
+    /// Subscribe to captured output from one service, or all with `None`.
+    /// Iterate the receiver for lines; drop it to unsubscribe.
+    pub fn subscribe_logs(&self, service: Option<&str>) -> Receiver<LogLine> {
+        self.logs.subscribe(service)
     }
 
     /// Snapshot of all service states. Reaps dead children first.
     pub fn status(&mut self) -> StatusData {
         self.check_liveness();
-        create_status_data(
-            self.llama.as_ref(),
-            self.whisper.as_ref(),
-            self.server.as_ref(),
-            self.embedding.as_ref(),
-            self.request_token.as_ref(),
-        )
+        let pids: Vec<u32> = [&self.llama, &self.whisper, &self.server, &self.embedding]
+            .into_iter()
+            .flatten()
+            .map(|p| p.child.id())
+            .collect();
+        let usage = sample_usage(&mut self.sys, &pids);
+        create_status_data(self, &usage)
+    }
+
+    /// Services that gave up on automatic restarts, in a stable order.
+    fn failing_services(&self) -> Vec<&'static str> {
+        let mut failing: Vec<&'static str> = self
+            .restarts
+            .iter()
+            .filter(|(_, tracker)| tracker.failing)
+            .map(|(service, _)| *service)
+            .collect();
+        failing.sort_unstable();
+        failing
+    }
+
+    /// Quiesce everything ahead of an app update: refuse further starts,
+    /// then stop every managed process so the binaries can be replaced.
+    /// Returns the services that were running.
+    pub fn prepare_for_update(&mut self) -> Vec<&'static str> {
+        self.updating = true;
+        let running: Vec<&'static str> = [
+            ("server", self.server.is_some()),
+            ("llama", self.llama.is_some()),
+            ("whisper", self.whisper.is_some()),
+            ("embedding", self.embedding.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, up)| up.then_some(name))
+        .collect();
+
+        log::info!("Preparing for update; stopping: {:?}", running);
+        self.shutdown();
+        running
+    }
+
+    fn ensure_not_updating(&self) -> Result<(), PmError> {
+        if self.updating {
+            Err(PmError::new(PmErrorKind::Updating, UPDATING_ERROR))
+        } else {
+            Ok(())
+        }
     }
 
     /// Kill every managed process. Used on window close and on shutdown.
@@ -1132,19 +3389,23 @@ impl ProcessManagerState {
             && self.whisper.is_none()
             && self.server.is_none()
             && self.embedding.is_none()
+            && !self.unlocking
         {
             log::debug!("shutdown() called but no managed processes; skipping");
             return;
         }
 
         log::info!("Shutting down all managed processes");
+        // An in-flight unlock kills its server when it hands the handle back;
+        // the name-based fallback below covers it in the meantime.
+        self.unlocking = false;
 
-        // Python server: graceful — SIGTERM / CTRL_BREAK with a 500ms grace
-        // period so uvicorn can finish in-flight requests and SQLCipher can
-        // flush before we fall back to SIGKILL.
+        // Python server: graceful — SIGTERM / CTRL_BREAK with a grace period
+        // so uvicorn can finish in-flight requests and SQLCipher can flush
+        // before we fall back to SIGKILL.
         if let Some(mut proc) = self.server.take() {
             stop_drain_threads(&mut proc);
-            kill_with_grace(&mut proc.child, Duration::from_millis(500), "server");
+            kill_with_grace(&mut proc.child, server_grace_period(), "server");
             remove_pid_file("server");
         }
         // Immediate SIGKILL: stateless inference engines with nothing to flush.
@@ -1170,84 +3431,384 @@ impl ProcessManagerState {
         kill_process_by_name("phlox-server", "phlox-server");
     }
 
+    /// Store how `service` exited along with its recent stderr.
+    fn record_failure(&mut self, service: &'static str, status: std::process::ExitStatus) {
+        let when = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.last_failure.insert(
+            service,
+            LastFailure {
+                exit: ExitInfo::from_status(status),
+                stderr_tail: self.logs.stderr_tail(service),
+                when,
+            },
+        );
+    }
+
+    /// The last unexpected exit of `service`, if it has died this session.
+    pub fn last_failure(&self, service: &str) -> Option<LastFailure> {
+        self.last_failure.get(service).cloned()
+    }
+
     /// Reap dead children; remove their state entries and PID files.
     /// Returns the names of services that died during this reap.
     /// Called by the liveness watcher thread every 30s and by `status`.
     pub fn check_liveness(&mut self) -> Vec<&'static str> {
         let mut died = Vec::new();
 
-        if self
+        if let Some(status) = self
             .llama
             .as_mut()
             .and_then(|p| p.child.try_wait().ok().flatten())
-            .is_some()
         {
-            log::warn!("Llama process died, removing from state");
+            log::warn!("Llama process died ({:?}), removing from state", status);
+            self.record_failure("llama", status);
             self.llama = None;
             remove_pid_file("llama");
+            let policy = self.restart_policies.get("llama");
+            self.restarts
+                .entry("llama")
+                .or_default()
+                .record_crash(Instant::now(), &policy);
             died.push("llama");
         }
 
-        if self
+        if let Some(status) = self
             .whisper
             .as_mut()
             .and_then(|p| p.child.try_wait().ok().flatten())
-            .is_some()
         {
-            log::warn!("Whisper process died, removing from state");
+            log::warn!("Whisper process died ({:?}), removing from state", status);
+            self.record_failure("whisper", status);
             self.whisper = None;
             remove_pid_file("whisper");
+            let policy = self.restart_policies.get("whisper");
+            self.restarts
+                .entry("whisper")
+                .or_default()
+                .record_crash(Instant::now(), &policy);
             died.push("whisper");
         }
 
-        if self
+        if let Some(status) = self
             .server
             .as_mut()
             .and_then(|p| p.child.try_wait().ok().flatten())
-            .is_some()
         {
-            log::warn!("Server process died, removing from state");
+            log::warn!("Server process died ({:?}), removing from state", status);
+            self.record_failure("server", status);
             if let Some(mut proc) = self.server.take() {
                 stop_drain_threads(&mut proc);
             }
             remove_pid_file("server");
+            let policy = self.restart_policies.server;
+            self.restarts
+                .entry("server")
+                .or_default()
+                .record_crash(Instant::now(), &policy);
             died.push("server");
         }
 
-        if self
+        if let Some(status) = self
             .embedding
             .as_mut()
             .and_then(|p| p.child.try_wait().ok().flatten())
-            .is_some()
         {
-            log::warn!("Embedding process died, removing from state");
+            log::warn!("Embedding process died ({:?}), removing from state", status);
+            self.record_failure("embedding", status);
             self.embedding = None;
             remove_pid_file("embedding");
+            let policy = self.restart_policies.get("embedding");
+            self.restarts
+                .entry("embedding")
+                .or_default()
+                .record_crash(Instant::now(), &policy);
             died.push("embedding");
         }
 
         died
     }
+
+    /// Whether the server policy allows restarting a dead server and it has
+    /// not crashed too often. The caller still needs a cached key.
+    pub fn server_restart_allowed(&self) -> bool {
+        self.restart_policies.server.auto_restart
+            && !self.restarts.get("server").is_some_and(|t| t.failing)
+    }
+
+    /// Forget the server's crash history once the user has started and
+    /// unlocked it by hand, so a server that gave up on automatic restarts
+    /// gets them back.
+    pub fn reset_server_restarts(&mut self) {
+        self.restarts.remove("server");
+    }
+
+    /// Restart sidecars whose backoff has elapsed. Returns the services that
+    /// were restarted and those that just gave up after repeated crashes.
+    /// The server is never restarted here; see [`Self::server_restart_allowed`].
+    pub fn run_due_restarts(&mut self, now: Instant) -> (Vec<&'static str>, Vec<&'static str>) {
+        let mut restarted = Vec::new();
+        let mut gave_up = Vec::new();
+        if self.updating {
+            return (restarted, gave_up);
+        }
+
+        for service in ["llama", "whisper", "embedding"] {
+            let running = self.is_running(service);
+            let policy = self.restart_policies.get(service);
+            match self.restarts.get_mut(service) {
+                Some(tracker) if tracker.is_due(now) => {
+                    // Already brought back by hand, or auto-restart was
+                    // turned off since the crash
+                    if running || !policy.auto_restart {
+                        tracker.next_attempt = None;
+                        continue;
+                    }
+                }
+                _ => continue,
+            }
+            log::info!("Auto-restarting {} after crash", service);
+            let result = match service {
+                "llama" => self.start_llama(None),
+                "whisper" => self.start_whisper(None),
+                _ => self.start_embedding(None),
+            };
+            let Some(tracker) = self.restarts.get_mut(service) else {
+                continue;
+            };
+            match result {
+                Ok((pid, port)) => {
+                    log::info!(
+                        "{} auto-restarted with PID: {}, port: {}",
+                        service,
+                        pid,
+                        port
+                    );
+                    tracker.next_attempt = None;
+                    restarted.push(service);
+                    *self.restart_counts.entry(service).or_default() += 1;
+                }
+                Err(e) => {
+                    log::warn!("Auto-restart of {} failed: {}", service, e);
+                    tracker.record_crash(now, &policy);
+                }
+            }
+        }
+
+        // Trackers can tip over the limit here or in check_liveness; report
+        // each one once
+        for (service, tracker) in &mut self.restarts {
+            if tracker.failing && !tracker.reported {
+                log::error!(
+                    "{} crashed {} times within {:?}; giving up on automatic restarts",
+                    service,
+                    tracker.crashes.len(),
+                    self.restart_policies.get(service).window()
+                );
+                tracker.reported = true;
+                gave_up.push(*service);
+            }
+        }
+
+        (restarted, gave_up)
+    }
+
+    /// Check each running sidecar against the ports the Python server
+    /// allocated. With `fix`, sidecars on the wrong port are restarted on
+    /// the allocated one; services that simply aren't listening are only
+    /// reported, since a model may still be loading.
+    pub fn diagnose_ports(&mut self, fix: bool) -> Result<PortReport, String> {
+        self.check_liveness();
+        let allocated = self
+            .allocated_ports
+            .clone()
+            .ok_or("Server has not reported its allocated ports yet")?;
+
+        let mut mismatches = Vec::new();
+        for (service, slot, want) in [
+            ("llama", &self.llama, allocated.llama),
+            ("whisper", &self.whisper, allocated.whisper),
+            ("embedding", &self.embedding, allocated.embedding),
+        ] {
+            if let Some(proc) = slot {
+                let listening = port_listening(proc.port);
+                mismatches.extend(compare_ports(service, want, proc.port, listening));
+            }
+        }
+
+        let mut fixed = Vec::new();
+        if fix {
+            for m in mismatches.iter().filter(|m| m.running != m.allocated) {
+                log::info!(
+                    "Restarting {} on allocated port {} (was {})",
+                    m.service,
+                    m.allocated,
+                    m.running
+                );
+                let _ = self.stop(m.service);
+                let started = match m.service {
+                    "llama" => self.start_llama(Some(m.allocated)),
+                    "whisper" => self.start_whisper(Some(m.allocated)),
+                    _ => self.start_embedding(Some(m.allocated)),
+                };
+                match started {
+                    Ok(_) => fixed.push(m.service),
+                    Err(e) => log::error!("Failed to restart {}: {}", m.service, e),
+                }
+            }
+        }
+
+        Ok(PortReport {
+            consistent: mismatches.is_empty(),
+            mismatches,
+            fixed,
+        })
+    }
+}
+
+/// Compare one sidecar's allocated port with the port it is running on and
+/// whether anything is accepting connections there.
+fn compare_ports(
+    service: &'static str,
+    allocated: u16,
+    running: u16,
+    listening: bool,
+) -> Option<PortMismatch> {
+    let suggestion = if running != allocated {
+        format!(
+            "Restart {} on port {} so the server can reach it",
+            service, allocated
+        )
+    } else if !listening {
+        format!(
+            "Nothing is listening on port {}; {} may still be loading or has hung, restart it if this persists",
+            running, service
+        )
+    } else {
+        return None;
+    };
+    Some(PortMismatch {
+        service,
+        allocated,
+        running,
+        listening,
+        suggestion,
+    })
+}
+
+/// Whether something accepts TCP connections on `127.0.0.1:port`.
+fn port_listening(port: u16) -> bool {
+    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+    std::net::TcpStream::connect_timeout(&addr, Duration::from_millis(300)).is_ok()
+}
+
+/// HTTP endpoint and status code that mean a service can take requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadinessProbe {
+    pub path: &'static str,
+    pub expected_status: u16,
+}
+
+impl ReadinessProbe {
+    /// Default probe per service. llama.cpp (llama and embedding) and the
+    /// parakeet server answer `/health` with 503 while the model loads. The
+    /// Python server's top-level `/health` (server/api/health.py) is exempt
+    /// from the request token; `/api/dashboard/health` is not.
+    pub fn for_service(service: &str) -> Option<Self> {
+        match service {
+            "llama" | "whisper" | "embedding" | "server" => Some(Self {
+                path: "/health",
+                expected_status: 200,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Status code of `GET path` on `127.0.0.1:port`, or `None` if the request
+/// fails. A minimal HTTP/1.1 client; only the status line is read.
+fn http_status(port: u16, path: &str) -> Option<u16> {
+    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+    let mut stream =
+        std::net::TcpStream::connect_timeout(&addr, Duration::from_millis(300)).ok()?;
+    stream.set_read_timeout(Some(Duration::from_secs(2))).ok()?;
+    stream
+        .set_write_timeout(Some(Duration::from_secs(2)))
+        .ok()?;
+    // One write: a request split across segments can reach a server that
+    // answers after the first read and resets the rest
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: 127.0.0.1:{}\r\nConnection: close\r\n\r\n",
+        path, port
+    );
+    stream.write_all(request.as_bytes()).ok()?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line).ok()?;
+    // "HTTP/1.1 200 OK"
+    status_line.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// Whether the service on `port` passes `probe`. Accepting a TCP connection
+/// is only the first gate: llama-server binds before its model has loaded.
+pub fn probe_ready(port: u16, probe: &ReadinessProbe) -> bool {
+    port_listening(port) && http_status(port, probe.path) == Some(probe.expected_status)
+}
+
+/// First delay between readiness polls; it doubles up to the caller's interval.
+const READY_FIRST_POLL: Duration = Duration::from_millis(50);
+
+/// Delay before the next readiness poll: double `delay`, capped at `interval`.
+fn next_poll_delay(delay: Duration, interval: Duration) -> Duration {
+    (delay * 2).min(interval)
+}
+
+/// Poll `probe` with exponential backoff, capped at `interval`, until it
+/// passes or `timeout` elapses.
+pub fn wait_for_http_ready(
+    port: u16,
+    probe: &ReadinessProbe,
+    timeout: Duration,
+    interval: Duration,
+) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    let mut delay = READY_FIRST_POLL.min(interval);
+    loop {
+        if probe_ready(port, probe) {
+            return Ok(());
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(format!(
+                "Service on port {} not ready after {}s (GET {} never returned {})",
+                port,
+                timeout.as_secs(),
+                probe.path,
+                probe.expected_status
+            ));
+        }
+        thread::sleep(delay.min(deadline - now));
+        delay = next_poll_delay(delay, interval);
+    }
+}
+
+/// Poll `probe` until it passes or `timeout` elapses.
+pub fn wait_for_ready(port: u16, probe: &ReadinessProbe, timeout: Duration) -> Result<(), String> {
+    wait_for_http_ready(port, probe, timeout, Duration::from_millis(500))
 }
 
 /// Kill a managed sidecar (non-server), remove its PID file, and clear state.
-fn stop_managed(slot: &mut Option<ManagedProcess>, service: &str) -> Result<(), String> {
+fn stop_managed(slot: &mut Option<ManagedProcess>, service: &str) -> Result<(), PmError> {
     if let Some(mut proc) = slot.take() {
         let _ = proc.child.kill();
         let _ = proc.child.wait();
         remove_pid_file(service);
-        Ok(())
     } else {
-        Err(format!("{} server is not running", capitalize(service)))
-    }
-}
-
-fn capitalize(s: &str) -> String {
-    let mut chars = s.chars();
-    match chars.next() {
-        None => String::new(),
-        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        log::debug!("stop({}): not running", service);
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -1,5 +1,13 @@
 use super::*;
 
+/// Create a fresh, empty scratch directory unique to this test process.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("phlox-pm-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
 #[test]
 fn parse_ports_line_well_formed() {
     let ports = parse_ports_line("PORTS:5000,8082,8081,8083|TOKEN:abcdef0123").unwrap();
@@ -46,6 +54,43 @@ fn parse_ports_line_no_token_prefix() {
     assert!(err.contains("Missing TOKEN prefix"));
 }
 
+#[test]
+fn parse_ports_line_json() {
+    let ports = parse_ports_line(
+        r#"PORTS:{"server":5000,"llama":8082,"whisper":8081,"embedding":8083}|TOKEN:abcdef0123"#,
+    )
+    .unwrap();
+    assert_eq!(ports.server, 5000);
+    assert_eq!(ports.llama, 8082);
+    assert_eq!(ports.whisper, 8081);
+    assert_eq!(ports.embedding, 8083);
+    assert_eq!(ports.request_token, "abcdef0123");
+}
+
+#[test]
+fn parse_ports_line_json_ignores_unknown_fields() {
+    let ports = parse_ports_line(
+        r#"PORTS:{"server":5000,"llama":8082,"whisper":8081,"metrics":9100}|TOKEN:tok"#,
+    )
+    .unwrap();
+    assert_eq!(ports.server, 5000);
+    assert_eq!(ports.embedding, EMBEDDING_PORT);
+}
+
+#[test]
+fn parse_ports_line_json_names_bad_field() {
+    let err = parse_ports_line(r#"PORTS:{"server":5000,"llama":"x","whisper":8081}|TOKEN:tok"#)
+        .unwrap_err();
+    assert!(err.contains("Failed to parse llama port"));
+
+    let err = parse_ports_line(r#"PORTS:{"server":5000,"llama":8082}|TOKEN:tok"#).unwrap_err();
+    assert!(err.contains("missing whisper port"));
+
+    let err = parse_ports_line(r#"PORTS:{"server":70000,"llama":8082,"whisper":8081}|TOKEN:tok"#)
+        .unwrap_err();
+    assert!(err.contains("Failed to parse server port"));
+}
+
 #[test]
 fn default_state_is_empty() {
     let state = ProcessManagerState::default();
@@ -58,3 +103,1588 @@ fn status_default_port_matches() -> bool {
     let s = StatusData::default();
     s.llama.is_none() && s.server.is_none()
 }
+
+#[test]
+fn classify_install_dev_build() {
+    let info = classify_install(
+        std::path::Path::new("/home/me/phlox/src-tauri/target/debug/phlox"),
+        None,
+    );
+    assert_eq!(info.install_type, InstallType::Dev);
+    assert_eq!(
+        info.resource_root,
+        Some(PathBuf::from("/home/me/phlox/src-tauri/target/debug"))
+    );
+}
+
+#[test]
+fn classify_install_macos_bundle() {
+    let info = classify_install(
+        std::path::Path::new("/Applications/Phlox.app/Contents/MacOS/phlox"),
+        None,
+    );
+    assert_eq!(info.install_type, InstallType::Dmg);
+    assert_eq!(
+        info.resource_root,
+        Some(PathBuf::from("/Applications/Phlox.app/Contents/Resources"))
+    );
+}
+
+#[test]
+fn classify_install_appimage() {
+    let info = classify_install(
+        std::path::Path::new("/tmp/.mount_PhloxAbc123/usr/bin/phlox"),
+        Some("/home/me/Phlox.AppImage"),
+    );
+    assert_eq!(info.install_type, InstallType::AppImage);
+    assert_eq!(
+        info.resource_root,
+        Some(PathBuf::from("/tmp/.mount_PhloxAbc123/usr/lib/Phlox"))
+    );
+}
+
+#[test]
+fn classify_install_system_package() {
+    let info = classify_install(std::path::Path::new("/usr/bin/phlox"), None);
+    assert_eq!(info.install_type, InstallType::SystemPackage);
+    assert_eq!(info.resource_root, Some(PathBuf::from("/usr/lib/Phlox")));
+}
+
+#[test]
+fn classify_install_windows() {
+    let info = classify_install(
+        std::path::Path::new("C:\\Program Files\\Phlox\\phlox.exe"),
+        None,
+    );
+    assert_eq!(info.install_type, InstallType::Msi);
+}
+
+#[test]
+fn parse_bool_setting_variants() {
+    assert_eq!(parse_bool_setting("true"), Some(true));
+    assert_eq!(parse_bool_setting(" ON\n"), Some(true));
+    assert_eq!(parse_bool_setting("0"), Some(false));
+    assert_eq!(parse_bool_setting("maybe"), None);
+}
+
+#[test]
+fn battery_throttle_decision() {
+    assert!(should_throttle_for_battery(true, Some(true)));
+    assert!(!should_throttle_for_battery(true, Some(false)));
+    assert!(!should_throttle_for_battery(true, None));
+    assert!(!should_throttle_for_battery(false, Some(true)));
+}
+
+#[test]
+fn ctx_size_is_clamped() {
+    assert_eq!(clamp_ctx_size(0), MIN_LLM_CTX_SIZE);
+    assert_eq!(clamp_ctx_size(511), 512);
+    assert_eq!(clamp_ctx_size(8192), 8192);
+    assert_eq!(clamp_ctx_size(131072), 131072);
+    assert_eq!(clamp_ctx_size(u32::MAX), MAX_LLM_CTX_SIZE);
+}
+
+#[test]
+fn flash_attn_defaults_on_for_apple_silicon_only() {
+    use crate::hardware::Accelerator;
+
+    assert!(default_flash_attn(Accelerator::AppleSilicon));
+    assert!(!default_flash_attn(Accelerator::Discrete {
+        vram_mb: 24576
+    }));
+    assert!(!default_flash_attn(Accelerator::CpuOnly));
+    assert!(!default_flash_attn(Accelerator::Unknown));
+}
+
+#[test]
+fn flash_attn_off_is_only_passed_when_explicit() {
+    use crate::hardware::Accelerator;
+
+    let discrete = Accelerator::Discrete { vram_mb: 24576 };
+    assert_eq!(resolve_flash_attn(None, discrete), None);
+    assert_eq!(resolve_flash_attn(None, Accelerator::CpuOnly), None);
+    assert_eq!(
+        resolve_flash_attn(None, Accelerator::AppleSilicon),
+        Some(true)
+    );
+    assert_eq!(resolve_flash_attn(Some(false), discrete), Some(false));
+    assert_eq!(
+        resolve_flash_attn(Some(false), Accelerator::AppleSilicon),
+        Some(false)
+    );
+    assert_eq!(resolve_flash_attn(Some(true), discrete), Some(true));
+}
+
+#[test]
+fn llama_args_never_pair_flash_attn_off_with_quantized_v_cache() {
+    let model = PathBuf::from("/models/qwen2.5-7b-instruct.gguf");
+    for flash_attn in [None, Some(true), Some(false)] {
+        let args = llama_server_args(&LlamaLaunch {
+            port: 8082,
+            model: &model,
+            ctx_size: 8192,
+            gpu_layers: 0,
+            threads: Some(4),
+            flash_attn,
+            embeddings: false,
+            mmproj: None,
+            battery_saver: false,
+        });
+        let value_of = |flag: &str| {
+            args.iter()
+                .position(|a| a == flag)
+                .map(|i| args[i + 1].as_str())
+        };
+        let expected_flag = flash_attn.map(|on| if on { "on" } else { "off" });
+        assert_eq!(value_of("--flash-attn"), expected_flag);
+        assert_eq!(value_of("--cache-type-k"), Some("q8_0"));
+        if flash_attn == Some(false) {
+            assert_eq!(value_of("--cache-type-v"), None);
+        } else {
+            assert_eq!(value_of("--cache-type-v"), Some("q8_0"));
+        }
+    }
+}
+
+#[test]
+fn default_threads_leaves_a_core_free() {
+    // (physical cores, logical CPUs) -> threads
+    let cases = [
+        (Some(1), 1, 1),
+        (Some(2), 4, 1),
+        (Some(4), 8, 3),
+        (Some(8), 16, 7),
+        (Some(16), 32, 15),
+        (None, 6, 5),
+        (None, 1, 1),
+        (None, 0, 1),
+    ];
+    for (physical, logical, expected) in cases {
+        assert_eq!(
+            default_threads(physical, logical),
+            expected,
+            "{:?} cores / {} cpus",
+            physical,
+            logical
+        );
+    }
+
+    assert_eq!(clamp_threads(0, 8), 1);
+    assert_eq!(clamp_threads(64, 8), 8);
+    assert_eq!(clamp_threads(4, 8), 4);
+}
+
+#[test]
+fn monitor_interval_is_parsed_and_clamped() {
+    assert_eq!(parse_monitor_interval("45"), Some(Duration::from_secs(45)));
+    assert_eq!(
+        parse_monitor_interval(" 10\n"),
+        Some(Duration::from_secs(10))
+    );
+    assert_eq!(
+        parse_monitor_interval("0"),
+        Some(Duration::from_secs(MIN_MONITOR_INTERVAL_SECS))
+    );
+    assert_eq!(
+        parse_monitor_interval("86400"),
+        Some(Duration::from_secs(MAX_MONITOR_INTERVAL_SECS))
+    );
+    assert_eq!(parse_monitor_interval("soon"), None);
+    assert_eq!(parse_monitor_interval("-5"), None);
+}
+
+#[test]
+fn server_auto_restart_needs_opt_in_and_cached_key() {
+    assert!(should_auto_restart_server(true, true, true));
+    assert!(!should_auto_restart_server(false, true, true));
+    assert!(!should_auto_restart_server(true, false, true));
+    assert!(!should_auto_restart_server(true, true, false));
+}
+
+#[test]
+fn sanitize_model_filename_rejects_paths() {
+    assert!(sanitize_model_filename("../secrets.gguf").is_err());
+    assert!(sanitize_model_filename("/etc/passwd").is_err());
+    assert!(sanitize_model_filename("C:\\models\\x.gguf").is_err());
+    assert!(sanitize_model_filename("..").is_err());
+    assert!(sanitize_model_filename("  ").is_err());
+    assert_eq!(
+        sanitize_model_filename(" qwen3-4b.Q4_K_M.gguf\n").unwrap(),
+        "qwen3-4b.Q4_K_M.gguf"
+    );
+}
+
+#[test]
+fn resolve_model_in_dir_accepts_bare_filename() {
+    let dir = temp_dir("resolve-ok");
+    fs::write(dir.join("model.gguf"), b"GGUF").unwrap();
+
+    let path = resolve_model_in_dir(&dir, "model.gguf").unwrap();
+    assert_eq!(path, dir.canonicalize().unwrap().join("model.gguf"));
+    assert_eq!(
+        resolve_model_in_dir(&dir, "missing.gguf").unwrap_err().kind,
+        PmErrorKind::ModelMissing
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn resolve_model_in_dir_rejects_traversal() {
+    let root = temp_dir("resolve-traversal");
+    let models = root.join("llm_models");
+    fs::create_dir_all(&models).unwrap();
+    fs::write(root.join("outside.gguf"), b"GGUF").unwrap();
+
+    assert!(resolve_model_in_dir(&models, "../outside.gguf").is_err());
+    let absolute = root.join("outside.gguf");
+    assert!(resolve_model_in_dir(&models, absolute.to_str().unwrap()).is_err());
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(root.join("outside.gguf"), models.join("link.gguf")).unwrap();
+        let err = resolve_model_in_dir(&models, "link.gguf").unwrap_err();
+        assert!(err.message.contains("outside the models directory"));
+    }
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn mmproj_is_taken_from_the_model_folder_only() {
+    let root = temp_dir("mmproj-folder");
+    let models = root.join("llm_models");
+    let vision = models.join("gemma");
+    let text = models.join("qwen");
+    fs::create_dir_all(&vision).unwrap();
+    fs::create_dir_all(&text).unwrap();
+    fs::write(vision.join("gemma-3-4b.gguf"), b"GGUF").unwrap();
+    fs::write(vision.join("mmproj-gemma-3-4b.gguf"), b"GGUF").unwrap();
+    fs::write(text.join("qwen3-4b.gguf"), b"GGUF").unwrap();
+
+    assert_eq!(
+        find_llama_mmproj(&vision.join("gemma-3-4b.gguf")),
+        Some(vision.join("mmproj-gemma-3-4b.gguf"))
+    );
+    assert_eq!(find_llama_mmproj(&text.join("qwen3-4b.gguf")), None);
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn invalid_selection_falls_back_to_scan() {
+    let root = temp_dir("selection-fallback");
+    let models = root.join("llm_models");
+    fs::create_dir_all(&models).unwrap();
+    fs::write(models.join("mmproj-vision.gguf"), b"GGUF").unwrap();
+    fs::write(models.join("a-scanned.gguf"), b"GGUF").unwrap();
+    fs::write(models.join("selected.gguf"), b"GGUF").unwrap();
+    fs::write(root.join("outside.gguf"), b"GGUF").unwrap();
+    let outside = root.join("outside.gguf");
+
+    let selected = find_llama_model_in(&models, Some("selected.gguf")).unwrap();
+    assert!(selected.ends_with("selected.gguf"));
+
+    for selection in [
+        "../outside.gguf",
+        "../../outside.gguf",
+        outside.to_str().unwrap(),
+        "missing.gguf",
+    ] {
+        let found = find_llama_model_in(&models, Some(selection)).unwrap();
+        assert_eq!(
+            found.file_name().unwrap(),
+            "a-scanned.gguf",
+            "{}",
+            selection
+        );
+        assert!(found.starts_with(&models));
+    }
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn preflight_reports_missing_items() {
+    let root = temp_dir("preflight");
+    let exe_dir = root.join("bin");
+    let models = root.join("data");
+    fs::create_dir_all(&exe_dir).unwrap();
+    fs::create_dir_all(models.join("llm_models").join("qwen")).unwrap();
+    fs::write(python_server_in(&exe_dir), b"").unwrap();
+    fs::write(llama_server_in(&exe_dir), b"").unwrap();
+    fs::write(
+        models.join("llm_models").join("qwen").join("model.gguf"),
+        b"GGUF",
+    )
+    .unwrap();
+
+    let items = preflight_in(&exe_dir, &models, None).unwrap();
+    let ok: Vec<(&str, bool)> = items.iter().map(|i| (i.name, i.ok)).collect();
+    assert_eq!(
+        ok,
+        vec![
+            ("server", true),
+            ("llama-server", true),
+            ("whisper-server", false),
+            ("llm-model", true),
+            ("whisper-model", false),
+        ]
+    );
+    assert!(items[3].path.as_ref().unwrap().ends_with("model.gguf"));
+    assert!(items[2].message.contains("missing"));
+    assert!(items[4].path.is_none());
+    assert!(items[4].message.contains("whisper_models"));
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn sanitize_model_path_accepts_subdirectories() {
+    assert_eq!(
+        sanitize_model_path("qwen/model.gguf").unwrap(),
+        "qwen/model.gguf"
+    );
+    assert_eq!(
+        sanitize_model_path("qwen\\4b\\model.gguf").unwrap(),
+        "qwen/4b/model.gguf"
+    );
+    assert_eq!(sanitize_model_path(" model.gguf\n").unwrap(), "model.gguf");
+    assert!(sanitize_model_path("qwen/../../secrets.gguf").is_err());
+    assert!(sanitize_model_path("/etc/passwd").is_err());
+    assert!(sanitize_model_path("qwen//model.gguf").is_err());
+    assert!(sanitize_model_path("C:/models/x.gguf").is_err());
+}
+
+#[test]
+fn resolve_model_in_dir_accepts_subdirectory() {
+    let dir = temp_dir("resolve-subdir");
+    fs::create_dir_all(dir.join("qwen")).unwrap();
+    fs::write(dir.join("qwen").join("model.gguf"), b"GGUF").unwrap();
+
+    let path = resolve_model_in_dir(&dir, "qwen/model.gguf").unwrap();
+    assert_eq!(
+        path,
+        dir.canonicalize().unwrap().join("qwen").join("model.gguf")
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn find_gguf_files_walks_nested_tree() {
+    let dir = temp_dir("scan-nested");
+    let touch = |rel: &str| {
+        let path = dir.join(rel);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"GGUF").unwrap();
+    };
+    touch("b/deep.gguf");
+    touch("a/one/two/three.gguf");
+    touch("a/one/two/three/too-deep.gguf");
+    touch("top.gguf");
+    touch("notes.txt");
+    touch(".cache/hidden.gguf");
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&dir, dir.join("a").join("loop")).unwrap();
+
+    let found: Vec<String> = find_gguf_files(&dir, 3)
+        .iter()
+        .map(|p| {
+            p.strip_prefix(&dir)
+                .unwrap()
+                .to_string_lossy()
+                .replace('\\', "/")
+        })
+        .collect();
+    assert_eq!(
+        found,
+        vec!["top.gguf", "b/deep.gguf", "a/one/two/three.gguf"]
+    );
+    assert!(find_gguf_files(&dir.join("missing"), 3).is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn models_root_override_resolution() {
+    let dir = temp_dir("models-root");
+    let default = dir.join("default");
+    let external = dir.join("external");
+    fs::create_dir_all(&external).unwrap();
+    fs::write(dir.join("file.txt"), b"").unwrap();
+
+    assert_eq!(resolve_models_root(default.clone(), None), default);
+    let set = format!("{}\n", external.display());
+    assert_eq!(resolve_models_root(default.clone(), Some(&set)), external);
+
+    // Missing, non-directory and relative overrides fall back to the default
+    let missing = dir.join("missing");
+    let file = dir.join("file.txt");
+    for bad in [
+        missing.to_str().unwrap(),
+        file.to_str().unwrap(),
+        "external",
+    ] {
+        assert_eq!(resolve_models_root(default.clone(), Some(bad)), default);
+        assert!(validate_models_root(bad).is_err());
+    }
+
+    assert_eq!(models_subdir("whisper").unwrap(), "whisper_models");
+    assert!(models_subdir("server").is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_data_dir_is_an_error() {
+    assert_eq!(require_dir(None).unwrap_err(), NO_DATA_DIR_ERROR);
+    let dir = PathBuf::from("/data/Phlox");
+    assert_eq!(require_dir(Some(dir.clone())).unwrap(), dir);
+}
+
+#[test]
+fn restart_backoff_doubles_up_to_cap() {
+    assert_eq!(restart_backoff(0), Duration::from_secs(15));
+    assert_eq!(restart_backoff(1), Duration::from_secs(30));
+    assert_eq!(restart_backoff(3), Duration::from_secs(120));
+    assert_eq!(restart_backoff(5), RESTART_MAX_DELAY);
+    assert_eq!(restart_backoff(u32::MAX), RESTART_MAX_DELAY);
+}
+
+const SIDECAR_POLICY: RestartPolicy = RestartPolicy::new(true);
+
+#[test]
+fn restart_tracker_gives_up_after_threshold() {
+    let start = Instant::now();
+    let mut tracker = RestartTracker::default();
+
+    for i in 0..MAX_RESTARTS_IN_WINDOW {
+        let now = start + Duration::from_secs(i as u64);
+        tracker.record_crash(now, &SIDECAR_POLICY);
+        assert!(!tracker.failing);
+        assert!(!tracker.is_due(now));
+        assert!(tracker.is_due(now + restart_backoff(i as u32)));
+    }
+
+    tracker.record_crash(start + Duration::from_secs(10), &SIDECAR_POLICY);
+    assert!(tracker.failing);
+    assert!(!tracker.is_due(start + RESTART_MAX_DELAY * 10));
+}
+
+#[test]
+fn restart_tracker_forgets_old_crashes() {
+    let start = Instant::now();
+    let mut tracker = RestartTracker::default();
+    for _ in 0..MAX_RESTARTS_IN_WINDOW {
+        tracker.record_crash(start, &SIDECAR_POLICY);
+    }
+
+    tracker.record_crash(start + RESTART_WINDOW, &SIDECAR_POLICY);
+    assert!(!tracker.failing);
+    assert_eq!(tracker.crashes.len(), 1);
+}
+
+#[test]
+fn run_due_restarts_reports_failing_once() {
+    let mut state = ProcessManagerState::default();
+    let now = Instant::now();
+    let tracker = state.restarts.entry("whisper").or_default();
+    for _ in 0..=MAX_RESTARTS_IN_WINDOW {
+        tracker.record_crash(now, &SIDECAR_POLICY);
+    }
+
+    assert_eq!(state.run_due_restarts(now), (vec![], vec!["whisper"]));
+    assert_eq!(state.run_due_restarts(now), (vec![], vec![]));
+    assert_eq!(state.status().failing, vec!["whisper"]);
+
+    // A manual restart clears the failing state, even if the start fails
+    let _ = state.restart("whisper");
+    assert!(state.status().failing.is_empty());
+}
+
+#[test]
+fn restart_policy_defaults_and_overrides() {
+    let defaults = parse_restart_policies("{}", false).unwrap();
+    assert_eq!(defaults, RestartPolicies::default());
+    assert!(defaults.llama.auto_restart && !defaults.server.auto_restart);
+    assert!(
+        parse_restart_policies("{}", true)
+            .unwrap()
+            .server
+            .auto_restart
+    );
+
+    let policies = parse_restart_policies(
+        r#"{"whisper": {"auto_restart": false, "max_restarts": 2, "window_secs": 60}}"#,
+        false,
+    )
+    .unwrap();
+    assert_eq!(
+        policies.whisper,
+        RestartPolicy {
+            auto_restart: false,
+            max_restarts: 2,
+            window_secs: 60
+        }
+    );
+    assert_eq!(policies.llama, defaults.llama);
+
+    assert!(parse_restart_policies(r#"{"whisper": {"auto_restart": true}}"#, false).is_err());
+    assert!(parse_restart_policies(r#"{"lama": {}}"#, false).is_err());
+    let zero_window = r#"{"llama": {"auto_restart": true, "max_restarts": 5, "window_secs": 0}}"#;
+    assert!(parse_restart_policies(zero_window, false).is_err());
+}
+
+#[test]
+fn restart_policy_without_auto_restart_suppresses_restarts() {
+    let off = RestartPolicy {
+        auto_restart: false,
+        ..SIDECAR_POLICY
+    };
+    let start = Instant::now();
+    let mut tracker = RestartTracker::default();
+    for i in 0..10 {
+        tracker.record_crash(start + Duration::from_secs(i), &off);
+    }
+    assert!(!tracker.failing);
+    assert!(!tracker.is_due(start + RESTART_MAX_DELAY * 10));
+
+    // Turned off after a restart was already scheduled
+    let mut state = ProcessManagerState::default();
+    state.restart_policies.llama = off;
+    state
+        .restarts
+        .entry("llama")
+        .or_default()
+        .record_crash(start, &SIDECAR_POLICY);
+    let later = start + RESTART_MAX_DELAY;
+    assert_eq!(state.run_due_restarts(later), (vec![], vec![]));
+    assert!(!state.restarts["llama"].is_due(later));
+}
+
+#[test]
+fn restart_policy_limits_server_restarts() {
+    let mut state = ProcessManagerState::default();
+    assert!(!state.server_restart_allowed());
+
+    state.restart_policies.server = RestartPolicy {
+        max_restarts: 1,
+        ..RestartPolicy::new(true)
+    };
+    assert!(state.server_restart_allowed());
+    let now = Instant::now();
+    let policy = state.restart_policies.server;
+    for allowed_after in [true, false] {
+        state
+            .restarts
+            .entry("server")
+            .or_default()
+            .record_crash(now, &policy);
+        assert_eq!(state.server_restart_allowed(), allowed_after);
+    }
+    assert_eq!(state.run_due_restarts(now), (vec![], vec!["server"]));
+
+    // A manual unlock clears the failing tracker
+    state.reset_server_restarts();
+    assert!(state.server_restart_allowed());
+    assert_eq!(state.failing_services(), Vec::<&str>::new());
+}
+
+#[test]
+fn switch_model_validates_before_stopping() {
+    let mut state = ProcessManagerState::default();
+    let err = state.switch_model("whisper", "model.bin").unwrap_err();
+    assert!(err.message.contains("not supported"));
+
+    let err = state.switch_model("llama", "../outside.gguf").unwrap_err();
+    assert!(err.message.contains("Invalid model filename"));
+}
+
+#[test]
+fn restore_setting_writes_or_removes() {
+    let dir = temp_dir("restore-setting");
+    let paths = PhloxPaths::new(dir.clone());
+    paths.write_setting(LLM_MODEL_FILE, "new.gguf").unwrap();
+
+    paths
+        .restore_setting(LLM_MODEL_FILE, Some("old.gguf"))
+        .unwrap();
+    assert_eq!(
+        paths.read_setting(LLM_MODEL_FILE).as_deref(),
+        Some("old.gguf")
+    );
+
+    // No earlier selection: back to scanning for a model
+    paths.restore_setting(LLM_MODEL_FILE, None).unwrap();
+    assert!(!dir.join(LLM_MODEL_FILE).exists());
+    paths.restore_setting(LLM_MODEL_FILE, None).unwrap();
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn phlox_paths_in_temp_dir() {
+    let dir = temp_dir("phlox-paths");
+    let paths = PhloxPaths::new(dir.join("Phlox"));
+
+    paths.write_pid_file("whisper", 4321).unwrap();
+    assert_eq!(paths.read_pid_file("whisper"), Some(4321));
+    assert_eq!(paths.read_pid_file("llama"), None);
+
+    assert_eq!(
+        paths.models_dir("llama").unwrap(),
+        dir.join("Phlox").join("llm_models")
+    );
+    let external = dir.join("external");
+    fs::create_dir_all(&external).unwrap();
+    paths
+        .write_setting(MODELS_DIR_FILE, external.to_str().unwrap())
+        .unwrap();
+    assert_eq!(
+        paths.models_dir("whisper").unwrap(),
+        external.join("whisper_models")
+    );
+
+    paths.write_setting(LLM_CTX_SIZE_FILE, "  \n").unwrap();
+    assert_eq!(paths.read_setting(LLM_CTX_SIZE_FILE), None);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn split_args_follows_shell_quoting() {
+    assert_eq!(
+        split_args("--rope-freq-base 10000  --no-mmap\n").unwrap(),
+        vec!["--rope-freq-base", "10000", "--no-mmap"]
+    );
+    assert_eq!(
+        split_args_with(
+            r#"--alias 'my model' --system-prompt "say \"hi\"" a\ b"#,
+            true
+        )
+        .unwrap(),
+        vec![
+            "--alias",
+            "my model",
+            "--system-prompt",
+            r#"say "hi""#,
+            "a b"
+        ]
+    );
+    assert_eq!(split_args("''").unwrap(), vec![""]);
+    assert!(split_args("").unwrap().is_empty());
+    assert!(split_args("--alias 'open").is_err());
+    assert!(split_args("--alias \"open").is_err());
+    assert!(split_args("bad\0arg").is_err());
+}
+
+#[test]
+fn split_args_keeps_windows_backslashes() {
+    assert_eq!(
+        split_args_with(
+            r#"--lora C:\models\lora.gguf --alias "D:\my models\""#,
+            false
+        )
+        .unwrap(),
+        vec![
+            "--lora",
+            r"C:\models\lora.gguf",
+            "--alias",
+            r"D:\my models\"
+        ]
+    );
+}
+
+#[test]
+fn extra_args_come_last_and_paths_are_redacted() {
+    let model = PathBuf::from("/home/user/models/secret/model.gguf");
+    let mut args = whisper_server_args(8081, &model, 4);
+    args.extend(split_args("--threads 2").unwrap());
+    assert_eq!(&args[args.len() - 2..], ["--threads", "2"]);
+    assert!(args.iter().position(|a| a == "--threads").unwrap() < args.len() - 2);
+
+    let line = redacted_command_line(&args);
+    assert!(line.contains("--model .../model.gguf"), "{}", line);
+    assert!(!line.contains("secret"));
+}
+
+#[test]
+fn model_quirks_match_by_filename() {
+    assert_eq!(
+        model_quirk_args("Qwen3-8B-Q4_K_M.gguf"),
+        vec!["--chat-template-kwargs", r#"{"enable_thinking": false}"#]
+    );
+    assert!(model_quirk_args("llama-3.2-3b-instruct-q4_k_m.gguf").is_empty());
+    assert!(model_quirk_args("qwen2.5-7b-instruct.gguf").is_empty());
+}
+
+#[test]
+fn llama_args_embeddings_mode_drops_chat_options() {
+    let model = PathBuf::from("/models/Qwen3-Embedding-0.6B.gguf");
+    let mmproj = PathBuf::from("/models/mmproj.gguf");
+    let mut launch = LlamaLaunch {
+        port: 8083,
+        model: &model,
+        ctx_size: 1024,
+        gpu_layers: 99,
+        threads: Some(4),
+        flash_attn: Some(true),
+        embeddings: false,
+        mmproj: Some(&mmproj),
+        battery_saver: true,
+    };
+
+    let chat = llama_server_args(&launch);
+    for arg in [
+        "--jinja",
+        "--chat-template-kwargs",
+        "--mmproj",
+        "--parallel",
+    ] {
+        assert!(chat.iter().any(|a| a == arg), "chat args missing {}", arg);
+    }
+    assert!(!chat.iter().any(|a| a == "--embedding"));
+
+    launch.embeddings = true;
+    let embed = llama_server_args(&launch);
+    assert!(embed.iter().any(|a| a == "--embedding"));
+    for arg in [
+        "--jinja",
+        "--chat-template-kwargs",
+        "--mmproj",
+        "--parallel",
+    ] {
+        assert!(
+            !embed.iter().any(|a| a == arg),
+            "embedding args include {}",
+            arg
+        );
+    }
+    let threads = embed.iter().position(|a| a == "--threads").unwrap();
+    assert_eq!(embed[threads + 1], "4");
+}
+
+#[test]
+fn validate_gguf_checks_magic_and_version() {
+    let dir = temp_dir("gguf");
+
+    let valid = dir.join("valid.gguf");
+    let mut header = b"GGUF".to_vec();
+    header.extend_from_slice(&3u32.to_le_bytes());
+    header.extend_from_slice(&[0u8; 16]);
+    fs::write(&valid, &header).unwrap();
+    assert!(validate_gguf(&valid).is_ok());
+
+    let garbage = dir.join("garbage.gguf");
+    fs::write(&garbage, b"<html>404 Not Found</html>").unwrap();
+    assert!(validate_gguf(&garbage)
+        .unwrap_err()
+        .contains("not a GGUF file"));
+
+    let future = dir.join("future.gguf");
+    let mut header = b"GGUF".to_vec();
+    header.extend_from_slice(&99u32.to_le_bytes());
+    fs::write(&future, &header).unwrap();
+    assert!(validate_gguf(&future).unwrap_err().contains("version 99"));
+
+    let truncated = dir.join("truncated.gguf");
+    fs::write(&truncated, b"GG").unwrap();
+    assert!(validate_gguf(&truncated).unwrap_err().contains("truncated"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn free_port_with_kills_then_rechecks() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    assert!(!is_port_free(port));
+
+    // The "orphan" releases the port when killed
+    assert!(free_port_with(port, move || drop(listener)).is_ok());
+    assert!(is_port_free(port));
+}
+
+#[test]
+fn free_port_with_reports_port_still_in_use() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let err = free_port_with(port, || {}).unwrap_err();
+    assert_eq!(err, format!("port {} in use", port));
+    drop(listener);
+}
+
+#[test]
+fn prepare_for_update_refuses_new_starts() {
+    let mut state = ProcessManagerState::default();
+    assert!(state.prepare_for_update().is_empty());
+
+    let err = state.start_llama(None).unwrap_err();
+    assert_eq!(err.kind, PmErrorKind::Updating);
+    assert!(err.message.contains("preparing to update"));
+    assert_eq!(
+        state.start_server().unwrap_err().kind,
+        PmErrorKind::Updating
+    );
+    assert!(state.llama.is_none());
+}
+
+#[test]
+fn exit_hint_classifies_signals_and_codes() {
+    assert!(exit_hint(None, Some(9)).contains("out of memory"));
+    assert!(exit_hint(None, Some(11)).contains("segmentation fault"));
+    assert_eq!(exit_hint(Some(0), None), "exited normally");
+    assert_eq!(exit_hint(Some(2), None), "exited with code 2");
+    assert!(exit_hint(Some(0xC000_0005_u32 as i32), None).contains("access violation"));
+}
+
+#[cfg(unix)]
+#[test]
+fn exit_info_records_terminating_signal() {
+    let status = Command::new("sh")
+        .args(["-c", "kill -9 $$"])
+        .status()
+        .unwrap();
+    let info = ExitInfo::from_status(status);
+    assert_eq!(info.code, None);
+    assert_eq!(info.signal, Some(9));
+    assert!(info.hint.contains("out of memory"));
+}
+
+#[cfg(unix)]
+#[test]
+fn last_failure_captures_exit_code_and_stderr_tail() {
+    let mut child = Command::new("sh")
+        .args([
+            "-c",
+            "i=0; while [ $i -lt 60 ]; do echo line $i >&2; i=$((i+1)); done; \
+             echo 'unknown model architecture' >&2; exit 3",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut state = ProcessManagerState::default();
+    state.logs.attach("whisper", &mut child);
+    state.whisper = Some(managed(child, WHISPER_PORT));
+    state.whisper.as_mut().unwrap().child.wait().unwrap();
+    // Let the reader thread drain the pipe
+    thread::sleep(Duration::from_millis(200));
+
+    assert_eq!(state.check_liveness(), vec!["whisper"]);
+    let failure = state.last_failure("whisper").unwrap();
+    assert_eq!(failure.exit.code, Some(3));
+    assert_eq!(failure.stderr_tail.len(), STDERR_TAIL_LINES);
+    assert_eq!(
+        failure.stderr_tail.last().unwrap(),
+        "unknown model architecture"
+    );
+    assert!(failure.when > 0);
+    assert_eq!(state.status().last_exit["whisper"].code, Some(3));
+    assert!(state.last_failure("llama").is_none());
+}
+
+#[test]
+fn compare_ports_flags_divergent_port() {
+    let m = compare_ports("llama", 8082, 9000, true).unwrap();
+    assert_eq!(m.allocated, 8082);
+    assert_eq!(m.running, 9000);
+    assert!(m.suggestion.contains("port 8082"));
+    assert_eq!(compare_ports("llama", 8082, 8082, true), None);
+}
+
+#[test]
+fn compare_ports_flags_silent_service() {
+    let m = compare_ports("whisper", 8081, 8081, false).unwrap();
+    assert!(!m.listening);
+    assert!(m.suggestion.contains("Nothing is listening"));
+}
+
+#[test]
+fn port_listening_detects_bound_socket() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    assert!(port_listening(port));
+    drop(listener);
+    assert!(!port_listening(port));
+}
+
+#[test]
+fn diagnose_ports_requires_allocated_ports() {
+    let mut state = ProcessManagerState::default();
+    assert!(state.diagnose_ports(false).is_err());
+
+    state.allocated_ports = Some(AllocatedPorts {
+        server: 5000,
+        llama: 8082,
+        whisper: 8081,
+        embedding: 8083,
+        request_token: String::new(),
+    });
+    let report = state.diagnose_ports(true).unwrap();
+    assert!(report.consistent);
+    assert!(report.fixed.is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn status_is_served_while_unlock_waits_for_ports() {
+    // Stands in for a server that takes a while to report its ports, then
+    // answers /health on the reported port.
+    let health_port = mock_http_server(vec![200]);
+    let script = format!(
+        "read key; sleep 1; echo 'PORTS:{},8082,8081,8083|TOKEN:abcdefgh'; sleep 5",
+        health_port
+    );
+    let child = Command::new("sh")
+        .args(["-c", &script])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let pm = Arc::new(PmState(Mutex::new(ProcessManagerState::default())));
+    pm.0.lock().unwrap().server = Some(managed(child, 0));
+
+    let unlocking = Arc::clone(&pm);
+    let handle = thread::spawn(move || unlocking.send_passphrase("00".to_string()));
+    thread::sleep(Duration::from_millis(200));
+
+    let started = std::time::Instant::now();
+    let status = pm.0.lock().unwrap().status();
+    assert!(started.elapsed() < Duration::from_millis(500));
+    assert!(status.server.is_none());
+    assert!(pm.0.lock().unwrap().start_server().is_ok());
+
+    let ports = handle.join().unwrap().unwrap();
+    assert_eq!(ports.server, health_port);
+
+    let mut state = pm.0.lock().unwrap();
+    assert_eq!(state.status().request_token.as_deref(), Some("abcdefgh"));
+    let mut proc = state.server.take().unwrap();
+    stop_drain_threads(&mut proc);
+    let _ = proc.child.kill();
+    let _ = proc.child.wait();
+}
+
+#[cfg(unix)]
+#[test]
+fn ensure_running_returns_existing_process() {
+    let child = Command::new("sleep").arg("5").spawn().unwrap();
+    let pid = child.id();
+    let mut state = ProcessManagerState {
+        whisper: Some(managed(child, WHISPER_PORT)),
+        ..Default::default()
+    };
+
+    // An explicit start still refuses a second instance
+    let err = state.start_whisper(None).unwrap_err();
+    assert!(err.message.contains("already running"));
+    assert_eq!(
+        state.ensure_running("whisper").unwrap(),
+        (pid, WHISPER_PORT)
+    );
+    assert!(state.ensure_running("server").is_err());
+
+    let _ = state.stop("whisper");
+}
+
+#[test]
+fn restart_rejects_unknown_and_server() {
+    let mut state = ProcessManagerState::default();
+    assert!(state.restart("bogus").is_err());
+    assert!(state.restart("server").is_err());
+}
+
+#[test]
+fn restart_of_stopped_service_attempts_a_start() {
+    let mut state = ProcessManagerState::default();
+    state.prepare_for_update();
+    // Not running, so restart goes straight to start, which is refused here.
+    let err = state.restart("whisper").unwrap_err();
+    assert_eq!(err.kind, PmErrorKind::Updating);
+}
+
+#[cfg(unix)]
+#[test]
+fn log_hub_fans_out_child_output() {
+    let hub = Arc::new(LogHub::default());
+    let llama = hub.subscribe(Some("llama"));
+    let all = hub.subscribe(None);
+    let whisper = hub.subscribe(Some("whisper"));
+
+    let mut child = Command::new("sh")
+        .args(["-c", "echo ready; echo oops >&2"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    hub.attach("llama", &mut child);
+    child.wait().unwrap();
+
+    let timeout = Duration::from_secs(5);
+    let mut lines = [
+        llama.recv_timeout(timeout).unwrap(),
+        llama.recv_timeout(timeout).unwrap(),
+    ];
+    lines.sort_by_key(|l| l.stream);
+    assert_eq!(
+        (lines[0].stream, lines[0].line.as_str()),
+        ("stderr", "oops")
+    );
+    assert_eq!(
+        (lines[1].stream, lines[1].line.as_str()),
+        ("stdout", "ready")
+    );
+    assert_eq!(all.recv_timeout(timeout).unwrap().service, "llama");
+    assert!(whisper.try_recv().is_err());
+}
+
+#[test]
+fn log_hub_drops_disconnected_subscribers() {
+    let hub = LogHub::default();
+    let kept = hub.subscribe(None);
+    drop(hub.subscribe(None));
+    hub.publish(LogLine {
+        service: "server",
+        stream: "stdout",
+        line: "hello".to_string(),
+    });
+    assert_eq!(hub.subscribers.lock().unwrap().len(), 1);
+    assert_eq!(kept.try_recv().unwrap().line, "hello");
+}
+
+#[test]
+fn grace_seconds_are_clamped() {
+    assert_eq!(parse_grace_seconds(None), DEFAULT_SERVER_GRACE_SECS);
+    assert_eq!(parse_grace_seconds(Some(" ")), DEFAULT_SERVER_GRACE_SECS);
+    assert_eq!(parse_grace_seconds(Some("abc")), DEFAULT_SERVER_GRACE_SECS);
+    assert_eq!(parse_grace_seconds(Some("0")), MIN_SERVER_GRACE_SECS);
+    assert_eq!(parse_grace_seconds(Some("10")), 10);
+    assert_eq!(parse_grace_seconds(Some("3600")), MAX_SERVER_GRACE_SECS);
+}
+
+#[test]
+fn ports_timeout_parsing() {
+    assert_eq!(parse_ports_timeout(None), DEFAULT_PORTS_TIMEOUT);
+    assert_eq!(parse_ports_timeout(Some(" 45 ")), Duration::from_secs(45));
+    assert_eq!(parse_ports_timeout(Some("0")), DEFAULT_PORTS_TIMEOUT);
+    assert_eq!(parse_ports_timeout(Some("soon")), DEFAULT_PORTS_TIMEOUT);
+}
+
+#[cfg(unix)]
+fn slow_server(script: &str) -> Child {
+    Command::new("sh")
+        .args(["-c", script])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
+
+#[cfg(unix)]
+#[test]
+fn wait_for_allocated_ports_accepts_line_just_inside_timeout() {
+    let mut child = slow_server(
+        "echo 'Opening database'; sleep 1; echo 'PORTS:5000,8082,8081,8083|TOKEN:abcdefgh'",
+    );
+    let ports = wait_for_allocated_ports(&mut child, Duration::from_millis(1800)).unwrap();
+    assert_eq!(ports.server, 5000);
+    assert_eq!(ports.request_token, "abcdefgh");
+    let _ = child.wait();
+}
+
+#[cfg(unix)]
+#[test]
+fn wait_for_allocated_ports_times_out() {
+    let mut child = slow_server("sleep 2; echo 'PORTS:5000,8082,8081,8083|TOKEN:abcdefgh'");
+    let err = wait_for_allocated_ports(&mut child, Duration::from_millis(300)).unwrap_err();
+    assert!(err.starts_with("Timeout"));
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[test]
+fn line_buffer_joins_lines_split_across_reads() {
+    let mut lines = LineBuffer::default();
+    assert!(lines.push(b"PORTS:5000,80").is_empty());
+    assert_eq!(
+        lines.push(b"82,8081,8083|TOKEN:abcdefgh\nWAITING_"),
+        vec!["PORTS:5000,8082,8081,8083|TOKEN:abcdefgh"]
+    );
+    assert_eq!(
+        lines.push(b"FOR_PASSPHRASE\r\n\nOpening"),
+        vec!["WAITING_FOR_PASSPHRASE", ""]
+    );
+    assert_eq!(lines.pending(), "Opening");
+
+    // A multi-byte character split between reads is decoded whole
+    let word = "Réglages\n".as_bytes();
+    assert!(lines.push(&word[..2]).is_empty());
+    assert_eq!(lines.push(&word[2..]), vec!["OpeningRéglages"]);
+}
+
+#[test]
+fn parse_signal_line_classifies_startup_output() {
+    assert!(matches!(
+        parse_signal_line("WAITING_FOR_PASSPHRASE "),
+        Some(Ok(ServerSignal::WaitingForPassphrase))
+    ));
+    assert!(matches!(
+        parse_signal_line("PORTS:5000,8082,8081|TOKEN:abcdefgh"),
+        Some(Ok(ServerSignal::Ports(_)))
+    ));
+    assert!(matches!(parse_signal_line("PORTS:5000"), Some(Err(_))));
+    assert_eq!(
+        parse_signal_line("ERROR:Database locked")
+            .unwrap()
+            .unwrap_err(),
+        "Database locked"
+    );
+    assert!(parse_signal_line("INFO: loading models").is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn wait_for_allocated_ports_reassembles_chunked_line() {
+    let mut child = slow_server(
+        "printf 'Opening database\\nPORTS:5000,80'; sleep 0.3; \
+         printf '82,8081,8083|TOK'; sleep 0.3; printf 'EN:abcdefgh\\n'; sleep 1",
+    );
+    let ports = wait_for_allocated_ports(&mut child, Duration::from_secs(5)).unwrap();
+    assert_eq!(ports.llama, 8082);
+    assert_eq!(ports.embedding, 8083);
+    assert_eq!(ports.request_token, "abcdefgh");
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(unix)]
+#[test]
+fn wait_for_allocated_ports_detects_split_wrong_key() {
+    let mut child = slow_server(
+        "printf 'sqlite: Wrong encryption' >&2; sleep 0.3; printf ' key?\\n' >&2; sleep 2",
+    );
+    let err = wait_for_allocated_ports(&mut child, Duration::from_secs(5)).unwrap_err();
+    assert_eq!(err, "Wrong encryption key");
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(unix)]
+#[test]
+fn wait_for_allocated_ports_reports_server_traceback() {
+    let mut child = slow_server(
+        "i=0; while [ $i -lt 40 ]; do echo \"noise $i\" >&2; i=$((i+1)); done; \
+         echo 'Traceback (most recent call last):' >&2; \
+         echo \"ModuleNotFoundError: No module named 'sqlcipher3'\" >&2; exit 1",
+    );
+    let err = wait_for_allocated_ports(&mut child, Duration::from_secs(5)).unwrap_err();
+    assert!(
+        err.starts_with("Server exited before sending signal"),
+        "{}",
+        err
+    );
+    assert!(err.ends_with("ModuleNotFoundError: No module named 'sqlcipher3'"));
+    assert!(err.contains("[... 22 earlier line(s)]"));
+    assert!(!err.contains("noise 0\n"));
+    let _ = child.wait();
+}
+
+#[test]
+fn with_stderr_keeps_short_output_whole() {
+    assert_eq!(with_stderr("Failed", b""), "Failed");
+    assert_eq!(with_stderr("Failed", b"\n  \n"), "Failed");
+    assert_eq!(
+        with_stderr("Failed", b"line one\nline two\n"),
+        "Failed\nline one\nline two"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn unlock_fails_when_health_never_answers() {
+    // Ports are reported, but /health 404s as it did before the route existed
+    let health_port = mock_http_server(vec![404; 50]);
+    let script = format!(
+        "read key; echo 'PORTS:{},8082,8081,8083|TOKEN:abcdefgh'; sleep 5",
+        health_port
+    );
+    let child = Command::new("sh")
+        .args(["-c", &script])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let pm = PmState(Mutex::new(ProcessManagerState::default()));
+    pm.0.lock().unwrap().server = Some(managed(child, 0));
+
+    let err = pm
+        .send_passphrase_within("00".to_string(), Duration::from_secs(1))
+        .unwrap_err();
+    assert_eq!(err.kind, PmErrorKind::ServerNotReady, "{}", err);
+    let state = pm.0.lock().unwrap();
+    assert!(state.server.is_none());
+    assert!(state.allocated_ports.is_none());
+}
+
+/// A supervised process with no drain threads or launch settings.
+fn managed(child: Child, port: u16) -> ManagedProcess {
+    ManagedProcess {
+        child,
+        port,
+        drain_handles: None,
+        drain_shutdown: None,
+        gpu_layers: None,
+        flash_attn: None,
+        started_at: Instant::now(),
+        model_name: None,
+    }
+}
+
+/// Serve one canned HTTP status per connection, in order.
+fn mock_http_server(statuses: Vec<u16>) -> u16 {
+    use std::io::Read;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut statuses = statuses.into_iter();
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            // Read the whole request; the TCP gate connects without sending anything
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+            if request.is_empty() {
+                continue;
+            }
+            let Some(status) = statuses.next() else { break };
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            );
+        }
+    });
+    port
+}
+
+#[test]
+fn poll_delay_backs_off_to_interval() {
+    let interval = Duration::from_millis(500);
+    let mut delay = READY_FIRST_POLL;
+    let mut delays = Vec::new();
+    for _ in 0..6 {
+        delays.push(delay.as_millis());
+        delay = next_poll_delay(delay, interval);
+    }
+    assert_eq!(delays, vec![50, 100, 200, 400, 500, 500]);
+}
+
+#[test]
+fn wait_for_http_ready_waits_for_late_server() {
+    // Nothing listens on the port until the server comes up
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(300));
+        let listener = std::net::TcpListener::bind(("127.0.0.1", port)).unwrap();
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let mut line = String::new();
+            let _ = BufReader::new(&stream).read_line(&mut line);
+            if !line.is_empty() {
+                let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+            }
+        }
+    });
+
+    let probe = ReadinessProbe::for_service("server").unwrap();
+    let start = Instant::now();
+    wait_for_http_ready(
+        port,
+        &probe,
+        Duration::from_secs(5),
+        Duration::from_millis(100),
+    )
+    .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(300));
+}
+
+#[test]
+fn wait_for_http_ready_times_out_on_schedule() {
+    let probe = ReadinessProbe::for_service("server").unwrap();
+    let port = mock_http_server(vec![503; 50]);
+    let start = Instant::now();
+    let err = wait_for_http_ready(
+        port,
+        &probe,
+        Duration::from_millis(400),
+        Duration::from_millis(100),
+    )
+    .unwrap_err();
+    assert!(err.contains("not ready"), "{}", err);
+    // The last sleep is clipped to the deadline
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn probe_ready_requires_expected_status() {
+    let probe = ReadinessProbe::for_service("llama").unwrap();
+    let port = mock_http_server(vec![503, 200]);
+    assert!(!probe_ready(port, &probe));
+    assert!(probe_ready(port, &probe));
+}
+
+#[test]
+fn wait_for_ready_polls_until_healthy() {
+    let probe = ReadinessProbe::for_service("whisper").unwrap();
+    let port = mock_http_server(vec![503, 503, 200]);
+    assert!(wait_for_ready(port, &probe, Duration::from_secs(5)).is_ok());
+
+    let port = mock_http_server(vec![503; 10]);
+    let err = wait_for_ready(port, &probe, Duration::from_millis(200)).unwrap_err();
+    assert!(err.contains("not ready"));
+}
+
+#[cfg(unix)]
+#[test]
+fn status_uptime_increases() {
+    let child = Command::new("sleep").arg("5").spawn().unwrap();
+    let mut state = ProcessManagerState {
+        whisper: Some(ManagedProcess {
+            started_at: Instant::now() - Duration::from_secs(10),
+            ..managed(child, WHISPER_PORT)
+        }),
+        ..Default::default()
+    };
+
+    let first = state.status().whisper.unwrap();
+    thread::sleep(Duration::from_millis(1100));
+    let second = state.status().whisper.unwrap();
+    assert!(first.uptime_seconds >= 10);
+    assert!(second.uptime_seconds > first.uptime_seconds);
+    assert_eq!(second.restart_count, 0);
+    assert_eq!(second.model_name, None);
+
+    let _ = state.stop("whisper");
+}
+
+#[cfg(unix)]
+#[test]
+fn stop_all_reports_stopped_and_orphans() {
+    let child = Command::new("sleep").arg("5").spawn().unwrap();
+    let mut state = ProcessManagerState {
+        whisper: Some(managed(child, WHISPER_PORT)),
+        ..Default::default()
+    };
+
+    let report = state.stop_all_with(|_| false);
+    assert_eq!(report.stopped, vec!["whisper"]);
+    assert_eq!(report.already_stopped, vec!["llama", "embedding", "server"]);
+    assert!(report.killed_by_name.is_empty());
+    assert!(state.whisper.is_none());
+
+    // A second call finds nothing left to stop
+    let report = state.stop_all_with(|_| false);
+    assert!(report.stopped.is_empty());
+    assert_eq!(report.already_stopped.len(), 4);
+}
+
+#[test]
+fn sample_usage_reads_own_process() {
+    let mut sys = sysinfo::System::new();
+    let own = std::process::id();
+    let usage = sample_usage(&mut sys, &[own, u32::MAX]);
+    assert_eq!(usage.len(), 1);
+    let own = usage[&own];
+    assert!(own.memory_mb > 0);
+    assert!(own.cpu_percent >= 0.0);
+}
+
+#[cfg(unix)]
+#[test]
+fn monitor_reaps_exited_child_without_a_request() {
+    let child = Command::new("sleep").arg("0.1").spawn().unwrap();
+    let pm = Arc::new(PmState(Mutex::new(ProcessManagerState::default())));
+    pm.0.lock().unwrap().whisper = Some(managed(child, WHISPER_PORT));
+
+    let (tx, rx) = mpsc::channel();
+    let monitor = Arc::clone(&pm);
+    thread::spawn(move || {
+        monitor.run_monitor(
+            || Duration::from_millis(50),
+            |tick| {
+                if tick.died.is_empty() {
+                    return true;
+                }
+                let _ = tx.send(tick.died);
+                false
+            },
+        );
+    });
+
+    let died = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(died, vec!["whisper"]);
+    assert!(pm.0.lock().unwrap().whisper.is_none());
+}
+
+#[test]
+fn parse_pid_rejects_garbage_and_zero() {
+    assert_eq!(parse_pid("1234\n"), Some(1234));
+    assert_eq!(parse_pid(" 42 "), Some(42));
+    assert_eq!(parse_pid("0"), None);
+    assert_eq!(parse_pid("-1"), None);
+    assert_eq!(parse_pid("not a pid"), None);
+    assert_eq!(parse_pid(""), None);
+}
+
+#[test]
+fn phlox_dir_matches_server_app_name() {
+    // server/constants.py: user_data_dir(APP_NAME="Phlox", APP_AUTHOR="bloodworks.io")
+    let dir = phlox_dir().unwrap();
+    assert_eq!(dir.file_name().unwrap(), "Phlox");
+    #[cfg(windows)]
+    assert_eq!(dir.parent().unwrap().file_name().unwrap(), "bloodworks.io");
+}
+
+#[test]
+fn migrate_data_dir_moves_legacy_files() {
+    let root = temp_dir("migrate-data-dir");
+    let legacy = root.join("Roaming").join("Phlox");
+    let canonical = root.join("Local").join("bloodworks.io").join("Phlox");
+    fs::create_dir_all(legacy.join("llm_models")).unwrap();
+    fs::write(legacy.join("llm_models").join("model.gguf"), b"GGUF").unwrap();
+    fs::write(legacy.join("llm_ctx_size.txt"), b"8192").unwrap();
+    fs::write(legacy.join("phlox_database.sqlite"), b"stale").unwrap();
+    fs::create_dir_all(&canonical).unwrap();
+    fs::write(canonical.join("phlox_database.sqlite"), b"current").unwrap();
+
+    assert_eq!(migrate_data_dir(&legacy, &canonical).unwrap(), 2);
+    assert!(canonical.join("llm_models").join("model.gguf").is_file());
+    assert_eq!(
+        fs::read(canonical.join("llm_ctx_size.txt")).unwrap(),
+        b"8192"
+    );
+    // The server's database is never overwritten; the legacy copy stays put
+    assert_eq!(
+        fs::read(canonical.join("phlox_database.sqlite")).unwrap(),
+        b"current"
+    );
+    assert!(legacy.join("phlox_database.sqlite").is_file());
+
+    // Nothing left to move; a missing legacy dir is a no-op
+    assert_eq!(migrate_data_dir(&legacy, &canonical).unwrap(), 0);
+    fs::remove_file(legacy.join("phlox_database.sqlite")).unwrap();
+    assert_eq!(migrate_data_dir(&legacy, &canonical).unwrap(), 0);
+    assert!(!legacy.exists());
+    assert_eq!(migrate_data_dir(&legacy, &canonical).unwrap(), 0);
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn atomic_write_never_exposes_a_partial_file() {
+    let dir = temp_dir("atomic-write");
+    let path = dir.join("llama.pid");
+
+    atomic_write(&path, b"1234").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "1234");
+
+    // A write that dies halfway leaves the previous contents in place
+    let err = atomic_write_with(&path, |file| {
+        file.write_all(b"56")?;
+        Err(std::io::Error::other("simulated crash"))
+    })
+    .unwrap_err();
+    assert_eq!(err.to_string(), "simulated crash");
+    assert_eq!(fs::read_to_string(&path).unwrap(), "1234");
+
+    atomic_write(&path, b"5678").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "5678");
+    let names: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .flatten()
+        .map(|e| e.file_name())
+        .collect();
+    assert_eq!(names, vec!["llama.pid"]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn pid_files_live_in_the_data_dir() {
+    for service in PID_FILE_SERVICES {
+        if let Some(path) = pid_file(service) {
+            assert_eq!(path.parent(), phlox_dir().as_deref());
+            assert_eq!(
+                path.file_name().unwrap(),
+                format!("{}.pid", service).as_str()
+            );
+        }
+    }
+}
+
+#[test]
+fn stop_is_idempotent() {
+    let mut state = ProcessManagerState::default();
+    for service in ["llama", "whisper", "embedding", "server"] {
+        assert_eq!(state.stop(service), Ok(()), "{}", service);
+    }
+    assert!(state.stop("nonsense").is_err());
+}
+
+#[test]
+fn service_status_changed_serializes() {
+    let event = ServiceStatusChanged {
+        service: "whisper",
+        running: false,
+    };
+    assert_eq!(
+        serde_json::to_value(&event).unwrap(),
+        serde_json::json!({ "service": "whisper", "running": false })
+    );
+}
+
+#[test]
+fn bundled_binaries_are_checked_against_manifest() {
+    let dir = temp_dir("binary-manifest");
+    let resources = dir.join("Resources");
+    fs::create_dir_all(&resources).unwrap();
+    let manifest_path = resources.join(binary_manifest::MANIFEST_FILE);
+    assert!(verify_binaries_in(&manifest_path, &dir).is_err());
+
+    fs::write(dir.join("phlox-llama-server"), b"llama").unwrap();
+    fs::write(dir.join("phlox-whisper-server"), b"whisper").unwrap();
+    let manifest = format!(
+        "{}  phlox-llama-server\n{}  phlox-whisper-server\n{}  phlox-server\n",
+        download::sha256_file(&dir.join("phlox-llama-server")).unwrap(),
+        download::sha256_file(&dir.join("phlox-llama-server")).unwrap(),
+        download::sha256_file(&dir.join("phlox-whisper-server")).unwrap(),
+    );
+    fs::write(&manifest_path, manifest).unwrap();
+
+    assert_eq!(
+        verify_binaries_in(&manifest_path, &dir).unwrap(),
+        BinaryVerification {
+            ok: vec!["phlox-llama-server".to_string()],
+            mismatched: vec!["phlox-whisper-server".to_string()],
+            missing: vec!["phlox-server".to_string()],
+        }
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
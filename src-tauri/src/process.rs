@@ -1,11 +1,32 @@
-use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
 use std::time::Duration;
 
-/// Get the PID file path for a service.
+use serde::Serialize;
 
-fn pid_file_for_service(service: &str) -> Option<PathBuf> {
-    crate::pm::phlox_dir().map(|dir| dir.join(format!("{}.pid", service)))
+use crate::pm;
+
+/// Binary names swept after the PID files, in kill order. The embedding
+/// server uses the same binary as the LLM server, so phlox-llama-server
+/// covers both.
+const KILL_NAME_PATTERNS: [&str; 3] =
+    ["phlox-llama-server", "phlox-whisper-server", "phlox-server"];
+
+/// How a [`KillTarget`] was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KillSource {
+    PidFile,
+    NamePattern,
+}
+
+/// A live process [`kill_all_processes`] would signal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KillTarget {
+    pub pid: u32,
+    /// The PID file's service, or the name pattern that matched.
+    pub name: &'static str,
+    pub source: KillSource,
 }
 
 /// Check if a specific PID is alive
@@ -39,9 +60,7 @@ fn is_process_alive(pid: u32) -> bool {
 /// Read PID from file and verify process is actually running.
 /// Returns Some(pid) if running, None if not running or stale file.
 pub fn is_process_running_from_pid(service: &str) -> Option<u32> {
-    let pid_file = pid_file_for_service(service)?;
-    let pid_str = std::fs::read_to_string(&pid_file).ok()?;
-    let pid: u32 = pid_str.trim().parse().ok()?;
+    let pid = pm::read_pid_file(service)?;
 
     if is_process_alive(pid) {
         log::debug!("Service {} is running with PID {}", service, pid);
@@ -49,54 +68,90 @@ pub fn is_process_running_from_pid(service: &str) -> Option<u32> {
     } else {
         // Stale PID file, clean it up
         log::debug!("Cleaning up stale PID file for {} (PID {})", service, pid);
-        let _ = std::fs::remove_file(&pid_file);
+        pm::remove_pid_file(service);
         None
     }
 }
 
-/// Kill a process by PID and wait for it to exit
-fn kill_process_by_pid(pid: u32, service_name: &str) {
+/// Kill a process by PID and wait for it to exit.
+/// Returns an error if the process could not be signalled or survived.
+pub fn kill_process_by_pid(pid: u32, service_name: &str) -> Result<(), String> {
     #[cfg(unix)]
     {
-        use libc::{kill, SIGTERM};
-        unsafe {
-            log::info!("Killing {} process (PID: {})", service_name, pid);
-            if kill(pid as i32, SIGTERM) == 0 {
-                // Wait for process to exit
-                for _ in 0..50 {
-                    // 5 seconds max
-                    thread::sleep(Duration::from_millis(100));
-                    if !is_process_alive(pid) {
-                        log::info!("{} process (PID: {}) terminated", service_name, pid);
-                        return;
-                    }
-                }
-                // Process didn't exit gracefully, force kill
-                log::warn!("Force killing {} process (PID: {})", service_name, pid);
-                let _ = kill(pid as i32, 9); // SIGKILL
-                thread::sleep(Duration::from_millis(500));
+        use libc::{kill, SIGKILL, SIGTERM};
+
+        // 0 and anything that wraps negative would signal a whole process group
+        let Ok(raw_pid) = i32::try_from(pid) else {
+            return Err(format!("Invalid PID {} for {}", pid, service_name));
+        };
+        if raw_pid <= 0 {
+            return Err(format!("Invalid PID {} for {}", pid, service_name));
+        }
+
+        log::info!("Killing {} process (PID: {})", service_name, pid);
+        if unsafe { kill(raw_pid, SIGTERM) } != 0 {
+            return Err(format!(
+                "Failed to signal {} (PID: {}): {}",
+                service_name,
+                pid,
+                std::io::Error::last_os_error()
+            ));
+        }
+        // Wait for process to exit
+        for _ in 0..50 {
+            // 5 seconds max
+            thread::sleep(Duration::from_millis(100));
+            if !is_process_alive(pid) {
+                log::info!("{} process (PID: {}) terminated", service_name, pid);
+                return Ok(());
             }
         }
+        // Process didn't exit gracefully, force kill
+        log::warn!("Force killing {} process (PID: {})", service_name, pid);
+        unsafe {
+            let _ = kill(raw_pid, SIGKILL);
+        }
+        thread::sleep(Duration::from_millis(500));
+        if is_process_alive(pid) {
+            return Err(format!(
+                "{} process (PID: {}) survived SIGKILL",
+                service_name, pid
+            ));
+        }
+        Ok(())
     }
 
     #[cfg(windows)]
     {
         use std::process::Command;
         log::info!("Killing {} process (PID: {})", service_name, pid);
-        let _ = Command::new("taskkill")
+        let output = Command::new("taskkill")
             .arg("/F")
             .arg("/PID")
             .arg(pid.to_string())
-            .output();
+            .output()
+            .map_err(|e| format!("Failed to run taskkill for {}: {}", service_name, e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "taskkill failed for {} (PID: {}): {}",
+                service_name,
+                pid,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
 
         // Wait for process to exit
         for _ in 0..50 {
             thread::sleep(Duration::from_millis(100));
             if !is_process_alive(pid) {
                 log::info!("{} process (PID: {}) terminated", service_name, pid);
-                return;
+                return Ok(());
             }
         }
+        Err(format!(
+            "{} process (PID: {}) still running after taskkill",
+            service_name, pid
+        ))
     }
 }
 
@@ -135,39 +190,222 @@ pub fn kill_all_processes() {
     log::info!("Killing all existing processes...");
 
     // First, kill any processes tracked by PID files
-    let services = ["llama", "whisper", "server", "embedding"];
-
-    for service in &services {
+    let mut failures = Vec::new();
+    for service in pm::PID_FILE_SERVICES {
         if let Some(pid) = is_process_running_from_pid(service) {
-            kill_process_by_pid(pid, service);
+            if let Err(e) = kill_process_by_pid(pid, service) {
+                failures.push(e);
+            }
         }
         // Clean up PID file even if process wasn't running
-        if let Some(pid_file) = pid_file_for_service(service) {
-            let _ = std::fs::remove_file(&pid_file);
-        }
+        pm::remove_pid_file(service);
     }
 
-    // Fallback: kill by name pattern for any orphaned processes.
-    // The embedding server uses the same binary as the LLM server, so
-    // phlox-llama-server covers both.
-    kill_process_by_name("phlox-llama-server", "phlox-llama-server");
-    kill_process_by_name("phlox-whisper-server", "phlox-whisper-server");
-    kill_process_by_name("phlox-server", "phlox-server");
+    // Fallback: kill by name pattern for any orphaned processes
+    for pattern in KILL_NAME_PATTERNS {
+        kill_process_by_name(pattern, pattern);
+    }
 
     // Final wait to ensure all processes are gone
     thread::sleep(Duration::from_millis(500));
 
-    log::info!("All processes killed");
+    if failures.is_empty() {
+        log::info!("All processes killed");
+    } else {
+        log::error!(
+            "{} process(es) could not be killed: {}",
+            failures.len(),
+            failures.join("; ")
+        );
+    }
 }
 
-pub fn cleanup_stale_files() {
-    if let Some(phlox_dir) = crate::pm::phlox_dir() {
-        // Clean up PID files
-        for service in ["llama", "whisper", "server", "embedding"] {
-            let pid_file = phlox_dir.join(format!("{}.pid", service));
-            if pid_file.exists() {
-                let _ = std::fs::remove_file(&pid_file);
+/// Dry run of [`kill_all_processes`]: the live processes it would signal,
+/// found without signalling anything or removing PID files.
+pub fn list_killable_processes() -> Vec<KillTarget> {
+    let pid_files: Vec<(&'static str, u32)> = pm::PID_FILE_SERVICES
+        .iter()
+        .filter_map(|&service| Some((service, pm::read_pid_file(service)?)))
+        .collect();
+    find_kill_targets(&pid_files, &KILL_NAME_PATTERNS)
+}
+
+/// Live PIDs from `pid_files`, then other processes whose name or command
+/// line contains one of `patterns`. This process is never listed.
+fn find_kill_targets(
+    pid_files: &[(&'static str, u32)],
+    patterns: &[&'static str],
+) -> Vec<KillTarget> {
+    let mut targets: Vec<KillTarget> = pid_files
+        .iter()
+        .filter(|&&(_, pid)| i32::try_from(pid).is_ok_and(|p| p > 0) && is_process_alive(pid))
+        .map(|&(name, pid)| KillTarget {
+            pid,
+            name,
+            source: KillSource::PidFile,
+        })
+        .collect();
+
+    let mut sys = sysinfo::System::new();
+    sys.refresh_processes_specifics(
+        sysinfo::ProcessRefreshKind::new().with_cmd(sysinfo::UpdateKind::Always),
+    );
+    let own_pid = std::process::id();
+    let mut by_name: Vec<KillTarget> = sys
+        .processes()
+        .iter()
+        .filter_map(|(pid, process)| {
+            let pid = pid.as_u32();
+            if pid == own_pid || targets.iter().any(|t| t.pid == pid) {
+                return None;
             }
+            let cmdline = process.cmd().join(" ");
+            let &name = patterns
+                .iter()
+                .find(|&&p| process.name().contains(p) || cmdline.contains(p))?;
+            Some(KillTarget {
+                pid,
+                name,
+                source: KillSource::NamePattern,
+            })
+        })
+        .collect();
+    by_name.sort_by_key(|t| t.pid);
+    targets.extend(by_name);
+    targets
+}
+
+/// Which last-chance cleanups have started. Signals and panics are tracked
+/// apart so a panic that was caught and recovered from doesn't stop a later
+/// signal from cleaning up and exiting.
+struct ExitCleanup {
+    signal: AtomicBool,
+    panic: AtomicBool,
+}
+
+static EXIT_CLEANUP: ExitCleanup = ExitCleanup::new();
+
+impl ExitCleanup {
+    const fn new() -> Self {
+        Self {
+            signal: AtomicBool::new(false),
+            panic: AtomicBool::new(false),
         }
     }
+
+    /// Run `cleanup` for the first signal only. Returns whether it ran.
+    fn on_signal(&self, cleanup: impl FnOnce()) -> bool {
+        run_once(&self.signal, cleanup)
+    }
+
+    /// Run `cleanup` for the first panic only. Returns whether it ran.
+    fn on_panic(&self, cleanup: impl FnOnce()) -> bool {
+        run_once(&self.panic, cleanup)
+    }
+}
+
+/// Run `cleanup` unless `started` is already set. Returns whether it ran.
+fn run_once(started: &AtomicBool, cleanup: impl FnOnce()) -> bool {
+    if started.swap(true, Ordering::SeqCst) {
+        return false;
+    }
+    cleanup();
+    true
+}
+
+/// [`kill_all_processes`] for the signal handler. A second signal arriving
+/// mid-cleanup (Ctrl-C pressed twice, or SIGTERM after SIGINT) returns
+/// `false` instead of killing everything again.
+pub fn kill_all_processes_on_signal() -> bool {
+    EXIT_CLEANUP.on_signal(kill_all_processes)
+}
+
+/// [`kill_all_processes`] for the panic hook, once per run.
+pub fn kill_all_processes_on_panic() {
+    EXIT_CLEANUP.on_panic(kill_all_processes);
+}
+
+pub fn cleanup_stale_files() {
+    for service in pm::PID_FILE_SERVICES {
+        pm::remove_pid_file(service);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kill_bogus_pid_is_an_error() {
+        // Above the kernel's pid_max, so nothing can be running there
+        assert!(kill_process_by_pid(i32::MAX as u32, "bogus").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_kill_refuses_group_pids() {
+        // These would reach kill(2) as 0 or -1 and signal a process group
+        assert!(kill_process_by_pid(0, "bogus").is_err());
+        assert!(kill_process_by_pid(u32::MAX, "bogus").is_err());
+    }
+
+    /// Child for the kill-target tests; it outlives the test and is
+    /// killed at the end. Doubles as the name pattern that finds it.
+    #[cfg(unix)]
+    const SLEEP_COMMAND: &str = "sleep 60";
+
+    #[cfg(unix)]
+    #[test]
+    fn test_list_killable_processes_is_a_dry_run() {
+        let mut command = SLEEP_COMMAND.split_whitespace();
+        let mut child = std::process::Command::new(command.next().unwrap())
+            .args(command)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+
+        let targets = find_kill_targets(&[("test", pid), ("stale", i32::MAX as u32)], &[]);
+        assert_eq!(
+            targets,
+            vec![KillTarget {
+                pid,
+                name: "test",
+                source: KillSource::PidFile,
+            }]
+        );
+
+        let targets = find_kill_targets(&[], &[SLEEP_COMMAND]);
+        assert!(targets.contains(&KillTarget {
+            pid,
+            name: SLEEP_COMMAND,
+            source: KillSource::NamePattern,
+        }));
+
+        assert!(
+            child.try_wait().unwrap().is_none(),
+            "dry run killed the process"
+        );
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn test_exit_cleanup_runs_once() {
+        let started = AtomicBool::new(false);
+        let mut runs = 0;
+        assert!(run_once(&started, || runs += 1));
+        assert!(!run_once(&started, || runs += 1));
+        assert_eq!(runs, 1);
+
+        // A panic's cleanup must not swallow a later signal's exit
+        let cleanup = ExitCleanup::new();
+        let mut runs = 0;
+        assert!(cleanup.on_panic(|| runs += 1));
+        assert!(!cleanup.on_panic(|| runs += 1));
+        assert!(cleanup.on_signal(|| runs += 1));
+        assert!(!cleanup.on_signal(|| runs += 1));
+        assert_eq!(runs, 2);
+    }
 }
@@ -19,8 +19,9 @@ export const ServerConnectionCheck = ({ children }) => {
         return;
       }
 
-      // Note: With no keychain caching (PHI requirement), the unlock screen
-      // will always be shown on app launch via App.jsx logic.
+      // Note: Keychain caching is off by default (PHI requirement), so the
+      // unlock screen is shown on app launch via App.jsx logic; it unlocks
+      // itself when the user has opted in and a key is cached.
       // Server will be started after successful unlock.
       // Skip server connection check here - let App.jsx handle the flow.
 
@@ -91,7 +91,7 @@ const EncryptionSetup = ({ onComplete }) => {
         console.error("Server start failed:", serverError);
         toaster.create({
           title: "Server Warning",
-          description: serverError.toString(),
+          description: serverError?.message ?? String(serverError),
           type: "warning",
           duration: 5000,
         });
@@ -108,7 +108,7 @@ const EncryptionSetup = ({ onComplete }) => {
     } catch (error) {
       toaster.create({
         title: "Setup Failed",
-        description: error.toString() || "An error occurred during setup",
+        description: error?.message ?? (String(error) || "An error occurred during setup"),
         type: "error",
         duration: 5000,
       });
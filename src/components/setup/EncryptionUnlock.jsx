@@ -1,4 +1,4 @@
-import { useState, useCallback } from "react";
+import { useState, useCallback, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { Box, Button, Heading, HStack, VStack, Text, Input, Flex, Image, Icon, Alert } from "@chakra-ui/react";
 import { toaster } from "@/components/ui/toaster";
@@ -14,8 +14,10 @@ const EncryptionUnlock = ({ onComplete }) => {
   const [attempts, setAttempts] = useState(0);
   const [lastWasPassphrase, setLastWasPassphrase] = useState(false);
 
-  const handleSubmit = useCallback(async () => {
-    if (passphrase.length < 1) {
+  const handleSubmit = useCallback(async (cachedHex) => {
+    // onClick passes the event; only a string is a key from the keychain
+    const keychainHex = typeof cachedHex === "string" ? cachedHex : null;
+    if (!keychainHex && passphrase.length < 1) {
       toaster.create({
         title: "Passphrase Required",
         description: "Please enter your passphrase to unlock.",
@@ -28,7 +30,8 @@ const EncryptionUnlock = ({ onComplete }) => {
     setIsSubmitting(true);
     try {
       // Unlock and get hex passphrase
-      const hexPassphrase = await encryptionApi.unlock(passphrase);
+      const hexPassphrase =
+        keychainHex ?? (await encryptionApi.unlock(passphrase));
 
       // Ensure the server is warmed up / still alive before sending; pm is idempotent
       try {
@@ -85,9 +88,7 @@ const EncryptionUnlock = ({ onComplete }) => {
       const newAttempts = attempts + 1;
       setAttempts(newAttempts);
 
-      const errStr = error?.toString() || "";
-      const isPassphraseError =
-        /wrong key|wrong encryption key|cannot decrypt database/i.test(errStr);
+      const isPassphraseError = error?.code === "wrong_passphrase";
       setLastWasPassphrase(isPassphraseError);
 
       toaster.create({
@@ -109,6 +110,24 @@ const EncryptionUnlock = ({ onComplete }) => {
     }
   }, [passphrase, attempts, onComplete]);
 
+  // If the user opted in to keychain caching, try the cached key once
+  useEffect(() => {
+    let cancelled = false;
+    (async () => {
+      if (!(await encryptionApi.hasKeychain())) return;
+      try {
+        const hex = await encryptionApi.unlockWithKeychain();
+        if (!cancelled) handleSubmit(hex);
+      } catch (error) {
+        console.warn("Keychain unlock unavailable:", error);
+      }
+    })();
+    return () => {
+      cancelled = true;
+    };
+    // eslint-disable-next-line react-hooks/exhaustive-deps
+  }, []);
+
   const handleKeyPress = useCallback(
     (e) => {
       if (e.key === "Enter" && passphrase.length > 0) {
@@ -3,7 +3,8 @@ import { invoke } from "@tauri-apps/api/core";
 /**
  * Encryption API for Tauri commands
  * Handles passphrase setup and unlock for SQLCipher database encryption.
- * No keychain caching - user must re-enter passphrase on every session (PHI requirement).
+ * Keychain caching is opt-in and off by default - normally the user re-enters
+ * the passphrase every session (PHI requirement).
  */
 export const encryptionApi = {
   /**
@@ -31,8 +32,8 @@ export const encryptionApi = {
   },
 
   /**
-   * Check if passphrase is cached in keychain
-   * Always returns false since we don't use keychain caching (PHI requirement)
+   * Check if the database key is cached in the OS keychain
+   * Always false unless keychain caching has been enabled
    */
   hasKeychain: async () => {
     try {
@@ -68,6 +69,15 @@ export const encryptionApi = {
     return await invoke("setup_encryption", { passphrase });
   },
 
+  /**
+   * Encrypt a plaintext database from before encryption existed
+   * @param {string} passphrase - New passphrase (min 12 characters)
+   * @returns {string} Hex-encoded passphrase to pass to start_server_command
+   */
+  migratePlaintext: async (passphrase) => {
+    return await invoke("migrate_plaintext_database", { passphrase });
+  },
+
   /**
    * Unlock with passphrase
    * @param {string} passphrase - User's passphrase
@@ -88,11 +98,34 @@ export const encryptionApi = {
   },
 
   /**
-   * Clear keychain (no-op since we don't use keychain)
+   * Remove any cached key from the OS keychain
    */
   clearKeychain: async () => {
     return await invoke("clear_keychain");
   },
+
+  /**
+   * Whether keychain caching is enabled (off by default)
+   */
+  getKeychainCaching: async () => {
+    return await invoke("get_keychain_caching_enabled");
+  },
+
+  /**
+   * Opt in to or out of keychain caching; opting out clears the cached key
+   * @param {boolean} enabled
+   */
+  setKeychainCaching: async (enabled) => {
+    return await invoke("set_keychain_caching_enabled", { enabled });
+  },
+
+  /**
+   * Read the cached key from the OS keychain
+   * @returns {string} Hex-encoded passphrase to pass to send_passphrase_command
+   */
+  unlockWithKeychain: async () => {
+    return await invoke("unlock_with_keychain");
+  },
 };
 
 /**
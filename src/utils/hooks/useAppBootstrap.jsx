@@ -115,7 +115,7 @@ export const useAppBootstrap = () => {
 
                 if (!status.has_setup && !status.has_database) {
                     setShowEncryptionSetup(true);
-                } else if (status.has_setup && !status.has_keychain) {
+                } else if (status.has_setup) {
                     try {
                         await invoke("start_server_command");
                         console.log(